@@ -13,6 +13,7 @@ use std::error::Error;
 use std::fs;
 use std::fs::File;
 use std::io::{Read, Write};
+use std::time::Instant;
 
 use clap::Args;
 use miette::{bail, miette, IntoDiagnostic};
@@ -106,6 +107,7 @@ pub(crate) fn repl_main(args: ReplArgs) -> Result<(), Box<dyn Error>> {
     let mut rl = rustyline::Editor::<Indented, DefaultHistory>::new()?;
     let mut params = BTreeMap::new();
     let mut save_next: Option<String> = None;
+    let mut timing = false;
     rl.set_helper(Some(Indented));
 
     let history_file = ".cozo_repl_history";
@@ -117,7 +119,8 @@ pub(crate) fn repl_main(args: ReplArgs) -> Result<(), Box<dyn Error>> {
         let readline = rl.readline("=> ");
         match readline {
             Ok(line) => {
-                if let Err(err) = process_line(&line, &db, &mut params, &mut save_next) {
+                if let Err(err) = process_line(&line, &db, &mut params, &mut save_next, &mut timing)
+                {
                     eprintln!("{err:?}");
                 }
                 if let Err(err) = rl.add_history_entry(line) {
@@ -149,13 +152,17 @@ fn process_line(
     db: &DbInstance,
     params: &mut BTreeMap<String, DataValue>,
     save_next: &mut Option<String>,
+    timing: &mut bool,
 ) -> miette::Result<()> {
     let line = line.trim();
     if line.is_empty() {
         return Ok(());
     }
 
+    let started = Instant::now();
+    let show_timing = *timing;
     let mut process_out = |out: NamedRows| -> miette::Result<()> {
+        let elapsed = started.elapsed();
         if let Some(path) = save_next.as_ref() {
             println!(
                 "Query has returned {} rows, saving to file {}",
@@ -203,6 +210,9 @@ fn process_line(
             table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
             table.printstd();
         }
+        if show_timing {
+            println!("Took {:.3}s", elapsed.as_secs_f64());
+        }
         Ok(())
     };
 
@@ -234,6 +244,10 @@ fn process_line(
             "clear" => {
                 params.clear();
             }
+            "timing" => {
+                *timing = !*timing;
+                println!("Timing is {}", if *timing { "on" } else { "off" });
+            }
             "params" => {
                 let display = serde_json::to_string_pretty(&json!(&params)).into_diagnostic()?;
                 println!("{display}");
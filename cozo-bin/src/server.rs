@@ -258,10 +258,13 @@ pub(crate) async fn server_main(args: ServerArgs) {
         ) // +keep alive
         .route("/transact", post(start_transact))
         .route("/transact/:id", post(transact_query).put(finish_query))
+        .route("/relations", get(list_relations))
+        .route("/metrics", get(metrics))
         .with_state(state)
         .layer(AsyncRequireAuthorizationLayer::new(auth_obj))
         .fallback(not_found)
         .route("/", get(root))
+        .route("/health", get(health))
         .layer(cors)
         .layer(CompressionLayer::new())
         .layer(DefaultBodyLimit::disable());
@@ -400,6 +403,24 @@ async fn text_query(
     }
 }
 
+async fn list_relations(State(st): State<DbState>) -> (StatusCode, Json<serde_json::Value>) {
+    let result = spawn_blocking(move || {
+        st.db
+            .run_script_fold_err("::relations", Default::default(), ScriptMutability::Immutable)
+    })
+        .await;
+    match result {
+        Ok(res) => wrap_json(res),
+        Err(err) => internal_error(err),
+    }
+}
+
+async fn metrics(State(st): State<DbState>) -> (StatusCode, Json<serde_json::Value>) {
+    let mut ret = serde_json::to_value(st.db.metrics()).unwrap();
+    ret["ok"] = json!(true);
+    (StatusCode::OK, ret.into())
+}
+
 async fn export_relations(
     State(st): State<DbState>,
     Path(relations): Path<String>,
@@ -671,6 +692,10 @@ async fn root() -> Html<&'static str> {
     Html(include_str!("./index.html"))
 }
 
+async fn health() -> (StatusCode, Json<serde_json::Value>) {
+    (StatusCode::OK, json!({"ok": true}).into())
+}
+
 fn internal_error<E>(err: E) -> (StatusCode, Json<serde_json::Value>)
     where
         E: std::error::Error,
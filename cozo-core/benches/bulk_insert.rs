@@ -0,0 +1,113 @@
+/*
+ * Copyright 2022, The Cozo Project Authors.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public License, v. 2.0.
+ * If a copy of the MPL was not distributed with this file,
+ * You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+#![feature(test)]
+
+extern crate test;
+
+use std::collections::BTreeMap;
+
+use itertools::Itertools;
+use test::Bencher;
+
+use cozo::{DataValue, DbInstance, NamedRows, ScriptMutability};
+
+const N_ROWS: i64 = 10000;
+
+fn rows() -> Vec<Vec<DataValue>> {
+    (0..N_ROWS)
+        .map(|i| {
+            vec![
+                DataValue::from(i),
+                DataValue::from(format!("name_{i}")),
+                DataValue::from(i as f64 / 2.),
+            ]
+        })
+        .collect_vec()
+}
+
+fn fresh_db(with_index: bool) -> DbInstance {
+    let db = DbInstance::new("mem", "", "").unwrap();
+    db.run_script(
+        ":create plain {k: Int => name: String, score: Float}",
+        Default::default(),
+        ScriptMutability::Mutable,
+    )
+    .unwrap();
+    if with_index {
+        db.run_script(
+            "::index create plain:by_name {name}",
+            Default::default(),
+            ScriptMutability::Mutable,
+        )
+        .unwrap();
+    }
+    db
+}
+
+fn rows_param(rows: Vec<Vec<DataValue>>) -> BTreeMap<String, DataValue> {
+    BTreeMap::from([(
+        "rows".to_string(),
+        DataValue::List(rows.into_iter().map(DataValue::List).collect_vec()),
+    )])
+}
+
+fn put_rows(db: &DbInstance, params: BTreeMap<String, DataValue>) {
+    db.run_script(
+        "?[k, name, score] <- $rows :put plain {k => name, score}",
+        params,
+        ScriptMutability::Mutable,
+    )
+    .unwrap();
+}
+
+/// The rows with their keys moved past those of the earlier iterations, so that
+/// every iteration inserts new rows instead of overwriting existing ones.
+fn fresh_rows(rows: &[Vec<DataValue>], iteration: &mut i64) -> Vec<Vec<DataValue>> {
+    let offset = *iteration * N_ROWS;
+    *iteration += 1;
+    rows.iter()
+        .map(|row| {
+            let mut row = row.clone();
+            row[0] = DataValue::from(row[0].get_int().unwrap() + offset);
+            row
+        })
+        .collect_vec()
+}
+
+// The databases are created once, outside of the timed loop. The rows are copied
+// in each iteration, as `run_script` takes its parameters by value.
+
+#[bench]
+fn bulk_put(b: &mut Bencher) {
+    let rows = rows();
+    let db = fresh_db(false);
+    let mut iteration = 0;
+    b.iter(|| put_rows(&db, rows_param(fresh_rows(&rows, &mut iteration))))
+}
+
+#[bench]
+fn bulk_put_with_index(b: &mut Bencher) {
+    let rows = rows();
+    let db = fresh_db(true);
+    let mut iteration = 0;
+    b.iter(|| put_rows(&db, rows_param(fresh_rows(&rows, &mut iteration))))
+}
+
+#[bench]
+fn bulk_import(b: &mut Bencher) {
+    let headers = vec!["k".to_string(), "name".to_string(), "score".to_string()];
+    let rows = rows();
+    let db = fresh_db(false);
+    let mut iteration = 0;
+    b.iter(|| {
+        let data = NamedRows::new(headers.clone(), fresh_rows(&rows, &mut iteration));
+        db.import_relations(BTreeMap::from([("plain".to_string(), data)]))
+            .unwrap();
+    })
+}
@@ -0,0 +1,335 @@
+/*
+ * Copyright 2022, The Cozo Project Authors.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public License, v. 2.0.
+ * If a copy of the MPL was not distributed with this file,
+ * You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use std::collections::HashMap;
+#[cfg(feature = "parquet")]
+use std::io;
+use std::sync::Arc;
+
+use arrow_array::builder::{FixedSizeListBuilder, Float32Builder, Float64Builder};
+use arrow_array::cast::AsArray;
+use arrow_array::types::{
+    Float32Type, Float64Type, Int16Type, Int32Type, Int64Type, Int8Type, TimestampMicrosecondType,
+    TimestampMillisecondType, TimestampNanosecondType, TimestampSecondType, UInt16Type, UInt32Type,
+    UInt64Type, UInt8Type,
+};
+use arrow_array::{
+    Array, ArrayRef, BinaryArray, BooleanArray, FixedSizeBinaryArray, Float64Array, Int64Array,
+    NullArray, RecordBatch, StringArray,
+};
+use arrow_schema::{DataType, Field, Schema, TimeUnit};
+use itertools::Itertools;
+use miette::{bail, Diagnostic, IntoDiagnostic, Result};
+use ndarray::Array1;
+#[cfg(feature = "parquet")]
+use parquet::arrow::ArrowWriter;
+#[cfg(feature = "parquet")]
+use parquet::basic::Compression;
+#[cfg(feature = "parquet")]
+use parquet::file::properties::WriterProperties;
+use serde_json::Value as JsonValue;
+use thiserror::Error;
+
+use crate::data::relation::{ColType, VecElementType};
+use crate::data::tuple::Tuple;
+use crate::data::value::{DataValue, Vector};
+use crate::runtime::db::NamedRows;
+
+/// The key of the field metadata naming the Arrow extension type of a column.
+const EXTENSION_NAME_KEY: &str = "ARROW:extension:name";
+/// The canonical Arrow extension type for UUIDs, stored as 16 bytes.
+const UUID_EXTENSION_NAME: &str = "arrow.uuid";
+
+#[derive(Debug, Error, Diagnostic)]
+#[error("Cannot convert Arrow values of type {0}")]
+#[diagnostic(code(eval::unsupported_arrow_type))]
+pub(crate) struct UnsupportedArrowType(pub(crate) DataType);
+
+/// The Arrow type values of `coltype` are stored as.
+/// Types without a counterpart in Arrow are stored as their JSON text.
+pub(crate) fn arrow_type(coltype: &ColType) -> DataType {
+    match coltype {
+        ColType::Bool => DataType::Boolean,
+        ColType::Int => DataType::Int64,
+        ColType::Float => DataType::Float64,
+        ColType::String => DataType::Utf8,
+        ColType::Bytes => DataType::Binary,
+        ColType::Uuid => DataType::FixedSizeBinary(16),
+        ColType::Vec { eltype, len } => {
+            let eltype = match eltype {
+                VecElementType::F32 => DataType::Float32,
+                VecElementType::F64 => DataType::Float64,
+            };
+            DataType::FixedSizeList(Arc::new(Field::new("item", eltype, true)), *len as i32)
+        }
+        ColType::Any
+        | ColType::List { .. }
+        | ColType::Tuple(_)
+        | ColType::Validity
+        | ColType::Json => DataType::Utf8,
+    }
+}
+
+fn value_type(val: &DataValue) -> Option<ColType> {
+    Some(match val {
+        DataValue::Null => return None,
+        DataValue::Bool(_) => ColType::Bool,
+        DataValue::Num(n) => match n.get_int() {
+            Some(_) => ColType::Int,
+            None => ColType::Float,
+        },
+        DataValue::Str(_) => ColType::String,
+        DataValue::Bytes(_) => ColType::Bytes,
+        DataValue::Uuid(_) => ColType::Uuid,
+        DataValue::Vec(v) => ColType::Vec {
+            eltype: match v {
+                Vector::F32(_) => VecElementType::F32,
+                Vector::F64(_) => VecElementType::F64,
+            },
+            len: v.len(),
+        },
+        _ => ColType::Any,
+    })
+}
+
+/// The narrowest type of the values in the column, `None` if all of them are null.
+fn column_type<'a>(vals: impl Iterator<Item = &'a DataValue>) -> Option<ColType> {
+    let mut ret: Option<ColType> = None;
+    for typ in vals.filter_map(value_type) {
+        ret = Some(match ret {
+            None => typ,
+            Some(prev) if prev == typ => prev,
+            Some(ColType::Int | ColType::Float) if matches!(typ, ColType::Int | ColType::Float) => {
+                ColType::Float
+            }
+            Some(_) => return Some(ColType::Any),
+        });
+    }
+    ret
+}
+
+fn column_array(vals: &[&DataValue], coltype: &ColType) -> Result<ArrayRef> {
+    Ok(match coltype {
+        ColType::Bool => Arc::new(vals.iter().map(|v| v.get_bool()).collect::<BooleanArray>()),
+        ColType::Int => Arc::new(vals.iter().map(|v| v.get_int()).collect::<Int64Array>()),
+        ColType::Float => Arc::new(vals.iter().map(|v| v.get_float()).collect::<Float64Array>()),
+        ColType::String => Arc::new(vals.iter().map(|v| v.get_str()).collect::<StringArray>()),
+        ColType::Bytes => Arc::new(vals.iter().map(|v| v.get_bytes()).collect::<BinaryArray>()),
+        ColType::Uuid => Arc::new(
+            FixedSizeBinaryArray::try_from_sparse_iter_with_size(
+                vals.iter().map(|v| v.get_uuid().map(|u| u.into_bytes())),
+                16,
+            )
+            .into_diagnostic()?,
+        ),
+        ColType::Vec { eltype, len } => {
+            let len = *len;
+            match eltype {
+                VecElementType::F32 => {
+                    let mut builder = FixedSizeListBuilder::new(Float32Builder::new(), len as i32);
+                    for val in vals {
+                        match val {
+                            DataValue::Vec(Vector::F32(v)) => {
+                                builder.values().extend(v.iter().map(|x| Some(*x)));
+                                builder.append(true);
+                            }
+                            _ => {
+                                builder.values().append_nulls(len);
+                                builder.append(false);
+                            }
+                        }
+                    }
+                    Arc::new(builder.finish())
+                }
+                VecElementType::F64 => {
+                    let mut builder = FixedSizeListBuilder::new(Float64Builder::new(), len as i32);
+                    for val in vals {
+                        match val {
+                            DataValue::Vec(Vector::F64(v)) => {
+                                builder.values().extend(v.iter().map(|x| Some(*x)));
+                                builder.append(true);
+                            }
+                            _ => {
+                                builder.values().append_nulls(len);
+                                builder.append(false);
+                            }
+                        }
+                    }
+                    Arc::new(builder.finish())
+                }
+            }
+        }
+        _ => Arc::new(
+            vals.iter()
+                .map(|v| match v {
+                    DataValue::Null => None,
+                    v => Some(JsonValue::from((*v).clone()).to_string()),
+                })
+                .collect::<StringArray>(),
+        ),
+    })
+}
+
+/// The value at `idx` of `array`, timestamps are converted to seconds since the epoch.
+fn array_value(array: &dyn Array, idx: usize) -> Result<DataValue> {
+    if array.is_null(idx) {
+        return Ok(DataValue::Null);
+    }
+    Ok(match array.data_type() {
+        DataType::Null => DataValue::Null,
+        DataType::Boolean => DataValue::Bool(array.as_boolean().value(idx)),
+        DataType::Int8 => DataValue::from(array.as_primitive::<Int8Type>().value(idx) as i64),
+        DataType::Int16 => DataValue::from(array.as_primitive::<Int16Type>().value(idx) as i64),
+        DataType::Int32 => DataValue::from(array.as_primitive::<Int32Type>().value(idx) as i64),
+        DataType::Int64 => DataValue::from(array.as_primitive::<Int64Type>().value(idx)),
+        DataType::UInt8 => DataValue::from(array.as_primitive::<UInt8Type>().value(idx) as i64),
+        DataType::UInt16 => DataValue::from(array.as_primitive::<UInt16Type>().value(idx) as i64),
+        DataType::UInt32 => DataValue::from(array.as_primitive::<UInt32Type>().value(idx) as i64),
+        DataType::UInt64 => {
+            let v = array.as_primitive::<UInt64Type>().value(idx);
+            match i64::try_from(v) {
+                Ok(v) => DataValue::from(v),
+                Err(_) => DataValue::from(v as f64),
+            }
+        }
+        DataType::Float32 => DataValue::from(array.as_primitive::<Float32Type>().value(idx) as f64),
+        DataType::Float64 => DataValue::from(array.as_primitive::<Float64Type>().value(idx)),
+        DataType::Timestamp(unit, _) => DataValue::from(match unit {
+            TimeUnit::Second => array.as_primitive::<TimestampSecondType>().value(idx) as f64,
+            TimeUnit::Millisecond => {
+                array.as_primitive::<TimestampMillisecondType>().value(idx) as f64 / 1e3
+            }
+            TimeUnit::Microsecond => {
+                array.as_primitive::<TimestampMicrosecondType>().value(idx) as f64 / 1e6
+            }
+            TimeUnit::Nanosecond => {
+                array.as_primitive::<TimestampNanosecondType>().value(idx) as f64 / 1e9
+            }
+        }),
+        DataType::Utf8 => DataValue::from(array.as_string::<i32>().value(idx)),
+        DataType::LargeUtf8 => DataValue::from(array.as_string::<i64>().value(idx)),
+        DataType::Binary => DataValue::Bytes(array.as_binary::<i32>().value(idx).to_vec()),
+        DataType::LargeBinary => DataValue::Bytes(array.as_binary::<i64>().value(idx).to_vec()),
+        DataType::FixedSizeBinary(_) => {
+            DataValue::Bytes(array.as_fixed_size_binary().value(idx).to_vec())
+        }
+        DataType::FixedSizeList(field, _) => {
+            let vals = array.as_fixed_size_list().value(idx);
+            match field.data_type() {
+                DataType::Float32 if vals.null_count() == 0 => DataValue::Vec(Vector::F32(
+                    Array1::from_iter(vals.as_primitive::<Float32Type>().values().iter().copied()),
+                )),
+                DataType::Float64 if vals.null_count() == 0 => DataValue::Vec(Vector::F64(
+                    Array1::from_iter(vals.as_primitive::<Float64Type>().values().iter().copied()),
+                )),
+                _ => array_list(vals.as_ref())?,
+            }
+        }
+        DataType::List(_) => array_list(array.as_list::<i32>().value(idx).as_ref())?,
+        DataType::LargeList(_) => array_list(array.as_list::<i64>().value(idx).as_ref())?,
+        dt => bail!(UnsupportedArrowType(dt.clone())),
+    })
+}
+
+fn array_list(array: &dyn Array) -> Result<DataValue> {
+    Ok(DataValue::List(
+        (0..array.len())
+            .map(|i| array_value(array, i))
+            .try_collect()?,
+    ))
+}
+
+impl NamedRows {
+    /// Convert the rows into an Arrow record batch, with one nullable column for each header.
+    ///
+    /// The type of a column is the narrowest one holding all of its non-null values:
+    /// booleans, integers, floats, strings and bytes map to `Boolean`, `Int64`, `Float64`,
+    /// `Utf8` and `Binary`, UUIDs to the `arrow.uuid` extension type on `FixedSizeBinary(16)`,
+    /// and vectors of one length to a `FixedSizeList` of `Float32` or `Float64`.
+    /// Columns mixing integers and floats are `Float64`, columns containing only nulls
+    /// are `Null`. Any other column, e.g. one of lists or mixing strings and numbers,
+    /// is `Utf8` holding the JSON representation of every non-null value.
+    /// Only the current rows are converted, `next` is ignored.
+    pub fn to_record_batch(&self) -> Result<RecordBatch> {
+        let mut fields = Vec::with_capacity(self.headers.len());
+        let mut columns = Vec::with_capacity(self.headers.len());
+        for (i, header) in self.headers.iter().enumerate() {
+            let vals = self
+                .rows
+                .iter()
+                .map(|row| row.get(i).unwrap_or(&DataValue::Null))
+                .collect_vec();
+            let (field, column) = match column_type(vals.iter().copied()) {
+                None => (
+                    Field::new(header, DataType::Null, true),
+                    Arc::new(NullArray::new(vals.len())) as ArrayRef,
+                ),
+                Some(coltype) => {
+                    let mut field = Field::new(header, arrow_type(&coltype), true);
+                    if coltype == ColType::Uuid {
+                        field = field.with_metadata(HashMap::from([(
+                            EXTENSION_NAME_KEY.to_string(),
+                            UUID_EXTENSION_NAME.to_string(),
+                        )]));
+                    }
+                    (field, column_array(&vals, &coltype)?)
+                }
+            };
+            fields.push(field);
+            columns.push(column);
+        }
+        RecordBatch::try_new(Arc::new(Schema::new(fields)), columns).into_diagnostic()
+    }
+    /// Make named rows from an Arrow record batch, the headers are the names of the fields.
+    ///
+    /// This is the reverse of [Self::to_record_batch], except that JSON text stays a string.
+    /// Integers become integers unless they are unsigned and do not fit in 64-bit signed
+    /// integers, and timestamps become floats of the seconds since the epoch.
+    /// Fixed-size lists of floats without nulls become vectors, other lists become lists.
+    pub fn from_record_batch(batch: &RecordBatch) -> Result<Self> {
+        let schema = batch.schema();
+        let headers = schema
+            .fields()
+            .iter()
+            .map(|f| f.name().to_string())
+            .collect_vec();
+        let mut rows: Vec<Tuple> = (0..batch.num_rows())
+            .map(|_| Vec::with_capacity(headers.len()))
+            .collect();
+        for (field, column) in schema.fields().iter().zip(batch.columns()) {
+            let is_uuid = field.metadata().get(EXTENSION_NAME_KEY).map(|s| s.as_str())
+                == Some(UUID_EXTENSION_NAME);
+            for (idx, row) in rows.iter_mut().enumerate() {
+                let val = array_value(column.as_ref(), idx)?;
+                row.push(match val {
+                    DataValue::Bytes(b) if is_uuid => match uuid::Uuid::from_slice(&b) {
+                        Ok(u) => DataValue::uuid(u),
+                        Err(_) => DataValue::Bytes(b),
+                    },
+                    val => val,
+                });
+            }
+        }
+        Ok(NamedRows::new(headers, rows))
+    }
+    /// Write the rows as a Snappy-compressed Parquet file, with the columns of
+    /// [Self::to_record_batch]. Only the current rows are written, `next` is ignored.
+    /// To write the result of a query to a file, use [crate::Db::export_parquet].
+    #[cfg(feature = "parquet")]
+    pub fn write_parquet<W: io::Write + Send>(&self, writer: W) -> Result<()> {
+        let batch = self.to_record_batch()?;
+        let props = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .build();
+        let mut wtr =
+            ArrowWriter::try_new(writer, batch.schema(), Some(props)).into_diagnostic()?;
+        wtr.write(&batch).into_diagnostic()?;
+        wtr.close().into_diagnostic()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,134 @@
+/*
+ * Copyright 2022, The Cozo Project Authors.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public License, v. 2.0.
+ * If a copy of the MPL was not distributed with this file,
+ * You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use std::fmt::Display;
+
+use miette::Diagnostic;
+use serde::de::value::{MapDeserializer, SeqDeserializer};
+use serde::de::{self, Deserialize, Deserializer, IntoDeserializer, Visitor};
+use serde::forward_to_deserialize_any;
+use thiserror::Error;
+
+use crate::data::json::JsonValue;
+use crate::data::value::{DataValue, JsonData, Num, Vector};
+
+#[derive(Debug, Error, Diagnostic)]
+#[error("Cannot deserialize row: {0}")]
+#[diagnostic(code(eval::deserialize_row))]
+pub(crate) struct DeserializeError(String);
+
+impl de::Error for DeserializeError {
+    fn custom<T: Display>(msg: T) -> Self {
+        DeserializeError(msg.to_string())
+    }
+}
+
+/// Deserialize a row as a map from the headers to the values.
+pub(crate) fn deserialize_row<'de, T: Deserialize<'de>>(
+    headers: &[String],
+    row: Vec<DataValue>,
+) -> Result<T, DeserializeError> {
+    T::deserialize(MapDeserializer::new(
+        headers.iter().map(|h| h.as_str()).zip(row),
+    ))
+}
+
+/// Hands a value to the visitor as it is, without going through its JSON representation.
+pub(crate) struct ValueDeserializer(DataValue);
+
+impl<'de> IntoDeserializer<'de, DeserializeError> for DataValue {
+    type Deserializer = ValueDeserializer;
+
+    fn into_deserializer(self) -> ValueDeserializer {
+        ValueDeserializer(self)
+    }
+}
+
+fn visit_seq<'de, V, I>(visitor: V, items: I) -> Result<V::Value, DeserializeError>
+where
+    V: Visitor<'de>,
+    I: IntoIterator,
+    I::Item: IntoDeserializer<'de, DeserializeError>,
+{
+    let mut seq = SeqDeserializer::new(items.into_iter());
+    let ret = visitor.visit_seq(&mut seq)?;
+    seq.end()?;
+    Ok(ret)
+}
+
+impl<'de> Deserializer<'de> for ValueDeserializer {
+    type Error = DeserializeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            DataValue::Null | DataValue::Bot => visitor.visit_unit(),
+            DataValue::Bool(b) => visitor.visit_bool(b),
+            DataValue::Num(Num::Int(i)) => visitor.visit_i64(i),
+            DataValue::Num(Num::Float(f)) => visitor.visit_f64(f),
+            DataValue::Str(s) => visitor.visit_string(s.into()),
+            DataValue::Bytes(b) => visitor.visit_byte_buf(b),
+            DataValue::Uuid(u) => visitor.visit_string(u.0.to_string()),
+            DataValue::Regex(r) => visitor.visit_str(r.0.as_str()),
+            DataValue::List(l) => visit_seq(visitor, l),
+            DataValue::Set(s) => visit_seq(visitor, s),
+            DataValue::Vec(Vector::F32(a)) => visit_seq(visitor, a),
+            DataValue::Vec(Vector::F64(a)) => visit_seq(visitor, a),
+            DataValue::Validity(v) => visit_seq(
+                visitor,
+                [
+                    DataValue::from(v.timestamp.0 .0),
+                    DataValue::from(v.is_assert.0),
+                ],
+            ),
+            DataValue::Json(JsonData(j)) => j.deserialize_any(visitor).map_err(de::Error::custom),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            DataValue::Null | DataValue::Json(JsonData(JsonValue::Null)) => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            // so that bytes can be deserialized into `Vec<u8>`
+            DataValue::Bytes(b) => visit_seq(visitor, b),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            DataValue::Str(s) => visitor.visit_enum(String::from(s).into_deserializer()),
+            DataValue::Json(JsonData(j)) => j
+                .deserialize_enum(name, variants, visitor)
+                .map_err(de::Error::custom),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct tuple tuple_struct map struct identifier ignored_any
+    }
+}
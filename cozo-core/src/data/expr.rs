@@ -880,6 +880,10 @@ pub(crate) fn get_op(name: &str) -> Option<&'static Op> {
         "unicode_normalize" => &OP_UNICODE_NORMALIZE,
         "haversine" => &OP_HAVERSINE,
         "haversine_deg_input" => &OP_HAVERSINE_DEG_INPUT,
+        "geohash" => &OP_GEOHASH,
+        "geohash_cover" => &OP_GEOHASH_COVER,
+        "in_bbox" => &OP_IN_BBOX,
+        "distance_within" => &OP_DISTANCE_WITHIN,
         "deg_to_rad" => &OP_DEG_TO_RAD,
         "rad_to_deg" => &OP_RAD_TO_DEG,
         "get" => &OP_GET,
@@ -381,20 +381,29 @@ pub(crate) fn op_le(args: &[DataValue]) -> Result<DataValue> {
 
 define_op!(OP_ADD, 0, true);
 pub(crate) fn op_add(args: &[DataValue]) -> Result<DataValue> {
-    let mut i_accum = 0i64;
+    // `None` once the integers overflow
+    let mut i_accum = Some(0i64);
     let mut f_accum = 0.0f64;
+    let mut has_float = false;
     for arg in args {
         match arg {
-            DataValue::Num(Num::Int(i)) => i_accum += i,
-            DataValue::Num(Num::Float(f)) => f_accum += f,
+            DataValue::Num(Num::Int(i)) => i_accum = i_accum.and_then(|a| a.checked_add(*i)),
+            DataValue::Num(Num::Float(f)) => {
+                has_float = true;
+                f_accum += f
+            }
             DataValue::Vec(_) => return add_vecs(args),
             _ => bail!("addition requires numbers"),
         }
     }
-    if f_accum == 0.0f64 {
-        Ok(DataValue::Num(Num::Int(i_accum)))
-    } else {
-        Ok(DataValue::Num(Num::Float(i_accum as f64 + f_accum)))
+    match i_accum {
+        Some(i) if f_accum == 0.0f64 => Ok(DataValue::Num(Num::Int(i))),
+        Some(i) => Ok(DataValue::Num(Num::Float(i as f64 + f_accum))),
+        // with a float among the arguments, overflowing integers are summed as floats
+        None if has_float => Ok(DataValue::Num(Num::Float(
+            args.iter().map(|arg| arg.get_float().unwrap()).sum(),
+        ))),
+        None => bail!("integer overflow in addition"),
     }
 }
 
@@ -483,9 +492,10 @@ pub(crate) fn op_min(args: &[DataValue]) -> Result<DataValue> {
 define_op!(OP_SUB, 2, false);
 pub(crate) fn op_sub(args: &[DataValue]) -> Result<DataValue> {
     Ok(match (&args[0], &args[1]) {
-        (DataValue::Num(Num::Int(a)), DataValue::Num(Num::Int(b))) => {
-            DataValue::Num(Num::Int(*a - *b))
-        }
+        (DataValue::Num(Num::Int(a)), DataValue::Num(Num::Int(b))) => DataValue::Num(Num::Int(
+            a.checked_sub(*b)
+                .ok_or_else(|| miette!("integer overflow in subtraction"))?,
+        )),
         (DataValue::Num(Num::Float(a)), DataValue::Num(Num::Float(b))) => {
             DataValue::Num(Num::Float(*a - *b))
         }
@@ -543,20 +553,29 @@ pub(crate) fn op_sub(args: &[DataValue]) -> Result<DataValue> {
 
 define_op!(OP_MUL, 0, true);
 pub(crate) fn op_mul(args: &[DataValue]) -> Result<DataValue> {
-    let mut i_accum = 1i64;
+    // `None` once the integers overflow
+    let mut i_accum = Some(1i64);
     let mut f_accum = 1.0f64;
+    let mut has_float = false;
     for arg in args {
         match arg {
-            DataValue::Num(Num::Int(i)) => i_accum *= i,
-            DataValue::Num(Num::Float(f)) => f_accum *= f,
+            DataValue::Num(Num::Int(i)) => i_accum = i_accum.and_then(|a| a.checked_mul(*i)),
+            DataValue::Num(Num::Float(f)) => {
+                has_float = true;
+                f_accum *= f
+            }
             DataValue::Vec(_) => return mul_vecs(args),
             _ => bail!("multiplication requires numbers"),
         }
     }
-    if f_accum == 1.0f64 {
-        Ok(DataValue::Num(Num::Int(i_accum)))
-    } else {
-        Ok(DataValue::Num(Num::Float(i_accum as f64 * f_accum)))
+    match i_accum {
+        Some(i) if f_accum == 1.0f64 => Ok(DataValue::Num(Num::Int(i))),
+        Some(i) => Ok(DataValue::Num(Num::Float(i as f64 * f_accum))),
+        // with a float among the arguments, overflowing integers are multiplied as floats
+        None if has_float => Ok(DataValue::Num(Num::Float(
+            args.iter().map(|arg| arg.get_float().unwrap()).product(),
+        ))),
+        None => bail!("integer overflow in multiplication"),
     }
 }
 
@@ -670,7 +689,10 @@ pub(crate) fn op_div(args: &[DataValue]) -> Result<DataValue> {
 define_op!(OP_MINUS, 1, false);
 pub(crate) fn op_minus(args: &[DataValue]) -> Result<DataValue> {
     Ok(match &args[0] {
-        DataValue::Num(Num::Int(i)) => DataValue::Num(Num::Int(-(*i))),
+        DataValue::Num(Num::Int(i)) => DataValue::Num(Num::Int(
+            i.checked_neg()
+                .ok_or_else(|| miette!("integer overflow in negation"))?,
+        )),
         DataValue::Num(Num::Float(f)) => DataValue::Num(Num::Float(-(*f))),
         DataValue::Vec(Vector::F64(v)) => DataValue::Vec(Vector::F64(0. - v)),
         DataValue::Vec(Vector::F32(v)) => DataValue::Vec(Vector::F32(0. - v)),
@@ -681,7 +703,10 @@ pub(crate) fn op_minus(args: &[DataValue]) -> Result<DataValue> {
 define_op!(OP_ABS, 1, false);
 pub(crate) fn op_abs(args: &[DataValue]) -> Result<DataValue> {
     Ok(match &args[0] {
-        DataValue::Num(Num::Int(i)) => DataValue::Num(Num::Int(i.abs())),
+        DataValue::Num(Num::Int(i)) => DataValue::Num(Num::Int(
+            i.checked_abs()
+                .ok_or_else(|| miette!("integer overflow in 'abs'"))?,
+        )),
         DataValue::Num(Num::Float(f)) => DataValue::Num(Num::Float(f.abs())),
         DataValue::Vec(Vector::F64(v)) => DataValue::Vec(Vector::F64(v.mapv(|x| x.abs()))),
         DataValue::Vec(Vector::F32(v)) => DataValue::Vec(Vector::F32(v.mapv(|x| x.abs()))),
@@ -1073,7 +1098,8 @@ pub(crate) fn op_mod(args: &[DataValue]) -> Result<DataValue> {
             if *b == 0 {
                 bail!("'mod' requires non-zero divisor")
             }
-            DataValue::Num(Num::Int(a.rem(b)))
+            // `i64::MIN % -1` overflows, but the remainder is zero
+            DataValue::Num(Num::Int(a.wrapping_rem(*b)))
         }
         (DataValue::Num(Num::Float(a)), DataValue::Num(Num::Float(b))) => {
             DataValue::Num(Num::Float(a.rem(*b)))
@@ -1638,6 +1664,163 @@ pub(crate) fn op_haversine_deg_input(args: &[DataValue]) -> Result<DataValue> {
     Ok(DataValue::from(ret))
 }
 
+define_op!(OP_GEOHASH, 3, false);
+pub(crate) fn op_geohash(args: &[DataValue]) -> Result<DataValue> {
+    let miette = || miette!("'geohash' requires numbers");
+    let lat = args[0].get_float().ok_or_else(miette)?;
+    let lon = args[1].get_float().ok_or_else(miette)?;
+    let precision = args[2]
+        .get_int()
+        .ok_or_else(|| miette!("'geohash' requires an integer precision"))?;
+    ensure!(
+        (-90. ..=90.).contains(&lat) && (-180. ..=180.).contains(&lon),
+        "'geohash' requires latitude in [-90, 90] and longitude in [-180, 180]"
+    );
+    ensure!(
+        (1..=12).contains(&precision),
+        "'geohash' requires a precision between 1 and 12"
+    );
+    let hash = geohash_encode(lat, lon, precision as usize);
+    Ok(DataValue::from(hash))
+}
+
+fn geohash_encode(lat: f64, lon: f64, precision: usize) -> String {
+    const BASE32: &[u8] = b"0123456789bcdefghjkmnpqrstuvwxyz";
+    let mut lat_range = (-90., 90.);
+    let mut lon_range = (-180., 180.);
+    let mut ret = String::with_capacity(precision);
+    let mut even_bit = true;
+    for _ in 0..precision {
+        let mut idx = 0;
+        for _ in 0..5 {
+            // bits alternate between longitude and latitude, starting with longitude
+            let (range, val) = if even_bit {
+                (&mut lon_range, lon)
+            } else {
+                (&mut lat_range, lat)
+            };
+            let mid = (range.0 + range.1) / 2.;
+            idx <<= 1;
+            if val >= mid {
+                idx |= 1;
+                range.0 = mid;
+            } else {
+                range.1 = mid;
+            }
+            even_bit = !even_bit;
+        }
+        ret.push(BASE32[idx] as char);
+    }
+    ret
+}
+
+/// Reads the bounding box `min_lat, min_lon, max_lat, max_lon` from `args`, in degrees.
+/// The box wraps around the antimeridian if `min_lon > max_lon`.
+fn bounding_box(name: &str, args: &[DataValue]) -> Result<[f64; 4]> {
+    let mut ret = [0.; 4];
+    for (r, arg) in ret.iter_mut().zip(args) {
+        *r = arg
+            .get_float()
+            .ok_or_else(|| miette!("'{}' requires numbers", name))?;
+    }
+    let [min_lat, min_lon, max_lat, max_lon] = ret;
+    ensure!(
+        (-90. ..=90.).contains(&min_lat)
+            && (-90. ..=90.).contains(&max_lat)
+            && (-180. ..=180.).contains(&min_lon)
+            && (-180. ..=180.).contains(&max_lon),
+        "'{}' requires latitudes in [-90, 90] and longitudes in [-180, 180]",
+        name
+    );
+    ensure!(
+        min_lat <= max_lat,
+        "'{}' requires the minimum latitude to be at most the maximum one",
+        name
+    );
+    Ok(ret)
+}
+
+define_op!(OP_IN_BBOX, 6, false);
+pub(crate) fn op_in_bbox(args: &[DataValue]) -> Result<DataValue> {
+    let miette = || miette!("'in_bbox' requires numbers");
+    let lat = args[0].get_float().ok_or_else(miette)?;
+    let lon = args[1].get_float().ok_or_else(miette)?;
+    let [min_lat, min_lon, max_lat, max_lon] = bounding_box("in_bbox", &args[2..])?;
+    let in_lon = if min_lon <= max_lon {
+        min_lon <= lon && lon <= max_lon
+    } else {
+        min_lon <= lon || lon <= max_lon
+    };
+    Ok(DataValue::from(min_lat <= lat && lat <= max_lat && in_lon))
+}
+
+/// The most cells `geohash_cover` returns, so that a mistaken precision fails quickly.
+const MAX_GEOHASH_COVER: u64 = 4096;
+
+define_op!(OP_GEOHASH_COVER, 5, false);
+pub(crate) fn op_geohash_cover(args: &[DataValue]) -> Result<DataValue> {
+    let [min_lat, min_lon, max_lat, max_lon] = bounding_box("geohash_cover", &args[..4])?;
+    let precision = args[4]
+        .get_int()
+        .ok_or_else(|| miette!("'geohash_cover' requires an integer precision"))?;
+    ensure!(
+        (1..=12).contains(&precision),
+        "'geohash_cover' requires a precision between 1 and 12"
+    );
+    let bits = 5 * precision as u32;
+    // longitude gets the extra bit when the number of bits is odd
+    let lat_cells = 1u64 << (bits / 2);
+    let lon_cells = 1u64 << (bits - bits / 2);
+    let cell_lat = 180. / lat_cells as f64;
+    let cell_lon = 360. / lon_cells as f64;
+    let lat_idx = |lat: f64| (((lat + 90.) / cell_lat) as u64).min(lat_cells - 1);
+    let lon_idx = |lon: f64| (((lon + 180.) / cell_lon) as u64).min(lon_cells - 1);
+    let lat_range = lat_idx(min_lat)..=lat_idx(max_lat);
+    let lon_ranges = if min_lon <= max_lon {
+        vec![lon_idx(min_lon)..=lon_idx(max_lon)]
+    } else {
+        vec![lon_idx(min_lon)..=lon_cells - 1, 0..=lon_idx(max_lon)]
+    };
+    let n_cells = (lat_range.end() - lat_range.start() + 1)
+        * lon_ranges
+            .iter()
+            .map(|r| r.end() - r.start() + 1)
+            .sum::<u64>();
+    ensure!(
+        n_cells <= MAX_GEOHASH_COVER,
+        "'geohash_cover' would return {} cells, more than {}: use a lower precision",
+        n_cells,
+        MAX_GEOHASH_COVER
+    );
+    let mut ret = Vec::with_capacity(n_cells as usize);
+    for i in lat_range {
+        for j in lon_ranges.iter().flat_map(|r| r.clone()) {
+            // the center of the cell is well within it, away from rounding at its edges
+            let lat = -90. + (i as f64 + 0.5) * cell_lat;
+            let lon = -180. + (j as f64 + 0.5) * cell_lon;
+            let hash = geohash_encode(lat, lon, precision as usize);
+            ret.push(DataValue::from(hash));
+        }
+    }
+    ret.sort();
+    Ok(DataValue::List(ret))
+}
+
+/// The mean radius of the Earth in metres, as used by `distance_within`.
+const EARTH_RADIUS_METRES: f64 = 6_371_008.8;
+
+define_op!(OP_DISTANCE_WITHIN, 5, false);
+pub(crate) fn op_distance_within(args: &[DataValue]) -> Result<DataValue> {
+    let angle = op_haversine_deg_input(&args[..4])
+        .map_err(|_| miette!("'distance_within' requires numbers"))?;
+    let dist = args[4]
+        .get_float()
+        .ok_or_else(|| miette!("'distance_within' requires a number of metres"))?;
+    Ok(DataValue::from(
+        angle.get_float().unwrap() * EARTH_RADIUS_METRES <= dist,
+    ))
+}
+
 define_op!(OP_DEG_TO_RAD, 1, false);
 pub(crate) fn op_deg_to_rad(args: &[DataValue]) -> Result<DataValue> {
     let x = args[0]
@@ -163,31 +163,41 @@ pub(crate) trait MemCmpEncoder: Write {
     }
 }
 
+#[cfg(test)]
 pub fn decode_bytes(data: &[u8]) -> (Vec<u8>, &[u8]) {
+    try_decode_bytes(data).expect("malformed encoded bytes")
+}
+
+/// Decodes bytes written by [MemCmpEncoder::encode_bytes], returning `None` on malformed data.
+fn try_decode_bytes(data: &[u8]) -> Option<(Vec<u8>, &[u8])> {
     let mut key = Vec::with_capacity(data.len() / (ENC_GROUP_SIZE + 1) * ENC_GROUP_SIZE);
-    let mut offset = 0;
-    let chunk_len = ENC_GROUP_SIZE + 1;
+    let mut remaining = data;
     loop {
-        let next_offset = offset + chunk_len;
-        debug_assert!(next_offset <= data.len());
-        let chunk = &data[offset..next_offset];
-        offset = next_offset;
-
-        let (&marker, bytes) = chunk.split_last().unwrap();
-        let pad_size = (ENC_MARKER - marker) as usize;
-
+        let (chunk, rest) = try_split_at(remaining, ENC_GROUP_SIZE + 1)?;
+        remaining = rest;
+        let (&marker, bytes) = chunk.split_last()?;
+        let pad_size = ENC_MARKER.checked_sub(marker)? as usize;
         if pad_size == 0 {
-            key.write_all(bytes).unwrap();
+            key.extend_from_slice(bytes);
             continue;
         }
-        debug_assert!(pad_size <= ENC_GROUP_SIZE);
-
+        if pad_size > ENC_GROUP_SIZE {
+            return None;
+        }
         let (bytes, padding) = bytes.split_at(ENC_GROUP_SIZE - pad_size);
-        key.write_all(bytes).unwrap();
-
-        debug_assert!(!padding.iter().any(|x| *x != 0));
+        if padding.iter().any(|x| *x != 0) {
+            return None;
+        }
+        key.extend_from_slice(bytes);
+        return Some((key, remaining));
+    }
+}
 
-        return (key, &data[offset..]);
+fn try_split_at(bs: &[u8], mid: usize) -> Option<(&[u8], &[u8])> {
+    if bs.len() < mid {
+        None
+    } else {
+        Some(bs.split_at(mid))
     }
 }
 
@@ -224,65 +234,62 @@ const ENC_MARKER: u8 = b'\xff';
 const ENC_ASC_PADDING: [u8; ENC_GROUP_SIZE] = [0; ENC_GROUP_SIZE];
 
 impl Num {
+    #[cfg(test)]
     pub(crate) fn decode_from_key(bs: &[u8]) -> (Self, &[u8]) {
-        let (float_part, remaining) = bs.split_at(8);
-        let fu = BigEndian::read_u64(float_part);
-        let f = order_decode_f64(fu);
-        let (tag, remaining) = remaining.split_first().unwrap();
+        Self::try_decode_from_key(bs).expect("malformed encoded number")
+    }
+
+    /// Same as [Self::decode_from_key], but returns `None` instead of panicking on malformed data.
+    pub(crate) fn try_decode_from_key(bs: &[u8]) -> Option<(Self, &[u8])> {
+        let (float_part, remaining) = try_split_at(bs, 8)?;
+        let f = order_decode_f64(BigEndian::read_u64(float_part));
+        let (tag, remaining) = remaining.split_first()?;
         match *tag {
-            IS_FLOAT => (Num::Float(f), remaining),
-            IS_EXACT_INT => (Num::Int(f as i64), remaining),
+            IS_FLOAT => Some((Num::Float(f), remaining)),
+            IS_EXACT_INT => Some((Num::Int(f as i64), remaining)),
             IS_APPROX_INT => {
-                let (int_part, remaining) = remaining.split_at(8);
-                let iu = BigEndian::read_u64(int_part);
-                let i = order_decode_i64(iu);
-                (Num::Int(i), remaining)
+                let (int_part, remaining) = try_split_at(remaining, 8)?;
+                let i = order_decode_i64(BigEndian::read_u64(int_part));
+                Some((Num::Int(i), remaining))
             }
-            _ => unreachable!(),
+            _ => None,
         }
-        // if *tag == 0x80 {
-        //     return (Num::F(f), remaining);
-        // }
-        // let (subtag, remaining) = remaining.split_first().unwrap();
-        // let n = f as i64;
-        // let mut n_bytes = n.to_be_bytes();
-        // n_bytes[6] &= 0x80;
-        // n_bytes[6] |= tag;
-        // n_bytes[7] = *subtag;
-        // let n = BigEndian::read_i64(&n_bytes);
-        // (Num::I(n), remaining)
     }
 }
 
 impl DataValue {
     pub(crate) fn decode_from_key(bs: &[u8]) -> (Self, &[u8]) {
-        let (tag, remaining) = bs.split_first().unwrap();
-        match *tag {
+        Self::try_decode_from_key(bs).expect("malformed encoded value")
+    }
+
+    /// Same as [Self::decode_from_key], but returns `None` instead of panicking on malformed
+    /// data, for checking the integrity of stored data.
+    pub(crate) fn try_decode_from_key(bs: &[u8]) -> Option<(Self, &[u8])> {
+        let (tag, remaining) = bs.split_first()?;
+        Some(match *tag {
             NULL_TAG => (DataValue::Null, remaining),
             FALSE_TAG => (DataValue::from(false), remaining),
             TRUE_TAG => (DataValue::from(true), remaining),
             NUM_TAG => {
-                let (n, remaining) = Num::decode_from_key(remaining);
+                let (n, remaining) = Num::try_decode_from_key(remaining)?;
                 (DataValue::Num(n), remaining)
             }
             STR_TAG => {
-                let (bytes, remaining) = decode_bytes(remaining);
-                let s = unsafe { String::from_utf8_unchecked(bytes) };
+                let (bytes, remaining) = try_decode_bytes(remaining)?;
+                let s = String::from_utf8(bytes).ok()?;
                 (DataValue::Str(s.into()), remaining)
             }
             JSON_TAG => {
-                let (bytes, remaining) = decode_bytes(remaining);
-                (
-                    DataValue::Json(JsonData(serde_json::from_slice(&bytes).unwrap())),
-                    remaining,
-                )
+                let (bytes, remaining) = try_decode_bytes(remaining)?;
+                let json = serde_json::from_slice(&bytes).ok()?;
+                (DataValue::Json(JsonData(json)), remaining)
             }
             BYTES_TAG => {
-                let (bytes, remaining) = decode_bytes(remaining);
+                let (bytes, remaining) = try_decode_bytes(remaining)?;
                 (DataValue::Bytes(bytes), remaining)
             }
             UUID_TAG => {
-                let (uuid_data, remaining) = remaining.split_at(16);
+                let (uuid_data, remaining) = try_split_at(remaining, 16)?;
                 let s_h = BigEndian::read_u16(&uuid_data[0..2]);
                 let s_m = BigEndian::read_u16(&uuid_data[2..4]);
                 let s_l = BigEndian::read_u32(&uuid_data[4..8]);
@@ -292,79 +299,64 @@ impl DataValue {
                 (DataValue::Uuid(UuidWrapper(uuid)), remaining)
             }
             REGEX_TAG => {
-                let (bytes, remaining) = decode_bytes(remaining);
-                let s = unsafe { String::from_utf8_unchecked(bytes) };
-                (
-                    DataValue::Regex(RegexWrapper(Regex::from_str(&s).unwrap())),
-                    remaining,
-                )
+                let (bytes, remaining) = try_decode_bytes(remaining)?;
+                let s = String::from_utf8(bytes).ok()?;
+                let re = Regex::from_str(&s).ok()?;
+                (DataValue::Regex(RegexWrapper(re)), remaining)
             }
             LIST_TAG => {
                 let mut collected = vec![];
                 let mut remaining = remaining;
-                while remaining[0] != INIT_TAG {
-                    let (val, next_chunk) = DataValue::decode_from_key(remaining);
+                while *remaining.first()? != INIT_TAG {
+                    let (val, next_chunk) = DataValue::try_decode_from_key(remaining)?;
                     remaining = next_chunk;
                     collected.push(val);
                 }
                 (DataValue::List(collected), &remaining[1..])
             }
             SET_TAG => {
+                #[allow(clippy::mutable_key_type)]
                 let mut collected = BTreeSet::default();
                 let mut remaining = remaining;
-                while remaining[0] != INIT_TAG {
-                    let (val, next_chunk) = DataValue::decode_from_key(remaining);
+                while *remaining.first()? != INIT_TAG {
+                    let (val, next_chunk) = DataValue::try_decode_from_key(remaining)?;
                     remaining = next_chunk;
                     collected.insert(val);
                 }
                 (DataValue::Set(collected), &remaining[1..])
             }
             VLD_TAG => {
-                let (ts_flipped_bytes, rest) = remaining.split_at(8);
-                let ts_flipped = BigEndian::read_u64(ts_flipped_bytes);
-                let ts_u64 = !ts_flipped;
-                let ts = order_decode_i64(ts_u64);
-                let (is_assert_byte, rest) = rest.split_first().unwrap();
-                let is_assert = *is_assert_byte == 0;
+                let (ts_flipped_bytes, rest) = try_split_at(remaining, 8)?;
+                let ts = order_decode_i64(!BigEndian::read_u64(ts_flipped_bytes));
+                let (is_assert_byte, rest) = rest.split_first()?;
                 (
                     DataValue::Validity(Validity {
                         timestamp: ValidityTs(Reverse(ts)),
-                        is_assert: Reverse(is_assert),
+                        is_assert: Reverse(*is_assert_byte == 0),
                     }),
                     rest,
                 )
             }
             BOT_TAG => (DataValue::Bot, remaining),
             VEC_TAG => {
-                let (t_tag, remaining) = remaining.split_first().unwrap();
-                let (len_bytes, mut rest) = remaining.split_at(8);
+                let (t_tag, remaining) = remaining.split_first()?;
+                let (len_bytes, rest) = try_split_at(remaining, 8)?;
                 let len = BigEndian::read_u64(len_bytes) as usize;
-                match *t_tag {
-                    VEC_F32 => {
-                        let mut res_arr = ndarray::Array1::zeros(len);
-                        for mut row in res_arr.axis_iter_mut(ndarray::Axis(0)) {
-                            let (f_bytes, next_chunk) = rest.split_at(4);
-                            rest = next_chunk;
-                            let f = BigEndian::read_f32(f_bytes);
-                            row.fill(f);
-                        }
-                        (DataValue::Vec(Vector::F32(res_arr)), rest)
-                    }
-                    VEC_F64 => {
-                        let mut res_arr = ndarray::Array1::zeros(len);
-                        for mut row in res_arr.axis_iter_mut(ndarray::Axis(0)) {
-                            let (f_bytes, next_chunk) = rest.split_at(8);
-                            rest = next_chunk;
-                            let f = BigEndian::read_f64(f_bytes);
-                            row.fill(f);
-                        }
-                        (DataValue::Vec(Vector::F64(res_arr)), rest)
-                    }
-                    _ => unreachable!(),
-                }
+                let width = match *t_tag {
+                    VEC_F32 => 4,
+                    VEC_F64 => 8,
+                    _ => return None,
+                };
+                let (data, rest) = try_split_at(rest, len.checked_mul(width)?)?;
+                let vec = if width == 4 {
+                    Vector::F32(data.chunks(4).map(BigEndian::read_f32).collect())
+                } else {
+                    Vector::F64(data.chunks(8).map(BigEndian::read_f64).collect())
+                };
+                (DataValue::Vec(vec), rest)
             }
-            _ => unreachable!("{:?}", bs),
-        }
+            _ => return None,
+        })
     }
 }
 
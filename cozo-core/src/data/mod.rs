@@ -7,6 +7,9 @@
  */
 
 pub(crate) mod aggr;
+#[cfg(feature = "arrow")]
+pub(crate) mod arrow;
+pub(crate) mod de;
 pub(crate) mod expr;
 pub(crate) mod functions;
 pub(crate) mod json;
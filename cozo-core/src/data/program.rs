@@ -54,6 +54,11 @@ pub(crate) struct QueryOutOptions {
     pub(crate) sleep: Option<f64>,
     pub(crate) sorters: Vec<(Symbol, SortDir)>,
     pub(crate) store_relation: Option<(InputRelationHandle, RelationOp, ReturnMutation)>,
+    /// set by `:lenient`: nullable columns not given when writing rows are set to null
+    pub(crate) lenient: bool,
+    /// set by `:parallel`: whether rule branches may be evaluated in parallel,
+    /// overriding the setting of the database
+    pub(crate) parallel: Option<bool>,
     pub(crate) assertion: Option<QueryAssertion>,
 }
 
@@ -74,6 +79,9 @@ impl Display for QueryOutOptions {
         if let Some(l) = self.timeout {
             writeln!(f, ":timeout {l};")?;
         }
+        if let Some(p) = self.parallel {
+            writeln!(f, ":parallel {p};")?;
+        }
         for (symb, dir) in &self.sorters {
             write!(f, ":order ")?;
             if *dir == SortDir::Dsc {
@@ -96,6 +104,9 @@ impl Display for QueryOutOptions {
             if *return_mutation == ReturnMutation::Returning {
                 writeln!(f, ":returning")?;
             }
+            if self.lenient {
+                writeln!(f, ":lenient")?;
+            }
             match op {
                 RelationOp::Create => {
                     write!(f, ":create ")?;
@@ -626,6 +637,7 @@ impl InputProgram {
 
         Err(NoEntryError.into())
     }
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
     pub(crate) fn into_normalized_program(
         self,
         tx: &SessionTx<'_>,
@@ -681,7 +693,7 @@ impl InputProgram {
                                 aggr: rule.aggr.clone(),
                                 body,
                             };
-                            collected_rules.push(normalized_rule.convert_to_well_ordered_rule()?);
+                            collected_rules.push(normalized_rule.convert_to_well_ordered_rule(tx)?);
                         }
                     }
                     prog.insert(
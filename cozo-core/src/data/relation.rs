@@ -124,21 +124,14 @@ pub(crate) struct StoredRelationMetadata {
 }
 
 impl StoredRelationMetadata {
-    pub(crate) fn satisfied_by_required_col(&self, col: &ColumnDef) -> Result<()> {
-        for target in self.keys.iter().chain(self.non_keys.iter()) {
-            if target.name == col.name {
-                return Ok(());
-            }
-        }
-        if col.default_gen.is_none() {
-            #[derive(Debug, Error, Diagnostic)]
-            #[error("required column {0} not provided by input")]
-            #[diagnostic(code(eval::required_col_not_provided))]
-            struct ColumnNotProvided(String);
-
-            bail!(ColumnNotProvided(col.name.to_string()))
-        }
-        Ok(())
+    /// Whether a column is either given by the input or has a default.
+    pub(crate) fn satisfies_required_col(&self, col: &ColumnDef) -> bool {
+        col.default_gen.is_some()
+            || self
+                .keys
+                .iter()
+                .chain(self.non_keys.iter())
+                .any(|target| target.name == col.name)
     }
     pub(crate) fn compatible_with_col(&self, col: &ColumnDef) -> Result<()> {
         for target in self.keys.iter().chain(self.non_keys.iter()) {
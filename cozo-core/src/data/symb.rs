@@ -15,6 +15,7 @@ use miette::{bail, Diagnostic, Result};
 use serde_derive::{Deserialize, Serialize};
 use smartstring::{LazyCompact, SmartString};
 use thiserror::Error;
+use unicode_normalization::{is_nfc, UnicodeNormalization};
 
 use crate::parse::SourceSpan;
 
@@ -72,10 +73,23 @@ impl Debug for Symbol {
     }
 }
 
+/// Put an identifier in Unicode normalization form C, so that names typed with
+/// composed and with decomposed characters refer to the same thing.
+///
+/// Databases created by earlier versions stored names as they were typed; their catalog
+/// is normalized the first time they are opened, see `SessionTx::normalize_catalog_names`.
+pub(crate) fn normalize_ident(name: &str) -> SmartString<LazyCompact> {
+    if is_nfc(name) {
+        SmartString::from(name)
+    } else {
+        name.nfc().collect()
+    }
+}
+
 impl Symbol {
     pub(crate) fn new(name: impl Into<SmartString<LazyCompact>>, span: SourceSpan) -> Self {
         Self {
-            name: name.into(),
+            name: normalize_ident(&name.into()),
             span,
         }
     }
@@ -7,12 +7,13 @@
  */
 
 use approx::AbsDiffEq;
+use itertools::Itertools;
 use num_traits::FloatConst;
 use regex::Regex;
 use serde_json::json;
 
 use crate::data::functions::*;
-use crate::data::value::{DataValue, RegexWrapper};
+use crate::data::value::{DataValue, Num, RegexWrapper};
 use crate::DbInstance;
 
 #[test]
@@ -543,6 +544,47 @@ fn test_mod() {
     assert!(op_mod(&[DataValue::from(5.), DataValue::from(0.)]).is_ok());
     assert!(op_mod(&[DataValue::from(5.), DataValue::from(0)]).is_ok());
     assert!(op_mod(&[DataValue::from(5), DataValue::from(0)]).is_err());
+    assert_eq!(
+        op_mod(&[DataValue::from(i64::MIN), DataValue::from(-1)]).unwrap(),
+        DataValue::from(0)
+    );
+}
+
+#[test]
+fn test_int_overflow() {
+    let max = DataValue::from(i64::MAX);
+    let min = DataValue::from(i64::MIN);
+    assert!(op_add(&[max.clone(), DataValue::from(1)]).is_err());
+    assert!(op_sub(&[min.clone(), DataValue::from(1)]).is_err());
+    assert!(op_mul(&[max.clone(), DataValue::from(2)]).is_err());
+    assert!(op_minus(&[DataValue::from(i64::MIN)]).is_err());
+    assert!(op_abs(&[min]).is_err());
+    assert_eq!(
+        op_add(&[max.clone(), DataValue::from(1.)]).unwrap(),
+        DataValue::from(i64::MAX as f64 + 1.)
+    );
+    assert_eq!(
+        op_add(&[max.clone(), DataValue::from(-1), DataValue::from(1)]).unwrap(),
+        max
+    );
+    // with a float among the arguments, overflowing integers are computed as floats
+    assert_eq!(
+        op_add(&[max.clone(), DataValue::from(1), DataValue::from(0.5)]).unwrap(),
+        DataValue::from(i64::MAX as f64 + 1.5)
+    );
+    assert_eq!(
+        op_mul(&[max.clone(), DataValue::from(2), DataValue::from(1.)]).unwrap(),
+        DataValue::from(i64::MAX as f64 * 2.)
+    );
+    // without overflow, adding zero or multiplying by one keeps an integer
+    assert!(matches!(
+        op_add(&[DataValue::from(1), DataValue::from(0.)]).unwrap(),
+        DataValue::Num(Num::Int(1))
+    ));
+    assert!(matches!(
+        op_mul(&[max.clone(), DataValue::from(1.)]).unwrap(),
+        DataValue::Num(Num::Int(i64::MAX))
+    ));
 }
 
 #[test]
@@ -1028,6 +1070,153 @@ fn test_haversine() {
     assert!(d.abs_diff_eq(&f64::PI(), 1e-5));
 }
 
+#[test]
+fn test_geohash() {
+    assert_eq!(
+        op_geohash(&[
+            DataValue::from(57.64911),
+            DataValue::from(10.40744),
+            DataValue::from(11),
+        ])
+        .unwrap(),
+        DataValue::from("u4pruydqqvj")
+    );
+    assert_eq!(
+        op_geohash(&[DataValue::from(0), DataValue::from(0), DataValue::from(1)]).unwrap(),
+        DataValue::from("s")
+    );
+    assert!(op_geohash(&[DataValue::from(91), DataValue::from(0), DataValue::from(5)]).is_err());
+    assert!(op_geohash(&[DataValue::from(0), DataValue::from(0), DataValue::from(13)]).is_err());
+}
+
+#[test]
+fn test_in_bbox() {
+    let in_bbox = |lat: f64, lon: f64, bbox: [f64; 4]| {
+        let mut args = vec![DataValue::from(lat), DataValue::from(lon)];
+        args.extend(bbox.iter().map(|x| DataValue::from(*x)));
+        op_in_bbox(&args)
+    };
+    assert_eq!(
+        in_bbox(10., 20., [0., 10., 20., 30.]).unwrap(),
+        DataValue::from(true)
+    );
+    assert_eq!(
+        in_bbox(10., 40., [0., 10., 20., 30.]).unwrap(),
+        DataValue::from(false)
+    );
+    // wrapping around the antimeridian
+    assert_eq!(
+        in_bbox(0., 179., [-1., 170., 1., -170.]).unwrap(),
+        DataValue::from(true)
+    );
+    assert_eq!(
+        in_bbox(0., 0., [-1., 170., 1., -170.]).unwrap(),
+        DataValue::from(false)
+    );
+    assert!(in_bbox(0., 0., [1., 0., -1., 0.]).is_err());
+    assert!(in_bbox(0., 0., [0., 0., 91., 0.]).is_err());
+}
+
+#[test]
+fn test_geohash_cover() {
+    let cover = |bbox: [f64; 4], precision: i64| {
+        let mut args = bbox.iter().map(|x| DataValue::from(*x)).collect_vec();
+        args.push(DataValue::from(precision));
+        op_geohash_cover(&args)
+    };
+    assert_eq!(
+        cover([-1., -1., 1., 1.], 1).unwrap(),
+        DataValue::List(
+            ["7", "k", "s", "e"]
+                .into_iter()
+                .map(DataValue::from)
+                .sorted()
+                .collect()
+        )
+    );
+    // the cells of points inside the box are in the cover
+    let bbox = [57.6, 10.3, 57.7, 10.5];
+    let cells = cover(bbox, 5).unwrap();
+    let cells = cells.get_slice().unwrap();
+    for (lat, lon) in [
+        (57.64911, 10.40744),
+        (57.6, 10.3),
+        (57.7, 10.5),
+        (57.61, 10.49),
+    ] {
+        let hash = op_geohash(&[
+            DataValue::from(lat),
+            DataValue::from(lon),
+            DataValue::from(5),
+        ])
+        .unwrap();
+        assert!(cells.contains(&hash));
+    }
+    // wrapping around the antimeridian
+    let cells = cover([-1., 179., 1., -179.], 2).unwrap();
+    let cells = cells.get_slice().unwrap();
+    for lon in [179.5, -179.5] {
+        let hash =
+            op_geohash(&[DataValue::from(0), DataValue::from(lon), DataValue::from(2)]).unwrap();
+        assert!(cells.contains(&hash));
+    }
+    assert!(cover([-90., -180., 90., 180.], 3).is_err());
+
+    let db = DbInstance::default();
+    db.run_default(
+        r"
+        ?[cell, name, lat, lon] <- [['', 'Aalborg', 57.0488, 9.9217],
+                                    ['', 'Skagen', 57.7209, 10.5839],
+                                    ['', 'Paris', 48.8566, 2.3522]]
+        :create places {cell, name => lat, lon}
+    ",
+    )
+    .unwrap();
+    db.run_default(
+        r"
+        ?[cell, name, lat, lon] := *places{name, lat, lon}, cell = geohash(lat, lon, 4)
+        :replace places {cell, name => lat, lon}
+    ",
+    )
+    .unwrap();
+    let res = db
+        .run_default(
+            r"
+        ?[name] := cell in geohash_cover(56, 9, 58, 11, 4),
+                   *places{cell, name, lat, lon},
+                   in_bbox(lat, lon, 56, 9, 58, 11)
+    ",
+        )
+        .unwrap()
+        .into_json();
+    assert_eq!(res["rows"], json!([["Aalborg"], ["Skagen"]]));
+}
+
+#[test]
+fn test_distance_within() {
+    let within = |dist: f64| {
+        op_distance_within(&[
+            DataValue::from(48.8566),
+            DataValue::from(2.3522),
+            DataValue::from(51.5074),
+            DataValue::from(-0.1278),
+            DataValue::from(dist),
+        ])
+        .unwrap()
+    };
+    // Paris and London are about 344 km apart
+    assert_eq!(within(345_000.), DataValue::from(true));
+    assert_eq!(within(343_000.), DataValue::from(false));
+    assert!(op_distance_within(&[
+        DataValue::from(0),
+        DataValue::from(0),
+        DataValue::from(0),
+        DataValue::from(0),
+        DataValue::from("far"),
+    ])
+    .is_err());
+}
+
 #[test]
 fn test_deg_rad() {
     assert_eq!(
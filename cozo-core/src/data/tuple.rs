@@ -49,6 +49,18 @@ pub fn decode_tuple_from_key(key: &[u8], size_hint: usize) -> Tuple {
     ret
 }
 
+/// Same as [decode_tuple_from_key], but returns `None` instead of panicking on malformed data.
+pub(crate) fn try_decode_tuple_from_key(key: &[u8], size_hint: usize) -> Option<Tuple> {
+    let mut remaining = key.get(ENCODED_KEY_MIN_LEN..)?;
+    let mut ret = Vec::with_capacity(size_hint);
+    while !remaining.is_empty() {
+        let (val, next) = DataValue::try_decode_from_key(remaining)?;
+        ret.push(val);
+        remaining = next;
+    }
+    Some(ret)
+}
+
 const DEFAULT_SIZE_HINT: usize = 16;
 
 /// Check if the tuple key passed in should be a valid return for a validity query.
@@ -827,6 +827,11 @@ lazy_static! {
                 "CsvReader".to_string(),
                 Arc::<Box<dyn FixedRule>>::new(Box::new(CsvReader)),
             ),
+            #[cfg(feature = "parquet")]
+            (
+                "ParquetReader".to_string(),
+                Arc::<Box<dyn FixedRule>>::new(Box::new(ParquetReader)),
+            ),
             (
                 "Constant".to_string(),
                 Arc::<Box<dyn FixedRule>>::new(Box::new(Constant)),
@@ -9,6 +9,7 @@
 use std::collections::BTreeMap;
 
 use csv::StringRecord;
+use log::warn;
 use miette::{bail, ensure, IntoDiagnostic, Result};
 use smartstring::{LazyCompact, SmartString};
 
@@ -48,6 +49,16 @@ impl FixedRule for CsvReader {
         let delimiter = delimiter[0];
         let prepend_index = payload.bool_option("prepend_index", Some(false))?;
         let has_headers = payload.bool_option("has_headers", Some(true))?;
+        let skip_bad_rows = match payload.string_option("on_error", Some("fail"))?.as_str() {
+            "fail" => false,
+            "skip" => true,
+            _ => bail!(WrongFixedRuleOptionError {
+                name: "on_error".to_string(),
+                span: payload.span(),
+                rule_name: "CsvReader".to_string(),
+                help: "'on_error' must be 'fail' or 'skip'".to_string()
+            }),
+        };
         let types_opts = payload.expr_option("types", None)?.eval_to_const()?;
         let typing = NullableColType {
             coltype: ColType::List {
@@ -87,8 +98,8 @@ impl FixedRule for CsvReader {
         let mut process_row = |row: StringRecord| -> Result<()> {
             let mut out_tuple = Vec::with_capacity(out_tuple_size);
             if prepend_index {
-                counter += 1;
-                out_tuple.push(DataValue::from(counter));
+                // filled in once the row is converted, so that skipped rows take no index
+                out_tuple.push(DataValue::Null);
             }
             for (i, typ) in types.iter().enumerate() {
                 match row.get(i) {
@@ -143,17 +154,39 @@ impl FixedRule for CsvReader {
                     }
                 }
             }
+            if prepend_index {
+                counter += 1;
+                out_tuple[0] = DataValue::from(counter);
+            }
             out.put(out_tuple);
             Ok(())
         };
+        // rows are only put once fully converted, so a skipped row leaves nothing behind
+        let mut n_skipped = 0usize;
+        let mut process_record = |record: csv::Result<StringRecord>| -> Result<()> {
+            let record = match record {
+                // failing to read the file is never a problem of a single row
+                Err(err) if err.is_io_error() => return Err(err).into_diagnostic(),
+                record => record,
+            };
+            match record.into_diagnostic().and_then(&mut process_row) {
+                Err(err) if skip_bad_rows => {
+                    if n_skipped == 0 {
+                        warn!("CsvReader skipping bad row: {}", err);
+                    }
+                    n_skipped += 1;
+                    Ok(())
+                }
+                res => res,
+            }
+        };
 
         let url = payload.string_option("url", None)?;
         match url.strip_prefix("file://") {
             Some(file_path) => {
                 let mut rdr = rdr_builder.from_path(file_path).into_diagnostic()?;
                 for record in rdr.records() {
-                    let record = record.into_diagnostic()?;
-                    process_row(record)?;
+                    process_record(record)?;
                 }
             }
             None => {
@@ -162,14 +195,16 @@ impl FixedRule for CsvReader {
                     let content = get_file_content_from_url(&url)?;
                     let mut rdr = rdr_builder.from_reader(content.as_bytes());
                     for record in rdr.records() {
-                        let record = record.into_diagnostic()?;
-                        process_row(record)?;
+                        process_record(record)?;
                     }
                 }
                 #[cfg(not(feature = "requests"))]
                 bail!("the feature `requests` is not enabled for the build")
             }
         }
+        if n_skipped > 0 {
+            warn!("CsvReader skipped {} bad rows of {}", n_skipped, url);
+        }
         Ok(())
     }
 
@@ -9,9 +9,13 @@
 pub(crate) mod constant;
 pub(crate) mod csv;
 pub(crate) mod jlines;
+#[cfg(feature = "parquet")]
+pub(crate) mod parquet;
 pub(crate) mod reorder_sort;
 
 pub(crate) use self::csv::CsvReader;
+#[cfg(feature = "parquet")]
+pub(crate) use self::parquet::ParquetReader;
 pub(crate) use constant::Constant;
 pub(crate) use jlines::JsonReader;
 pub(crate) use reorder_sort::ReorderSort;
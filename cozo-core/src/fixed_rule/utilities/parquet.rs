@@ -0,0 +1,137 @@
+/*
+ * Copyright 2022, The Cozo Project Authors.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public License, v. 2.0.
+ * If a copy of the MPL was not distributed with this file,
+ * You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use std::collections::BTreeMap;
+use std::fs;
+
+use miette::{bail, miette, IntoDiagnostic, Result};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::arrow::ProjectionMask;
+use smartstring::{LazyCompact, SmartString};
+
+use crate::data::expr::Expr;
+use crate::data::program::{FixedRuleOptionNotFoundError, WrongFixedRuleOptionError};
+use crate::data::symb::Symbol;
+use crate::data::value::DataValue;
+use crate::fixed_rule::{CannotDetermineArity, FixedRule, FixedRulePayload};
+use crate::parse::SourceSpan;
+use crate::runtime::db::{NamedRows, Poison};
+use crate::runtime::temp_store::RegularTempStore;
+
+pub(crate) struct ParquetReader;
+
+impl FixedRule for ParquetReader {
+    fn run(
+        &self,
+        payload: FixedRulePayload<'_, '_>,
+        out: &mut RegularTempStore,
+        poison: Poison,
+    ) -> Result<()> {
+        let url = payload.string_option("url", None)?;
+        // Parquet files are read by seeking to their footer first, so they must be local
+        let Some(file_path) = url.strip_prefix("file://") else {
+            bail!(WrongFixedRuleOptionError {
+                name: "url".to_string(),
+                span: payload.span(),
+                rule_name: "ParquetReader".to_string(),
+                help: "only 'file://' URLs are supported".to_string()
+            })
+        };
+        let columns = payload.expr_option("columns", None)?.eval_to_const()?;
+        let columns: Vec<&str> = columns
+            .get_slice()
+            .and_then(|l| l.iter().map(|c| c.get_str()).collect())
+            .ok_or_else(|| WrongFixedRuleOptionError {
+                name: "columns".to_string(),
+                span: payload.span(),
+                rule_name: "ParquetReader".to_string(),
+                help: "'columns' must be a list of strings".to_string(),
+            })?;
+        let row_groups = payload
+            .expr_option(
+                "row_groups",
+                Some(Expr::Const {
+                    val: DataValue::Null,
+                    span: payload.span(),
+                }),
+            )?
+            .eval_to_const()?;
+
+        let file = fs::File::open(file_path).into_diagnostic()?;
+        let mut builder = ParquetRecordBatchReaderBuilder::try_new(file).into_diagnostic()?;
+        let mut indices = Vec::with_capacity(columns.len());
+        for col in &columns {
+            let idx = builder
+                .schema()
+                .index_of(col)
+                .map_err(|_| miette!("column '{}' not found in {}", col, url))?;
+            indices.push(idx);
+        }
+        let mask = ProjectionMask::roots(builder.parquet_schema(), indices);
+        builder = builder.with_projection(mask);
+        if row_groups != DataValue::Null {
+            let n_groups = builder.metadata().num_row_groups();
+            let row_groups: Vec<usize> = row_groups
+                .get_slice()
+                .and_then(|l| {
+                    l.iter()
+                        .map(|g| g.get_non_neg_int().map(|g| g as usize))
+                        .collect()
+                })
+                .filter(|groups: &Vec<usize>| groups.iter().all(|g| *g < n_groups))
+                .ok_or_else(|| WrongFixedRuleOptionError {
+                    name: "row_groups".to_string(),
+                    span: payload.span(),
+                    rule_name: "ParquetReader".to_string(),
+                    help: format!(
+                        "'row_groups' must be a list of indices, and {} has {} row groups",
+                        url, n_groups
+                    ),
+                })?;
+            builder = builder.with_row_groups(row_groups);
+        }
+
+        for batch in builder.build().into_diagnostic()? {
+            let rows = NamedRows::from_record_batch(&batch.into_diagnostic()?)?;
+            // the projected columns are in the order of the file
+            let positions = columns
+                .iter()
+                .map(|col| rows.headers.iter().position(|h| h == col).unwrap())
+                .collect::<Vec<_>>();
+            for row in rows.rows {
+                out.put(positions.iter().map(|i| row[*i].clone()).collect());
+            }
+            poison.check()?;
+        }
+        Ok(())
+    }
+
+    fn arity(
+        &self,
+        options: &BTreeMap<SmartString<LazyCompact>, Expr>,
+        _rule_head: &[Symbol],
+        span: SourceSpan,
+    ) -> Result<usize> {
+        let columns = options
+            .get("columns")
+            .ok_or_else(|| FixedRuleOptionNotFoundError {
+                name: "columns".to_string(),
+                span,
+                rule_name: "ParquetReader".to_string(),
+            })?;
+        let columns = columns.clone().eval_to_const()?;
+        if let Some(l) = columns.get_slice() {
+            return Ok(l.len());
+        }
+        bail!(CannotDetermineArity(
+            "ParquetReader".to_string(),
+            "invalid option 'columns' given, expect a list".to_string(),
+            span
+        ))
+    }
+}
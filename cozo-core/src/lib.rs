@@ -46,12 +46,19 @@ use miette::{
     bail, miette, GraphicalReportHandler, GraphicalTheme, IntoDiagnostic, JSONReportHandler,
     Result, ThemeCharacters, ThemeStyles,
 };
+use serde::de::DeserializeOwned;
 use serde_json::json;
 
 pub use data::value::{DataValue, Num, RegexWrapper, UuidWrapper, Validity, ValidityTs};
 pub use fixed_rule::{FixedRule, FixedRuleInputRelation, FixedRulePayload};
+pub use runtime::db::CsvOptions;
 pub use runtime::db::Db;
+pub use runtime::db::DbInfo;
+pub use runtime::db::DbMetrics;
+pub use runtime::db::DbVerification;
+pub use runtime::db::MAX_VERIFY_PROBLEMS;
 pub use runtime::db::NamedRows;
+pub use runtime::db::RowSink;
 pub use runtime::relation::decode_tuple_from_kv;
 pub use runtime::temp_store::RegularTempStore;
 pub use storage::mem::{new_cozo_mem, MemStorage};
@@ -135,6 +142,10 @@ impl DbInstance {
     ///
     /// `path` is ignored for `mem` and `tikv` engines.
     /// `options` is ignored for every engine except `tikv`.
+    ///
+    /// Names of relations and columns are put in Unicode normalization form C. Opening a
+    /// database created by an earlier version renames those stored in another form,
+    /// see [Db::initialize].
     #[allow(unused_variables)]
     pub fn new(engine: &str, path: impl AsRef<Path>, options: &str) -> Result<Self> {
         let options = if options.is_empty() { "{}" } else { options };
@@ -189,10 +200,158 @@ impl DbInstance {
             DbInstance::TiKv(db) => db.run_script(payload, params, mutability),
         }
     }
+    /// Dispatcher method. See [crate::Db::run_script_with_sink].
+    pub fn run_script_with_sink(
+        &self,
+        payload: &str,
+        params: BTreeMap<String, DataValue>,
+        mutability: ScriptMutability,
+        sink: &mut dyn RowSink,
+    ) -> Result<()> {
+        match self {
+            DbInstance::Mem(db) => db.run_script_with_sink(payload, params, mutability, sink),
+            #[cfg(feature = "storage-sqlite")]
+            DbInstance::Sqlite(db) => db.run_script_with_sink(payload, params, mutability, sink),
+            #[cfg(feature = "storage-rocksdb")]
+            DbInstance::RocksDb(db) => db.run_script_with_sink(payload, params, mutability, sink),
+            #[cfg(feature = "storage-sled")]
+            DbInstance::Sled(db) => db.run_script_with_sink(payload, params, mutability, sink),
+            #[cfg(feature = "storage-tikv")]
+            DbInstance::TiKv(db) => db.run_script_with_sink(payload, params, mutability, sink),
+        }
+    }
+    /// Dispatcher method. See [crate::Db::export_csv].
+    pub fn export_csv(
+        &self,
+        payload: &str,
+        params: BTreeMap<String, DataValue>,
+        out_file: impl AsRef<Path>,
+        options: &CsvOptions,
+    ) -> Result<()> {
+        match self {
+            DbInstance::Mem(db) => db.export_csv(payload, params, out_file, options),
+            #[cfg(feature = "storage-sqlite")]
+            DbInstance::Sqlite(db) => db.export_csv(payload, params, out_file, options),
+            #[cfg(feature = "storage-rocksdb")]
+            DbInstance::RocksDb(db) => db.export_csv(payload, params, out_file, options),
+            #[cfg(feature = "storage-sled")]
+            DbInstance::Sled(db) => db.export_csv(payload, params, out_file, options),
+            #[cfg(feature = "storage-tikv")]
+            DbInstance::TiKv(db) => db.export_csv(payload, params, out_file, options),
+        }
+    }
+    /// Dispatcher method. See [crate::Db::export_parquet].
+    #[cfg(feature = "parquet")]
+    pub fn export_parquet(
+        &self,
+        payload: &str,
+        params: BTreeMap<String, DataValue>,
+        out_file: impl AsRef<Path>,
+    ) -> Result<()> {
+        match self {
+            DbInstance::Mem(db) => db.export_parquet(payload, params, out_file),
+            #[cfg(feature = "storage-sqlite")]
+            DbInstance::Sqlite(db) => db.export_parquet(payload, params, out_file),
+            #[cfg(feature = "storage-rocksdb")]
+            DbInstance::RocksDb(db) => db.export_parquet(payload, params, out_file),
+            #[cfg(feature = "storage-sled")]
+            DbInstance::Sled(db) => db.export_parquet(payload, params, out_file),
+            #[cfg(feature = "storage-tikv")]
+            DbInstance::TiKv(db) => db.export_parquet(payload, params, out_file),
+        }
+    }
     /// `run_script` with mutable script and no parameters
     pub fn run_default(&self, payload: &str) -> Result<NamedRows> {
         self.run_script(payload, BTreeMap::new(), ScriptMutability::Mutable)
     }
+    /// Same as [Self::run_script], but runs on the blocking thread pool of the tokio runtime,
+    /// so that long-running queries do not block the async executor.
+    /// Must be called from within a tokio runtime.
+    #[cfg(feature = "async")]
+    pub async fn run_script_async(
+        &self,
+        payload: String,
+        params: BTreeMap<String, DataValue>,
+        mutability: ScriptMutability,
+    ) -> Result<NamedRows> {
+        let db = self.clone();
+        tokio::task::spawn_blocking(move || db.run_script(&payload, params, mutability))
+            .await
+            .into_diagnostic()?
+    }
+    /// Same as [Self::run_script_with_sink], but runs on the blocking thread pool of
+    /// the tokio runtime and sends the rows through a channel holding at most `buffer` rows.
+    /// The query waits while the channel is full, so rows are produced as the receiver
+    /// takes them. Wrap the receiver in `tokio_stream::wrappers::ReceiverStream` to use it
+    /// as a `Stream`. See [crate::Db::run_script_with_sink] for which scripts are streamed.
+    ///
+    /// Errors raised before the headers are known are returned directly, later ones are
+    /// sent as the last item of the channel. Dropping the receiver aborts the query.
+    /// Must be called from within a tokio runtime.
+    #[cfg(feature = "async")]
+    pub async fn run_script_stream(
+        &self,
+        payload: String,
+        params: BTreeMap<String, DataValue>,
+        mutability: ScriptMutability,
+        buffer: usize,
+    ) -> Result<(
+        Vec<String>,
+        tokio::sync::mpsc::Receiver<Result<Vec<DataValue>>>,
+    )> {
+        struct ChannelSink {
+            headers: Option<tokio::sync::oneshot::Sender<Result<Vec<String>>>>,
+            rows: tokio::sync::mpsc::Sender<Result<Vec<DataValue>>>,
+        }
+
+        impl RowSink for ChannelSink {
+            fn headers(&mut self, headers: &[String]) -> Result<()> {
+                if let Some(sender) = self.headers.take() {
+                    sender
+                        .send(Ok(headers.to_vec()))
+                        .map_err(|_| miette!("the receiver of the stream was dropped"))?;
+                }
+                Ok(())
+            }
+            fn row(&mut self, row: Vec<DataValue>) -> Result<()> {
+                self.rows
+                    .blocking_send(Ok(row))
+                    .map_err(|_| miette!("the receiver of the stream was dropped"))
+            }
+        }
+
+        let (headers_sender, headers_receiver) = tokio::sync::oneshot::channel();
+        let (sender, receiver) = tokio::sync::mpsc::channel(buffer.max(1));
+        let db = self.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut sink = ChannelSink {
+                headers: Some(headers_sender),
+                rows: sender,
+            };
+            if let Err(err) = db.run_script_with_sink(&payload, params, mutability, &mut sink) {
+                match sink.headers.take() {
+                    Some(headers_sender) => {
+                        let _ = headers_sender.send(Err(err));
+                    }
+                    None => {
+                        let _ = sink.rows.blocking_send(Err(err));
+                    }
+                }
+            }
+        });
+        let headers = headers_receiver.await.into_diagnostic()??;
+        Ok((headers, receiver))
+    }
+    /// `run_script`, with the resulting rows deserialized into `T`.
+    /// See [NamedRows::into_structs] for how values are converted.
+    pub fn run_as<T: DeserializeOwned>(
+        &self,
+        payload: &str,
+        params: BTreeMap<String, DataValue>,
+        mutability: ScriptMutability,
+    ) -> Result<Vec<T>> {
+        self.run_script(payload, params, mutability)?.into_structs()
+    }
     /// Run the CozoScript passed in. The `params` argument is a map of parameters.
     /// Fold any error into the return JSON itself.
     /// See [crate::Db::run_script].
@@ -248,6 +407,62 @@ impl DbInstance {
         )
             .to_string()
     }
+    /// Dispatcher method. See [crate::Db::metrics].
+    pub fn metrics(&self) -> DbMetrics {
+        match self {
+            DbInstance::Mem(db) => db.metrics(),
+            #[cfg(feature = "storage-sqlite")]
+            DbInstance::Sqlite(db) => db.metrics(),
+            #[cfg(feature = "storage-rocksdb")]
+            DbInstance::RocksDb(db) => db.metrics(),
+            #[cfg(feature = "storage-sled")]
+            DbInstance::Sled(db) => db.metrics(),
+            #[cfg(feature = "storage-tikv")]
+            DbInstance::TiKv(db) => db.metrics(),
+        }
+    }
+    /// Dispatcher method. See [crate::Db::set_parallel_branches].
+    pub fn set_parallel_branches(&self, enabled: bool) {
+        match self {
+            DbInstance::Mem(db) => db.set_parallel_branches(enabled),
+            #[cfg(feature = "storage-sqlite")]
+            DbInstance::Sqlite(db) => db.set_parallel_branches(enabled),
+            #[cfg(feature = "storage-rocksdb")]
+            DbInstance::RocksDb(db) => db.set_parallel_branches(enabled),
+            #[cfg(feature = "storage-sled")]
+            DbInstance::Sled(db) => db.set_parallel_branches(enabled),
+            #[cfg(feature = "storage-tikv")]
+            DbInstance::TiKv(db) => db.set_parallel_branches(enabled),
+        }
+    }
+    /// Dispatcher method. See [crate::Db::info].
+    pub fn info(&self) -> Result<DbInfo> {
+        match self {
+            DbInstance::Mem(db) => db.info(),
+            #[cfg(feature = "storage-sqlite")]
+            DbInstance::Sqlite(db) => db.info(),
+            #[cfg(feature = "storage-rocksdb")]
+            DbInstance::RocksDb(db) => db.info(),
+            #[cfg(feature = "storage-sled")]
+            DbInstance::Sled(db) => db.info(),
+            #[cfg(feature = "storage-tikv")]
+            DbInstance::TiKv(db) => db.info(),
+        }
+    }
+    /// Dispatcher method. See [crate::Db::verify].
+    pub fn verify(&self) -> Result<DbVerification> {
+        match self {
+            DbInstance::Mem(db) => db.verify(),
+            #[cfg(feature = "storage-sqlite")]
+            DbInstance::Sqlite(db) => db.verify(),
+            #[cfg(feature = "storage-rocksdb")]
+            DbInstance::RocksDb(db) => db.verify(),
+            #[cfg(feature = "storage-sled")]
+            DbInstance::Sled(db) => db.verify(),
+            #[cfg(feature = "storage-tikv")]
+            DbInstance::TiKv(db) => db.verify(),
+        }
+    }
     /// Dispatcher method. See [crate::Db::export_relations].
     pub fn export_relations<I, T>(&self, relations: I) -> Result<BTreeMap<String, NamedRows>>
         where
@@ -413,6 +628,76 @@ impl DbInstance {
 
         self.import_from_backup(&json_payload.path, &json_payload.relations)
     }
+    /// Dispatcher method. See [crate::Db::dump].
+    pub fn dump(&self, out: impl std::io::Write) -> Result<()> {
+        match self {
+            DbInstance::Mem(db) => db.dump(out),
+            #[cfg(feature = "storage-sqlite")]
+            DbInstance::Sqlite(db) => db.dump(out),
+            #[cfg(feature = "storage-rocksdb")]
+            DbInstance::RocksDb(db) => db.dump(out),
+            #[cfg(feature = "storage-sled")]
+            DbInstance::Sled(db) => db.dump(out),
+            #[cfg(feature = "storage-tikv")]
+            DbInstance::TiKv(db) => db.dump(out),
+        }
+    }
+    /// Dispatcher method. See [crate::Db::restore_dump].
+    pub fn restore_dump(&self, dump: impl std::io::BufRead) -> Result<()> {
+        match self {
+            DbInstance::Mem(db) => db.restore_dump(dump),
+            #[cfg(feature = "storage-sqlite")]
+            DbInstance::Sqlite(db) => db.restore_dump(dump),
+            #[cfg(feature = "storage-rocksdb")]
+            DbInstance::RocksDb(db) => db.restore_dump(dump),
+            #[cfg(feature = "storage-sled")]
+            DbInstance::Sled(db) => db.restore_dump(dump),
+            #[cfg(feature = "storage-tikv")]
+            DbInstance::TiKv(db) => db.restore_dump(dump),
+        }
+    }
+    /// Dispatcher method. See [crate::Db::schema_script].
+    pub fn schema_script(&self) -> Result<String> {
+        match self {
+            DbInstance::Mem(db) => db.schema_script(),
+            #[cfg(feature = "storage-sqlite")]
+            DbInstance::Sqlite(db) => db.schema_script(),
+            #[cfg(feature = "storage-rocksdb")]
+            DbInstance::RocksDb(db) => db.schema_script(),
+            #[cfg(feature = "storage-sled")]
+            DbInstance::Sled(db) => db.schema_script(),
+            #[cfg(feature = "storage-tikv")]
+            DbInstance::TiKv(db) => db.schema_script(),
+        }
+    }
+    /// Dispatcher method. See [crate::Db::apply_schema_script].
+    pub fn apply_schema_script(&self, script: &str) -> Result<()> {
+        match self {
+            DbInstance::Mem(db) => db.apply_schema_script(script),
+            #[cfg(feature = "storage-sqlite")]
+            DbInstance::Sqlite(db) => db.apply_schema_script(script),
+            #[cfg(feature = "storage-rocksdb")]
+            DbInstance::RocksDb(db) => db.apply_schema_script(script),
+            #[cfg(feature = "storage-sled")]
+            DbInstance::Sled(db) => db.apply_schema_script(script),
+            #[cfg(feature = "storage-tikv")]
+            DbInstance::TiKv(db) => db.apply_schema_script(script),
+        }
+    }
+    /// Dispatcher method. See [crate::Db::import_from_sqlite].
+    pub fn import_from_sqlite(&self, in_file: impl AsRef<Path>, tables: &[String]) -> Result<()> {
+        match self {
+            DbInstance::Mem(db) => db.import_from_sqlite(in_file, tables),
+            #[cfg(feature = "storage-sqlite")]
+            DbInstance::Sqlite(db) => db.import_from_sqlite(in_file, tables),
+            #[cfg(feature = "storage-rocksdb")]
+            DbInstance::RocksDb(db) => db.import_from_sqlite(in_file, tables),
+            #[cfg(feature = "storage-sled")]
+            DbInstance::Sled(db) => db.import_from_sqlite(in_file, tables),
+            #[cfg(feature = "storage-tikv")]
+            DbInstance::TiKv(db) => db.import_from_sqlite(in_file, tables),
+        }
+    }
 
     /// Dispatcher method. See [crate::Db::register_callback].
     #[cfg(not(target_arch = "wasm32"))]
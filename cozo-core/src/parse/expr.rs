@@ -448,11 +448,44 @@ struct InvalidUtf8Error(u32, #[label] SourceSpan);
 #[diagnostic(code(parser::invalid_escape_seq))]
 struct InvalidEscapeSeqError(String, #[label] SourceSpan);
 
+/// Adds the UTF-16 code unit of a `\uXXXX` escape to `ret`. A high surrogate is kept
+/// in `high` until the low surrogate following it completes the character.
+fn push_code_unit(
+    ret: &mut SmartString<LazyCompact>,
+    high: &mut Option<(u32, SourceSpan)>,
+    code: u32,
+    span: SourceSpan,
+) -> Result<()> {
+    if let Some((high_code, high_span)) = high.take() {
+        if !(0xDC00..0xE000).contains(&code) {
+            bail!(InvalidUtf8Error(high_code, high_span))
+        }
+        let combined = 0x10000 + ((high_code - 0xD800) << 10) + (code - 0xDC00);
+        ret.push(char::from_u32(combined).unwrap());
+    } else if (0xD800..0xDC00).contains(&code) {
+        *high = Some((code, span));
+    } else {
+        let ch = char::from_u32(code).ok_or(InvalidUtf8Error(code, span))?;
+        ret.push(ch);
+    }
+    Ok(())
+}
+
+/// A high surrogate must be directly followed by a `\uXXXX` escape of a low surrogate.
+fn ensure_no_high_surrogate(high: Option<(u32, SourceSpan)>, next: &str) -> Result<()> {
+    match high {
+        Some((code, span)) if !next.starts_with(r"\u") => bail!(InvalidUtf8Error(code, span)),
+        _ => Ok(()),
+    }
+}
+
 fn parse_quoted_string(pair: Pair<'_>) -> Result<SmartString<LazyCompact>> {
     let pairs = pair.into_inner().next().unwrap().into_inner();
     let mut ret = SmartString::new();
+    let mut high_surrogate = None;
     for pair in pairs {
         let s = pair.as_str();
+        ensure_no_high_surrogate(high_surrogate, s)?;
         match s {
             r#"\""# => ret.push('"'),
             r"\\" => ret.push('\\'),
@@ -464,9 +497,7 @@ fn parse_quoted_string(pair: Pair<'_>) -> Result<SmartString<LazyCompact>> {
             r"\t" => ret.push('\t'),
             s if s.starts_with(r"\u") => {
                 let code = parse_int(s, 16) as u32;
-                let ch = char::from_u32(code)
-                    .ok_or_else(|| InvalidUtf8Error(code, pair.extract_span()))?;
-                ret.push(ch);
+                push_code_unit(&mut ret, &mut high_surrogate, code, pair.extract_span())?;
             }
             s if s.starts_with('\\') => {
                 bail!(InvalidEscapeSeqError(s.to_string(), pair.extract_span()))
@@ -474,14 +505,17 @@ fn parse_quoted_string(pair: Pair<'_>) -> Result<SmartString<LazyCompact>> {
             s => ret.push_str(s),
         }
     }
+    ensure_no_high_surrogate(high_surrogate, "")?;
     Ok(ret)
 }
 
 fn parse_s_quoted_string(pair: Pair<'_>) -> Result<SmartString<LazyCompact>> {
     let pairs = pair.into_inner().next().unwrap().into_inner();
     let mut ret = SmartString::new();
+    let mut high_surrogate = None;
     for pair in pairs {
         let s = pair.as_str();
+        ensure_no_high_surrogate(high_surrogate, s)?;
         match s {
             r#"\'"# => ret.push('\''),
             r"\\" => ret.push('\\'),
@@ -493,9 +527,7 @@ fn parse_s_quoted_string(pair: Pair<'_>) -> Result<SmartString<LazyCompact>> {
             r"\t" => ret.push('\t'),
             s if s.starts_with(r"\u") => {
                 let code = parse_int(s, 16) as u32;
-                let ch = char::from_u32(code)
-                    .ok_or_else(|| InvalidUtf8Error(code, pair.extract_span()))?;
-                ret.push(ch);
+                push_code_unit(&mut ret, &mut high_surrogate, code, pair.extract_span())?;
             }
             s if s.starts_with('\\') => {
                 bail!(InvalidEscapeSeqError(s.to_string(), pair.extract_span()))
@@ -503,6 +535,7 @@ fn parse_s_quoted_string(pair: Pair<'_>) -> Result<SmartString<LazyCompact>> {
             s => ret.push_str(s),
         }
     }
+    ensure_no_high_surrogate(high_surrogate, "")?;
     Ok(ret)
 }
 
@@ -13,9 +13,9 @@ use std::sync::Arc;
 use either::{Left, Right};
 use itertools::Itertools;
 use miette::{Diagnostic, Result};
-use smartstring::SmartString;
 use thiserror::Error;
 
+use crate::data::symb::normalize_ident;
 use crate::parse::query::parse_query;
 use crate::parse::sys::parse_sys;
 use crate::parse::{
@@ -69,7 +69,7 @@ fn parse_imperative_stmt(
             let target = pair
                 .into_inner()
                 .next()
-                .map(|p| SmartString::from(p.as_str()));
+                .map(|p| normalize_ident(p.as_str()));
             ImperativeStmt::Break { target, span }
         }
         Rule::continue_stmt => {
@@ -77,7 +77,7 @@ fn parse_imperative_stmt(
             let target = pair
                 .into_inner()
                 .next()
-                .map(|p| SmartString::from(p.as_str()));
+                .map(|p| normalize_ident(p.as_str()));
             ImperativeStmt::Continue { target, span }
         }
         Rule::return_stmt => {
@@ -86,7 +86,7 @@ fn parse_imperative_stmt(
             for p in pair.into_inner() {
                 match p.as_rule() {
                     Rule::ident | Rule::underscore_ident => {
-                        let rel = SmartString::from(p.as_str());
+                        let rel = normalize_ident(p.as_str());
                         rets.push(Right(rel));
                     }
                     Rule::query_script_inner => {
@@ -97,7 +97,7 @@ fn parse_imperative_stmt(
                             fixed_rules,
                             cur_vld,
                         )?;
-                        let store_as = src.next().map(|p| SmartString::from(p.as_str().trim()));
+                        let store_as = src.next().map(|p| normalize_ident(p.as_str().trim()));
                         rets.push(Left(ImperativeStmtClause { prog, store_as }))
                     }
                     _ => unreachable!(),
@@ -110,7 +110,7 @@ fn parse_imperative_stmt(
             let mut inner = pair.into_inner();
             let condition = inner.next().unwrap();
             let cond = match condition.as_rule() {
-                Rule::underscore_ident => Left(SmartString::from(condition.as_str())),
+                Rule::underscore_ident => Left(normalize_ident(condition.as_str())),
                 Rule::imperative_clause => {
                     let mut src = condition.into_inner();
                     let prog = parse_query(
@@ -119,7 +119,7 @@ fn parse_imperative_stmt(
                         fixed_rules,
                         cur_vld,
                     )?;
-                    let store_as = src.next().map(|p| SmartString::from(p.as_str().trim()));
+                    let store_as = src.next().map(|p| normalize_ident(p.as_str().trim()));
                     Right(ImperativeStmtClause { prog, store_as })
                 }
                 _ => unreachable!(),
@@ -149,7 +149,7 @@ fn parse_imperative_stmt(
             let mut mark = None;
             let mut nxt = inner.next().unwrap();
             if nxt.as_rule() == Rule::ident {
-                mark = Some(SmartString::from(nxt.as_str()));
+                mark = Some(normalize_ident(nxt.as_str()));
                 nxt = inner.next().unwrap();
             }
             let body = parse_imperative_block(nxt, param_pool, fixed_rules, cur_vld)?;
@@ -164,8 +164,8 @@ fn parse_imperative_stmt(
             let right_name = right.as_str();
 
             ImperativeStmt::TempSwap {
-                left: SmartString::from(left_name),
-                right: SmartString::from(right_name),
+                left: normalize_ident(left_name),
+                right: normalize_ident(right_name),
             }
         }
         Rule::debug_stmt => {
@@ -174,7 +174,7 @@ fn parse_imperative_stmt(
             let name = name_p.as_str();
 
             ImperativeStmt::TempDebug {
-                temp: SmartString::from(name),
+                temp: normalize_ident(name),
             }
         }
         Rule::imperative_sysop => {
@@ -185,7 +185,7 @@ fn parse_imperative_stmt(
                 fixed_rules,
                 cur_vld,
             )?;
-            let store_as = src.next().map(|p| SmartString::from(p.as_str().trim()));
+            let store_as = src.next().map(|p| normalize_ident(p.as_str().trim()));
             ImperativeStmt::SysOp {
                 sysop: ImperativeSysop { sysop, store_as },
             }
@@ -198,7 +198,7 @@ fn parse_imperative_stmt(
                 fixed_rules,
                 cur_vld,
             )?;
-            let store_as = src.next().map(|p| SmartString::from(p.as_str().trim()));
+            let store_as = src.next().map(|p| normalize_ident(p.as_str().trim()));
             ImperativeStmt::Program {
                 prog: ImperativeStmtClause { prog, store_as },
             }
@@ -212,7 +212,7 @@ fn parse_imperative_stmt(
                 fixed_rules,
                 cur_vld,
             )?;
-            let store_as = src.next().map(|p| SmartString::from(p.as_str().trim()));
+            let store_as = src.next().map(|p| normalize_ident(p.as_str().trim()));
             ImperativeStmt::IgnoreErrorProgram {
                 prog: ImperativeStmtClause { prog, store_as },
             }
@@ -180,6 +180,14 @@ impl ImperativeStmt {
                     SysOp::RemoveIndex(rel, idx) => {
                         collector.insert(SmartString::from(format!("{}:{}", rel.name, idx.name)));
                     }
+                    SysOp::SetTriggers(rel, ..) | SysOp::Analyze(rel) => {
+                        collector.insert(rel.name.clone());
+                    }
+                    SysOp::SetAccessLevel(rels, _) => {
+                        for rel in rels {
+                            collector.insert(rel.name.clone());
+                        }
+                    }
                     _ => {}
                 }
             }
@@ -272,6 +280,7 @@ pub(crate) fn parse_expressions(
     build_expr(parsed.into_inner().next().unwrap(), param_pool)
 }
 
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
 pub(crate) fn parse_script(
     src: &str,
     param_pool: &BTreeMap<String, DataValue>,
@@ -29,7 +29,7 @@ use crate::data::program::{
     QueryAssertion, QueryOutOptions, RelationOp, ReturnMutation, SearchInput, SortDir, Unification,
 };
 use crate::data::relation::{ColType, ColumnDef, NullableColType, StoredRelationMetadata};
-use crate::data::symb::{Symbol, PROG_ENTRY};
+use crate::data::symb::{normalize_ident, Symbol, PROG_ENTRY};
 use crate::data::value::{DataValue, ValidityTs};
 use crate::fixed_rule::utilities::constant::Constant;
 use crate::fixed_rule::{FixedRuleHandle, FixedRuleNotFoundError};
@@ -72,6 +72,11 @@ struct DuplicateQueryAssertion(#[label] SourceSpan);
 #[diagnostic(code(parser::multiple_yields))]
 struct DuplicateYield(#[label] SourceSpan);
 
+#[derive(Debug, Error, Diagnostic)]
+#[error("Field '{0}' is bound more than once")]
+#[diagnostic(code(parser::duplicate_named_field))]
+struct DuplicateNamedField(String, #[label] SourceSpan);
+
 impl Error for MultipleRuleDefinitionError {}
 
 impl Display for MultipleRuleDefinitionError {
@@ -265,11 +270,8 @@ pub(crate) fn parse_query(
                     .map_err(|err| OptionNotConstantError("timeout", span, [err]))?
                     .get_float()
                     .ok_or(OptionNotNonNegIntError("timeout", span))?;
-                if timeout > 0. {
-                    out_opts.timeout = Some(timeout);
-                } else {
-                    out_opts.timeout = None;
-                }
+                // zero is kept, as it overrides the default timeout set with `::set`
+                out_opts.timeout = Some(timeout.max(0.));
             }
             Rule::sleep_option => {
                 #[cfg(target_arch = "wasm32")]
@@ -330,6 +332,19 @@ pub(crate) fn parse_query(
             Rule::returning_option => {
                 returning_mutation = ReturnMutation::Returning;
             }
+            Rule::parallel_option => {
+                let pair = pair.into_inner().next().unwrap();
+                let span = pair.extract_span();
+                let val = build_expr(pair, param_pool)?
+                    .eval_to_const()
+                    .map_err(|err| OptionNotConstantError("parallel", span, [err]))?
+                    .get_bool()
+                    .ok_or(OptionNotBoolError("parallel", span))?;
+                out_opts.parallel = Some(val);
+            }
+            Rule::lenient_option => {
+                out_opts.lenient = true;
+            }
             Rule::relation_option => {
                 let span = pair.extract_span();
                 let mut args = pair.into_inner();
@@ -750,12 +765,14 @@ fn parse_atom(
             let mut src = src.into_inner();
             let name_p = src.next().unwrap();
             let name = Symbol::new(&name_p.as_str()[1..], name_p.extract_span());
-            let args = src
-                .next()
-                .unwrap()
-                .into_inner()
-                .map(|arg| extract_named_apply_arg(arg, param_pool))
-                .try_collect()?;
+            let mut args = BTreeMap::new();
+            for arg in src.next().unwrap().into_inner() {
+                let arg_span = arg.extract_span();
+                let (field, expr) = extract_named_apply_arg(arg, param_pool)?;
+                if args.insert(field.clone(), expr).is_some() {
+                    bail!(DuplicateNamedField(field.to_string(), arg_span));
+                }
+            }
             let valid_at = match src.next() {
                 None => None,
                 Some(vld_clause) => {
@@ -782,7 +799,7 @@ fn extract_named_apply_arg(
 ) -> Result<(SmartString<LazyCompact>, Expr)> {
     let mut inner = pair.into_inner();
     let name_p = inner.next().unwrap();
-    let name = SmartString::from(name_p.as_str());
+    let name = normalize_ident(name_p.as_str());
     let arg = match inner.next() {
         Some(a) => build_expr(a, param_pool)?,
         None => Expr::Binding {
@@ -967,7 +984,7 @@ fn parse_fixed_rule(
                                 Rule::fixed_named_relation_arg_pair => {
                                     let mut vs = p.into_inner();
                                     let kp = vs.next().unwrap();
-                                    let k = SmartString::from(kp.as_str());
+                                    let k = normalize_ident(kp.as_str());
                                     let v = match vs.next() {
                                         Some(vp) => {
                                             if !seen_bindings.insert(vp.as_str()) {
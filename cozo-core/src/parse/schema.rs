@@ -10,11 +10,10 @@ use std::collections::BTreeSet;
 
 use itertools::Itertools;
 use miette::{bail, ensure, Diagnostic, Result, IntoDiagnostic};
-use smartstring::SmartString;
 use thiserror::Error;
 
 use crate::data::relation::{VecElementType, ColType, ColumnDef, NullableColType, StoredRelationMetadata};
-use crate::data::symb::Symbol;
+use crate::data::symb::{normalize_ident, Symbol};
 use crate::data::value::DataValue;
 use crate::parse::expr::{build_expr};
 use crate::parse::{ExtractSpan, Pair, Rule, SourceSpan};
@@ -67,7 +66,7 @@ pub(crate) fn parse_schema(
 fn parse_col(pair: Pair<'_>) -> Result<(ColumnDef, Symbol)> {
     let mut src = pair.into_inner();
     let name_p = src.next().unwrap();
-    let name = SmartString::from(name_p.as_str());
+    let name = normalize_ident(name_p.as_str());
     let mut typing = NullableColType {
         coltype: ColType::Any,
         nullable: true,
@@ -10,25 +10,29 @@ use std::collections::BTreeMap;
 use std::sync::Arc;
 
 use itertools::Itertools;
-use miette::{bail, ensure, miette, Diagnostic, Result};
+use miette::{bail, ensure, Diagnostic, Result};
 use ordered_float::OrderedFloat;
 use smartstring::{LazyCompact, SmartString};
 use thiserror::Error;
 
 use crate::data::program::InputProgram;
 use crate::data::relation::VecElementType;
-use crate::data::symb::Symbol;
+use crate::data::symb::{normalize_ident, Symbol};
 use crate::data::value::{DataValue, ValidityTs};
 use crate::fts::TokenizerConfig;
 use crate::parse::expr::{build_expr, parse_string};
 use crate::parse::query::parse_query;
 use crate::parse::{ExtractSpan, Pairs, Rule, SourceSpan};
+use crate::runtime::dump::expr_script;
 use crate::runtime::relation::AccessLevel;
 use crate::{Expr, FixedRule};
 
 #[derive(Debug)]
 pub(crate) enum SysOp {
     Compact,
+    Analyze(Symbol),
+    ShowStats(Symbol),
+    SetOption(Symbol, DataValue),
     ListColumns(Symbol),
     ListIndices(Symbol),
     ListRelations,
@@ -36,6 +40,7 @@ pub(crate) enum SysOp {
     ListFixedRules,
     KillRunning(u64),
     Explain(Box<InputProgram>),
+    ExplainAnalyze(Box<InputProgram>),
     RemoveRelation(Vec<Symbol>),
     RenameRelation(Vec<(Symbol, Symbol)>),
     ShowTrigger(Symbol),
@@ -96,6 +101,11 @@ pub(crate) enum HnswDistance {
     Cosine,
 }
 
+#[derive(Debug, Diagnostic, Error)]
+#[error("{0}")]
+#[diagnostic(code(parser::invalid_index_option))]
+struct InvalidIndexOption(String, #[label] SourceSpan);
+
 #[derive(Debug, Diagnostic, Error)]
 #[error("Cannot interpret {0} as process ID")]
 #[diagnostic(code(parser::not_proc_id))]
@@ -113,21 +123,27 @@ pub(crate) fn parse_sys(
         Rule::running_op => SysOp::ListRunning,
         Rule::kill_op => {
             let i_expr = inner.into_inner().next().unwrap();
+            let span = i_expr.extract_span();
             let i_val = build_expr(i_expr, param_pool)?;
             let i_val = i_val.eval_to_const()?;
             let i_val = i_val
                 .get_int()
-                .ok_or_else(|| miette!("Process ID must be an integer"))?;
+                .ok_or_else(|| ProcessIdError(i_val.to_string(), span))?;
             SysOp::KillRunning(i_val as u64)
         }
         Rule::explain_op => {
-            let prog = parse_query(
-                inner.into_inner().next().unwrap().into_inner(),
-                param_pool,
-                algorithms,
-                cur_vld,
-            )?;
-            SysOp::Explain(Box::new(prog))
+            let mut inner = inner.into_inner();
+            let mut script_p = inner.next().unwrap();
+            let analyze = script_p.as_rule() == Rule::explain_analyze;
+            if analyze {
+                script_p = inner.next().unwrap();
+            }
+            let prog = parse_query(script_p.into_inner(), param_pool, algorithms, cur_vld)?;
+            if analyze {
+                SysOp::ExplainAnalyze(Box::new(prog))
+            } else {
+                SysOp::Explain(Box::new(prog))
+            }
         }
         Rule::describe_relation_op => {
             let mut inner = inner.into_inner();
@@ -158,6 +174,23 @@ pub(crate) fn parse_sys(
             let rel = Symbol::new(rels_p.as_str(), rels_p.extract_span());
             SysOp::ListIndices(rel)
         }
+        Rule::analyze_op => {
+            let rels_p = inner.into_inner().next().unwrap();
+            let rel = Symbol::new(rels_p.as_str(), rels_p.extract_span());
+            SysOp::Analyze(rel)
+        }
+        Rule::set_option_op => {
+            let mut inner = inner.into_inner();
+            let name_p = inner.next().unwrap();
+            let name = Symbol::new(name_p.as_str(), name_p.extract_span());
+            let val = build_expr(inner.next().unwrap(), param_pool)?.eval_to_const()?;
+            SysOp::SetOption(name, val)
+        }
+        Rule::stats_op => {
+            let rels_p = inner.into_inner().next().unwrap();
+            let rel = Symbol::new(rels_p.as_str(), rels_p.extract_span());
+            SysOp::ShowStats(rel)
+        }
         Rule::rename_relations_op => {
             let rename_pairs = inner
                 .into_inner()
@@ -243,58 +276,109 @@ pub(crate) fn parse_sys(
                         let mut opt_inner = opt_pair.into_inner();
                         let opt_name = opt_inner.next().unwrap();
                         let opt_val = opt_inner.next().unwrap();
+                        let opt_span = opt_val.extract_span();
                         match opt_name.as_str() {
                             "false_positive_weight" => {
                                 let mut expr = build_expr(opt_val, param_pool)?;
                                 expr.partial_eval()?;
                                 let v = expr.eval_to_const()?;
                                 false_positive_weight = v.get_float().ok_or_else(|| {
-                                    miette!("false_positive_weight must be a float")
+                                    InvalidIndexOption(
+                                        "false_positive_weight must be a float".to_string(),
+                                        opt_span,
+                                    )
                                 })?;
+                                ensure!(
+                                    false_positive_weight > 0.,
+                                    InvalidIndexOption(
+                                        "false_positive_weight must be positive".to_string(),
+                                        opt_span
+                                    )
+                                );
                             }
                             "false_negative_weight" => {
                                 let mut expr = build_expr(opt_val, param_pool)?;
                                 expr.partial_eval()?;
                                 let v = expr.eval_to_const()?;
                                 false_negative_weight = v.get_float().ok_or_else(|| {
-                                    miette!("false_negative_weight must be a float")
+                                    InvalidIndexOption(
+                                        "false_negative_weight must be a float".to_string(),
+                                        opt_span,
+                                    )
                                 })?;
+                                ensure!(
+                                    false_negative_weight > 0.,
+                                    InvalidIndexOption(
+                                        "false_negative_weight must be positive".to_string(),
+                                        opt_span
+                                    )
+                                );
                             }
                             "n_gram" => {
                                 let mut expr = build_expr(opt_val, param_pool)?;
                                 expr.partial_eval()?;
                                 let v = expr.eval_to_const()?;
-                                n_gram = v
-                                    .get_int()
-                                    .ok_or_else(|| miette!("n_gram must be an integer"))?
-                                    as usize;
+                                let v = v.get_int().ok_or_else(|| {
+                                    InvalidIndexOption(
+                                        "n_gram must be an integer".to_string(),
+                                        opt_span,
+                                    )
+                                })?;
+                                ensure!(
+                                    v > 0,
+                                    InvalidIndexOption(
+                                        "n_gram must be positive".to_string(),
+                                        opt_span
+                                    )
+                                );
+                                n_gram = v as usize;
                             }
                             "n_perm" => {
                                 let mut expr = build_expr(opt_val, param_pool)?;
                                 expr.partial_eval()?;
                                 let v = expr.eval_to_const()?;
-                                n_perm = v
-                                    .get_int()
-                                    .ok_or_else(|| miette!("n_perm must be an integer"))?
-                                    as usize;
+                                let v = v.get_int().ok_or_else(|| {
+                                    InvalidIndexOption(
+                                        "n_perm must be an integer".to_string(),
+                                        opt_span,
+                                    )
+                                })?;
+                                ensure!(
+                                    v > 0,
+                                    InvalidIndexOption(
+                                        "n_perm must be positive".to_string(),
+                                        opt_span
+                                    )
+                                );
+                                n_perm = v as usize;
                             }
                             "target_threshold" => {
                                 let mut expr = build_expr(opt_val, param_pool)?;
                                 expr.partial_eval()?;
                                 let v = expr.eval_to_const()?;
-                                target_threshold = v
-                                    .get_float()
-                                    .ok_or_else(|| miette!("target_threshold must be a float"))?;
+                                target_threshold = v.get_float().ok_or_else(|| {
+                                    InvalidIndexOption(
+                                        "target_threshold must be a float".to_string(),
+                                        opt_span,
+                                    )
+                                })?;
+                                ensure!(
+                                    target_threshold > 0. && target_threshold < 1.,
+                                    InvalidIndexOption(
+                                        "target_threshold must be between 0 and 1".to_string(),
+                                        opt_span
+                                    )
+                                );
                             }
                             "extractor" => {
                                 let mut ex = build_expr(opt_val, param_pool)?;
                                 ex.partial_eval()?;
-                                extractor = ex.to_string();
+                                extractor = expr_script(&ex);
                             }
                             "extract_filter" => {
                                 let mut ex = build_expr(opt_val, param_pool)?;
                                 ex.partial_eval()?;
-                                extract_filter = ex.to_string();
+                                extract_filter = expr_script(&ex);
                             }
                             "tokenizer" => {
                                 let mut expr = build_expr(opt_val, param_pool)?;
@@ -313,7 +397,11 @@ pub(crate) fn parse_sys(
                                         tokenizer.name = var.name;
                                         tokenizer.args = vec![];
                                     }
-                                    _ => bail!("Tokenizer must be a symbol or a call for an existing tokenizer"),
+                                    _ => bail!(InvalidIndexOption(
+                                        "Tokenizer must be a symbol or a call for an existing tokenizer"
+                                            .to_string(),
+                                        opt_span
+                                    )),
                                 }
                             }
                             "filters" => {
@@ -322,7 +410,10 @@ pub(crate) fn parse_sys(
                                 match expr {
                                     Expr::Apply { op, args, .. } => {
                                         if op.name != "OP_LIST" {
-                                            bail!("Filters must be a list of filters");
+                                            bail!(InvalidIndexOption(
+                                                "Filters must be a list of filters".to_string(),
+                                                opt_span
+                                            ));
                                         }
                                         for arg in args.iter() {
                                             match arg {
@@ -343,30 +434,26 @@ pub(crate) fn parse_sys(
                                                         args: vec![],
                                                     })
                                                 }
-                                                _ => bail!("Tokenizer must be a symbol or a call for an existing tokenizer"),
+                                                _ => bail!(InvalidIndexOption(
+                                                    "Tokenizer must be a symbol or a call for an existing tokenizer"
+                                                        .to_string(),
+                                                    opt_span
+                                                )),
                                             }
                                         }
                                     }
-                                    _ => bail!("Filters must be a list of filters"),
+                                    _ => bail!(InvalidIndexOption(
+                                        "Filters must be a list of filters".to_string(),
+                                        opt_span
+                                    )),
                                 }
                             }
-                            _ => bail!("Unknown option {} for LSH index", opt_name.as_str()),
+                            _ => bail!(InvalidIndexOption(
+                                format!("Unknown option {} for LSH index", opt_name.as_str()),
+                                opt_name.extract_span()
+                            )),
                         }
                     }
-                    ensure!(
-                        false_positive_weight > 0.,
-                        "false_positive_weight must be positive"
-                    );
-                    ensure!(
-                        false_negative_weight > 0.,
-                        "false_negative_weight must be positive"
-                    );
-                    ensure!(n_gram > 0, "n_gram must be positive");
-                    ensure!(n_perm > 0, "n_perm must be positive");
-                    ensure!(
-                        target_threshold > 0. && target_threshold < 1.,
-                        "target_threshold must be between 0 and 1"
-                    );
                     let total_weights = false_positive_weight + false_negative_weight;
                     false_positive_weight /= total_weights;
                     false_negative_weight /= total_weights;
@@ -376,8 +463,8 @@ pub(crate) fn parse_sys(
                     }
 
                     let config = MinHashLshConfig {
-                        base_relation: SmartString::from(rel.as_str()),
-                        index_name: SmartString::from(name.as_str()),
+                        base_relation: normalize_ident(rel.as_str()),
+                        index_name: normalize_ident(name.as_str()),
                         extractor,
                         tokenizer,
                         filters,
@@ -419,16 +506,17 @@ pub(crate) fn parse_sys(
                         let mut opt_inner = opt_pair.into_inner();
                         let opt_name = opt_inner.next().unwrap();
                         let opt_val = opt_inner.next().unwrap();
+                        let opt_span = opt_val.extract_span();
                         match opt_name.as_str() {
                             "extractor" => {
                                 let mut ex = build_expr(opt_val, param_pool)?;
                                 ex.partial_eval()?;
-                                extractor = ex.to_string();
+                                extractor = expr_script(&ex);
                             }
                             "extract_filter" => {
                                 let mut ex = build_expr(opt_val, param_pool)?;
                                 ex.partial_eval()?;
-                                extract_filter = ex.to_string();
+                                extract_filter = expr_script(&ex);
                             }
                             "tokenizer" => {
                                 let mut expr = build_expr(opt_val, param_pool)?;
@@ -447,7 +535,11 @@ pub(crate) fn parse_sys(
                                         tokenizer.name = var.name;
                                         tokenizer.args = vec![];
                                     }
-                                    _ => bail!("Tokenizer must be a symbol or a call for an existing tokenizer"),
+                                    _ => bail!(InvalidIndexOption(
+                                        "Tokenizer must be a symbol or a call for an existing tokenizer"
+                                            .to_string(),
+                                        opt_span
+                                    )),
                                 }
                             }
                             "filters" => {
@@ -456,7 +548,10 @@ pub(crate) fn parse_sys(
                                 match expr {
                                     Expr::Apply { op, args, .. } => {
                                         if op.name != "OP_LIST" {
-                                            bail!("Filters must be a list of filters");
+                                            bail!(InvalidIndexOption(
+                                                "Filters must be a list of filters".to_string(),
+                                                opt_span
+                                            ));
                                         }
                                         for arg in args.iter() {
                                             match arg {
@@ -477,22 +572,32 @@ pub(crate) fn parse_sys(
                                                         args: vec![],
                                                     })
                                                 }
-                                                _ => bail!("Tokenizer must be a symbol or a call for an existing tokenizer"),
+                                                _ => bail!(InvalidIndexOption(
+                                                    "Tokenizer must be a symbol or a call for an existing tokenizer"
+                                                        .to_string(),
+                                                    opt_span
+                                                )),
                                             }
                                         }
                                     }
-                                    _ => bail!("Filters must be a list of filters"),
+                                    _ => bail!(InvalidIndexOption(
+                                        "Filters must be a list of filters".to_string(),
+                                        opt_span
+                                    )),
                                 }
                             }
-                            _ => bail!("Unknown option {} for FTS index", opt_name.as_str()),
+                            _ => bail!(InvalidIndexOption(
+                                format!("Unknown option {} for FTS index", opt_name.as_str()),
+                                opt_name.extract_span()
+                            )),
                         }
                     }
                     if !extract_filter.is_empty() {
                         extractor = format!("if({}, {})", extract_filter, extractor);
                     }
                     let config = FtsIndexConfig {
-                        base_relation: SmartString::from(rel.as_str()),
-                        index_name: SmartString::from(name.as_str()),
+                        base_relation: normalize_ident(rel.as_str()),
+                        index_name: normalize_ident(name.as_str()),
                         extractor,
                         tokenizer,
                         filters,
@@ -515,6 +620,7 @@ pub(crate) fn parse_sys(
             let inner = inner.into_inner().next().unwrap();
             match inner.as_rule() {
                 Rule::index_create_adv => {
+                    let span = inner.extract_span();
                     let mut inner = inner.into_inner();
                     let rel = inner.next().unwrap();
                     let name = inner.next().unwrap();
@@ -533,14 +639,23 @@ pub(crate) fn parse_sys(
                         let mut opt_inner = opt_pair.into_inner();
                         let opt_name = opt_inner.next().unwrap();
                         let opt_val = opt_inner.next().unwrap();
+                        let opt_span = opt_val.extract_span();
                         let opt_val_str = opt_val.as_str();
                         match opt_name.as_str() {
                             "dim" => {
                                 let v = build_expr(opt_val, param_pool)?
                                     .eval_to_const()?
                                     .get_int()
-                                    .ok_or_else(|| miette!("Invalid vec_dim: {}", opt_val_str))?;
-                                ensure!(v > 0, "Invalid vec_dim: {}", v);
+                                    .ok_or_else(|| {
+                                        InvalidIndexOption(
+                                            format!("Invalid vec_dim: {}", opt_val_str),
+                                            opt_span,
+                                        )
+                                    })?;
+                                ensure!(
+                                    v > 0,
+                                    InvalidIndexOption(format!("Invalid vec_dim: {}", v), opt_span)
+                                );
                                 vec_dim = v as usize;
                             }
                             "ef_construction" | "ef" => {
@@ -548,9 +663,18 @@ pub(crate) fn parse_sys(
                                     .eval_to_const()?
                                     .get_int()
                                     .ok_or_else(|| {
-                                        miette!("Invalid ef_construction: {}", opt_val_str)
+                                        InvalidIndexOption(
+                                            format!("Invalid ef_construction: {}", opt_val_str),
+                                            opt_span,
+                                        )
                                     })?;
-                                ensure!(v > 0, "Invalid ef_construction: {}", v);
+                                ensure!(
+                                    v > 0,
+                                    InvalidIndexOption(
+                                        format!("Invalid ef_construction: {}", v),
+                                        opt_span
+                                    )
+                                );
                                 ef_construction = v as usize;
                             }
                             "m_neighbours" | "m" => {
@@ -558,9 +682,18 @@ pub(crate) fn parse_sys(
                                     .eval_to_const()?
                                     .get_int()
                                     .ok_or_else(|| {
-                                        miette!("Invalid m_neighbours: {}", opt_val_str)
+                                        InvalidIndexOption(
+                                            format!("Invalid m_neighbours: {}", opt_val_str),
+                                            opt_span,
+                                        )
                                     })?;
-                                ensure!(v > 0, "Invalid m_neighbours: {}", v);
+                                ensure!(
+                                    v > 0,
+                                    InvalidIndexOption(
+                                        format!("Invalid m_neighbours: {}", v),
+                                        opt_span
+                                    )
+                                );
                                 m_neighbours = v as usize;
                             }
                             "dtype" => {
@@ -568,7 +701,10 @@ pub(crate) fn parse_sys(
                                     "F32" | "Float" => VecElementType::F32,
                                     "F64" | "Double" => VecElementType::F64,
                                     _ => {
-                                        return Err(miette!("Invalid dtype: {}", opt_val.as_str()))
+                                        bail!(InvalidIndexOption(
+                                            format!("Invalid dtype: {}", opt_val.as_str()),
+                                            opt_span
+                                        ))
                                     }
                                 }
                             }
@@ -582,9 +718,9 @@ pub(crate) fn parse_sys(
                                     "IP" => HnswDistance::InnerProduct,
                                     "Cosine" => HnswDistance::Cosine,
                                     _ => {
-                                        return Err(miette!(
-                                            "Invalid distance: {}",
-                                            opt_val.as_str()
+                                        bail!(InvalidIndexOption(
+                                            format!("Invalid distance: {}", opt_val.as_str()),
+                                            opt_span
                                         ))
                                     }
                                 }
@@ -598,18 +734,27 @@ pub(crate) fn parse_sys(
                             "keep_pruned_connections" => {
                                 keep_pruned_connections = opt_val.as_str().trim() == "true";
                             }
-                            _ => return Err(miette!("Invalid option: {}", opt_name.as_str())),
+                            _ => bail!(InvalidIndexOption(
+                                format!("Invalid option: {}", opt_name.as_str()),
+                                opt_name.extract_span()
+                            )),
                         }
                     }
                     if ef_construction == 0 {
-                        bail!("ef_construction must be set");
+                        bail!(InvalidIndexOption(
+                            "ef_construction must be set".to_string(),
+                            span
+                        ));
                     }
                     if m_neighbours == 0 {
-                        bail!("m_neighbours must be set");
+                        bail!(InvalidIndexOption(
+                            "m_neighbours must be set".to_string(),
+                            span
+                        ));
                     }
                     SysOp::CreateVectorIndex(HnswIndexConfig {
-                        base_relation: SmartString::from(rel.as_str()),
-                        index_name: SmartString::from(name.as_str()),
+                        base_relation: normalize_ident(rel.as_str()),
+                        index_name: normalize_ident(name.as_str()),
                         vec_dim,
                         dtype,
                         vec_fields,
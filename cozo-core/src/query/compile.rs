@@ -109,6 +109,7 @@ pub(crate) enum IndexPositionUse {
 }
 
 impl<'a> SessionTx<'a> {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
     pub(crate) fn stratified_magic_compile(
         &mut self,
         prog: StratifiedMagicProgram,
@@ -264,8 +265,12 @@ impl<'a> SessionTx<'a> {
                         }
                     }
 
-                    let chosen_index =
-                        store.choose_index(&join_indices, rel_app.valid_at.is_some());
+                    let stats = self.get_relation_stats(&store)?;
+                    let chosen_index = store.choose_index(
+                        stats.as_ref(),
+                        &join_indices,
+                        rel_app.valid_at.is_some(),
+                    );
 
                     match chosen_index {
                         None => {
@@ -322,10 +327,13 @@ impl<'a> SessionTx<'a> {
                                 })
                                 .collect_vec();
 
-                            let mut final_joiner_vars = vec![];
-                            for idx in mapper.iter() {
-                                final_joiner_vars.push(right_vars[*idx].clone());
-                            }
+                            // joined on the primary key columns carried by the index,
+                            // in the same order as `middle_joiner_right_vars`
+                            let final_joiner_vars = mapper
+                                .iter()
+                                .filter(|idx| **idx < store.metadata.keys.len())
+                                .map(|idx| right_vars[*idx].clone())
+                                .collect_vec();
 
                             let middle = RelAlgebra::relation(
                                 middle_vars,
@@ -433,8 +441,12 @@ impl<'a> SessionTx<'a> {
                         }
                     }
 
-                    let chosen_index =
-                        store.choose_index(&join_indices, rel_app.valid_at.is_some());
+                    let stats = self.get_relation_stats(&store)?;
+                    let chosen_index = store.choose_index(
+                        stats.as_ref(),
+                        &join_indices,
+                        rel_app.valid_at.is_some(),
+                    );
 
                     match chosen_index {
                         None | Some((_, _, true)) => {
@@ -26,7 +26,7 @@ use crate::parse::SourceSpan;
 use crate::query::compile::{
     AggrKind, CompiledProgram, CompiledRule, CompiledRuleSet, ContainedRuleMultiplicity,
 };
-use crate::runtime::db::Poison;
+use crate::runtime::db::{seconds_since_the_epoch, Poison};
 use crate::runtime::temp_store::{EpochStore, MeetAggrStore, RegularTempStore};
 use crate::runtime::transact::SessionTx;
 
@@ -62,6 +62,7 @@ impl QueryLimiter {
 }
 
 impl<'a> SessionTx<'a> {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
     pub(crate) fn stratified_magic_evaluate(
         &self,
         strata: &[CompiledProgram],
@@ -101,7 +102,14 @@ impl<'a> SessionTx<'a> {
                 total_num_to_take,
                 num_to_skip,
                 poison.clone(),
+                stratum,
             )?;
+            if let Some(profile) = &self.profile {
+                for rule_name in cur_prog.keys() {
+                    let rows = stores[rule_name].all_iter().count();
+                    profile.record_rows(stratum, rule_name, rows);
+                }
+            }
         }
         let entry_symbol = MagicSymbol::Muggle {
             inner: Symbol::new(PROG_ENTRY, SourceSpan(0, 0)),
@@ -110,6 +118,7 @@ impl<'a> SessionTx<'a> {
         Ok((ret_area, early_return))
     }
     /// returns true if early return is activated
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(stratum = stratum)))]
     fn semi_naive_magic_evaluate(
         &self,
         prog: &CompiledProgram,
@@ -117,6 +126,7 @@ impl<'a> SessionTx<'a> {
         total_num_to_take: Option<usize>,
         num_to_skip: Option<usize>,
         poison: Poison,
+        stratum: usize,
     ) -> Result<bool> {
         let limiter = QueryLimiter {
             total: total_num_to_take,
@@ -133,6 +143,10 @@ impl<'a> SessionTx<'a> {
             if epoch == 0 {
                 #[allow(clippy::needless_borrow)]
                 let execution = |(k, compiled_ruleset): (_, &CompiledRuleSet)| -> Result<_> {
+                    let started = match &self.profile {
+                        Some(_) => seconds_since_the_epoch()?,
+                        None => 0.,
+                    };
                     let new_store = match compiled_ruleset {
                         CompiledRuleSet::Rules(ruleset) => match compiled_ruleset.aggr_kind() {
                             AggrKind::None => {
@@ -179,6 +193,9 @@ impl<'a> SessionTx<'a> {
                             out.wrap()
                         }
                     };
+                    if let Some(profile) = &self.profile {
+                        profile.record_run(stratum, k, seconds_since_the_epoch()? - started);
+                    }
                     Ok((k, new_store))
                 };
                 #[cfg(not(target_arch = "wasm32"))]
@@ -217,6 +234,10 @@ impl<'a> SessionTx<'a> {
                 // Follow up epoch > 0
                 #[allow(clippy::needless_borrow)]
                 let execution = |(k, compiled_ruleset): (_, &CompiledRuleSet)| -> Result<_> {
+                    let started = match &self.profile {
+                        Some(_) => seconds_since_the_epoch()?,
+                        None => 0.,
+                    };
                     let new_store = match compiled_ruleset {
                         CompiledRuleSet::Rules(ruleset) => {
                             match compiled_ruleset.aggr_kind() {
@@ -253,6 +274,25 @@ impl<'a> SessionTx<'a> {
                             RegularTempStore::default().wrap()
                         }
                     };
+                    if let Some(profile) = &self.profile {
+                        // fixed rules and rules with normal aggregations are only
+                        // evaluated in the first epoch, other rules only when their
+                        // dependencies changed
+                        let evaluated = match compiled_ruleset {
+                            CompiledRuleSet::Rules(ruleset) => {
+                                compiled_ruleset.aggr_kind() != AggrKind::Normal
+                                    && ruleset.iter().any(|rule| {
+                                        rule.contained_rules
+                                            .keys()
+                                            .any(|symb| borrowed_stores[symb].has_delta())
+                                    })
+                            }
+                            CompiledRuleSet::Fixed(_) => false,
+                        };
+                        if evaluated {
+                            profile.record_run(stratum, k, seconds_since_the_epoch()? - started);
+                        }
+                    }
                     Ok((k, new_store))
                 };
                 #[cfg(not(target_arch = "wasm32"))]
@@ -310,34 +350,80 @@ impl<'a> SessionTx<'a> {
         limiter: &QueryLimiter,
         poison: Poison,
     ) -> Result<(bool, RegularTempStore)> {
-        let mut out_store = RegularTempStore::default();
         let should_check_limit = limiter.total.is_some() && rule_symb.is_prog_entry();
-
-        for (rule_n, rule) in ruleset.iter().enumerate() {
-            debug!("initial calculation for rule {:?}.{}", rule_symb, rule_n);
-            for item_res in rule.relation.iter(self, None, stores)? {
-                let item = item_res?;
-                trace!("item for {:?}.{}: {:?} at {}", rule_symb, rule_n, item, 0);
-                if should_check_limit {
-                    if !out_store.exists(&item) {
-                        if limiter.should_skip_next() {
-                            out_store.put_with_skip(item);
-                        } else {
-                            out_store.put(item);
-                        }
-                        if limiter.incr_and_should_stop() {
-                            trace!("early stopping due to result count limit exceeded");
-                            return Ok((true, out_store));
+        let (_, out_store) = self.rule_branches_eval(
+            ruleset,
+            should_check_limit,
+            limiter,
+            |rule_n, rule, out_store| {
+                debug!("initial calculation for rule {:?}.{}", rule_symb, rule_n);
+                for item_res in rule.relation.iter(self, None, stores)? {
+                    let item = item_res?;
+                    poison.check()?;
+                    trace!("item for {:?}.{}: {:?} at {}", rule_symb, rule_n, item, 0);
+                    if should_check_limit {
+                        if !out_store.exists(&item) {
+                            if limiter.should_skip_next() {
+                                out_store.put_with_skip(item);
+                            } else {
+                                out_store.put(item);
+                            }
+                            if limiter.incr_and_should_stop() {
+                                trace!("early stopping due to result count limit exceeded");
+                                return Ok(true);
+                            }
                         }
+                    } else {
+                        out_store.put(item);
                     }
-                } else {
-                    out_store.put(item);
                 }
-            }
-            poison.check()?;
+                poison.check()?;
+                Ok(false)
+            },
+        )?;
+        Ok((should_check_limit, out_store))
+    }
+    /// Evaluates the branches of a rule with `eval`, which puts the derived tuples
+    /// into the given store and returns true if the limiter says to stop early.
+    ///
+    /// Branches are independent of each other unless the limiter is in effect, so
+    /// if parallel evaluation is enabled for the transaction they are evaluated on the
+    /// rayon thread pool, each thread filling its own store, and the stores are merged.
+    /// Returns true if evaluation stopped early.
+    fn rule_branches_eval(
+        &self,
+        ruleset: &[CompiledRule],
+        should_check_limit: bool,
+        limiter: &QueryLimiter,
+        eval: impl Fn(usize, &CompiledRule, &mut RegularTempStore) -> Result<bool> + Sync,
+    ) -> Result<(bool, RegularTempStore)> {
+        #[cfg(not(target_arch = "wasm32"))]
+        if self.parallel_branches
+            && ruleset.len() > 1
+            && !should_check_limit
+            && limiter.skip.is_none()
+        {
+            let out_store = ruleset
+                .par_iter()
+                .enumerate()
+                .try_fold(
+                    RegularTempStore::default,
+                    |mut out_store, (rule_n, rule)| -> Result<_> {
+                        eval(rule_n, rule, &mut out_store)?;
+                        Ok(out_store)
+                    },
+                )
+                .try_reduce(RegularTempStore::default, |a, b| Ok(a.union(b)))?;
+            return Ok((false, out_store));
         }
 
-        Ok((should_check_limit, out_store))
+        let mut out_store = RegularTempStore::default();
+        for (rule_n, rule) in ruleset.iter().enumerate() {
+            if eval(rule_n, rule, &mut out_store)? {
+                return Ok((true, out_store));
+            }
+        }
+        Ok((false, out_store))
     }
     fn initial_rule_meet_eval(
         &self,
@@ -356,6 +442,7 @@ impl<'a> SessionTx<'a> {
             }
             for item_res in rule.relation.iter(self, None, stores)? {
                 let item = item_res?;
+                poison.check()?;
                 trace!("item for {:?}.{}: {:?} at {}", rule_symb, rule_n, item, 0);
                 out_store.meet_put(item)?;
             }
@@ -416,6 +503,7 @@ impl<'a> SessionTx<'a> {
 
             for item_res in rule.relation.iter(self, None, stores)? {
                 let item = item_res?;
+                poison.check()?;
                 trace!("item for {:?}.{}: {:?} at {}", rule_symb, rule_n, item, 0);
 
                 let keys = extract_keys(&item);
@@ -514,71 +602,49 @@ impl<'a> SessionTx<'a> {
         poison: Poison,
     ) -> Result<(bool, RegularTempStore)> {
         let prev_store = stores.get(rule_symb).unwrap();
-        let mut out_store = RegularTempStore::default();
         let should_check_limit = limiter.total.is_some() && rule_symb.is_prog_entry();
-        for (rule_n, rule) in ruleset.iter().enumerate() {
-            let mut need_complete_run = false;
-            let mut dependencies_changed = false;
+        let (stopped, out_store) = self.rule_branches_eval(
+            ruleset,
+            should_check_limit,
+            limiter,
+            |rule_n, rule, out_store| {
+                let mut need_complete_run = false;
+                let mut dependencies_changed = false;
 
-            for (symb, multiplicity) in rule.contained_rules.iter() {
-                if stores.get(symb).unwrap().has_delta() {
-                    dependencies_changed = true;
-                    if *multiplicity == ContainedRuleMultiplicity::Many {
-                        need_complete_run = true;
-                        break;
+                for (symb, multiplicity) in rule.contained_rules.iter() {
+                    if stores.get(symb).unwrap().has_delta() {
+                        dependencies_changed = true;
+                        if *multiplicity == ContainedRuleMultiplicity::Many {
+                            need_complete_run = true;
+                            break;
+                        }
                     }
                 }
-            }
 
-            if !dependencies_changed {
-                continue;
-            }
+                if !dependencies_changed {
+                    return Ok(false);
+                }
 
-            if need_complete_run {
-                debug!("complete rule for rule {:?}.{}", rule_symb, rule_n);
-                for item_res in rule.relation.iter(self, None, stores)? {
-                    let item = item_res?;
-                    // improvement: the clauses can actually be evaluated in parallel
-                    if prev_store.exists(&item) {
-                        trace!(
-                            "item for {:?}.{}: {:?} at {}, rederived",
-                            rule_symb,
-                            rule_n,
-                            item,
-                            epoch
-                        );
-                    } else {
-                        trace!(
-                            "item for {:?}.{}: {:?} at {}",
-                            rule_symb,
-                            rule_n,
-                            item,
-                            epoch
+                let delta_keys = if need_complete_run {
+                    debug!("complete rule for rule {:?}.{}", rule_symb, rule_n);
+                    vec![None]
+                } else {
+                    stores
+                        .keys()
+                        .filter(|delta_key| rule.contained_rules.contains_key(*delta_key))
+                        .map(Some)
+                        .collect_vec()
+                };
+                for delta_key in delta_keys {
+                    if let Some(delta_key) = delta_key {
+                        debug!(
+                            "with delta {:?} for rule {:?}.{}",
+                            delta_key, rule_symb, rule_n
                         );
-                        if limiter.should_skip_next() {
-                            out_store.put_with_skip(item);
-                        } else {
-                            out_store.put(item);
-                        }
-                        if should_check_limit && limiter.incr_and_should_stop() {
-                            trace!("early stopping due to result count limit exceeded");
-                            return Ok((true, out_store));
-                        }
-                    }
-                }
-                poison.check()?;
-            } else {
-                for (delta_key, _) in stores.iter() {
-                    if !rule.contained_rules.contains_key(delta_key) {
-                        continue;
                     }
-                    debug!(
-                        "with delta {:?} for rule {:?}.{}",
-                        delta_key, rule_symb, rule_n
-                    );
-                    for item_res in rule.relation.iter(self, Some(delta_key), stores)? {
+                    for item_res in rule.relation.iter(self, delta_key, stores)? {
                         let item = item_res?;
-                        // improvement: the clauses can actually be evaluated in parallel
+                        poison.check()?;
                         if prev_store.exists(&item) {
                             trace!(
                                 "item for {:?}.{}: {:?} at {}, rederived",
@@ -602,15 +668,16 @@ impl<'a> SessionTx<'a> {
                             }
                             if should_check_limit && limiter.incr_and_should_stop() {
                                 trace!("early stopping due to result count limit exceeded");
-                                return Ok((true, out_store));
+                                return Ok(true);
                             }
                         }
                     }
                     poison.check()?;
                 }
-            }
-        }
-        Ok((should_check_limit, out_store))
+                Ok(false)
+            },
+        )?;
+        Ok((stopped || should_check_limit, out_store))
     }
     fn incremental_rule_meet_eval(
         &self,
@@ -646,6 +713,7 @@ impl<'a> SessionTx<'a> {
             if need_complete_run {
                 debug!("complete run for rule {:?}.{}", rule_symb, rule_n);
                 for item_res in rule.relation.iter(self, None, stores)? {
+                    poison.check()?;
                     out_store.meet_put(item_res?)?;
                 }
                 poison.check()?;
@@ -659,6 +727,7 @@ impl<'a> SessionTx<'a> {
                         delta_key, rule_symb, rule_n
                     );
                     for item_res in rule.relation.iter(self, Some(delta_key), stores)? {
+                        poison.check()?;
                         out_store.meet_put(item_res?)?;
                     }
                     poison.check()?;
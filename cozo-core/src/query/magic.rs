@@ -52,6 +52,7 @@ impl NormalFormProgram {
 }
 
 impl StratifiedNormalFormProgram {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
     pub(crate) fn magic_sets_rewrite(self, tx: &SessionTx<'_>) -> Result<StratifiedMagicProgram> {
         let mut exempt_rules = BTreeSet::from([Symbol::new(PROG_ENTRY, SourceSpan(0, 0))]);
         let mut collected = vec![];
@@ -0,0 +1,313 @@
+/*
+ * Copyright 2022, The Cozo Project Authors.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public License, v. 2.0.
+ * If a copy of the MPL was not distributed with this file,
+ * You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Runtime statistics collected by `::explain analyze`.
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::mem;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+
+use miette::Result;
+
+use crate::data::program::MagicSymbol;
+use crate::data::tuple::{Tuple, TupleIter};
+use crate::data::value::ValidityTs;
+use crate::query::ra::RelAlgebra;
+use crate::runtime::transact::SessionTx;
+use crate::storage::StoreTx;
+use crate::NamedRows;
+
+/// Runtime statistics of a single rule.
+#[derive(Debug, Default)]
+pub(crate) struct RuleProfile {
+    /// number of epochs in which the rule was evaluated
+    pub(crate) epochs: u32,
+    /// total wall time spent evaluating the rule, in seconds
+    pub(crate) elapsed: f64,
+    /// number of rows in the rule's store once its stratum is done
+    pub(crate) rows: usize,
+}
+
+/// Storage accesses made while evaluating a query.
+#[derive(Debug, Default)]
+pub(crate) struct StorageStats {
+    /// number of point lookups, and of rows returned by scans
+    pub(crate) gets: AtomicU64,
+    /// number of keys written or deleted
+    pub(crate) puts: AtomicU64,
+    /// total size of the keys and values returned by the storage
+    pub(crate) bytes_read: AtomicU64,
+}
+
+/// Runtime statistics of a single operator of the relational algebra.
+#[derive(Debug, Default)]
+pub(crate) struct OperatorStats {
+    /// number of tuples produced, over all epochs
+    pub(crate) rows: AtomicU64,
+    /// time spent producing the tuples, including the time spent in the inputs,
+    /// in nanoseconds; not measured on WASM
+    nanos: AtomicU64,
+    /// storage accesses made by the operator itself, not by its inputs
+    pub(crate) storage: StorageStats,
+}
+
+fn bump(counter: &AtomicU64, n: u64) {
+    counter.fetch_add(n, Ordering::Relaxed);
+}
+
+thread_local! {
+    /// The operator currently producing a tuple on this thread, to which storage
+    /// accesses are attributed.
+    static CURRENT_OPERATOR: RefCell<Option<Arc<OperatorStats>>> = const { RefCell::new(None) };
+}
+
+impl OperatorStats {
+    /// Total time spent producing the tuples.
+    pub(crate) fn elapsed(&self) -> Duration {
+        Duration::from_nanos(self.nanos.load(Ordering::Relaxed))
+    }
+    /// Runs `f` as part of this operator, adding the time it takes to the operator.
+    fn measure<T>(self: &Arc<Self>, f: impl FnOnce() -> T) -> T {
+        let prev = CURRENT_OPERATOR.with(|cur| cur.replace(Some(self.clone())));
+        #[cfg(not(target_arch = "wasm32"))]
+        let started = Instant::now();
+        let ret = f();
+        #[cfg(not(target_arch = "wasm32"))]
+        bump(&self.nanos, started.elapsed().as_nanos() as u64);
+        CURRENT_OPERATOR.with(|cur| cur.replace(prev));
+        ret
+    }
+}
+
+/// Collects the statistics of one evaluation of a query.
+///
+/// Rules are keyed by stratum and name. Operators are keyed by their address,
+/// which is stable as long as the compiled program they belong to is alive.
+#[derive(Debug, Default)]
+pub(crate) struct EvalProfile {
+    pub(crate) rules: Mutex<BTreeMap<(usize, MagicSymbol), RuleProfile>>,
+    operators: Mutex<BTreeMap<usize, Arc<OperatorStats>>>,
+    /// all storage accesses of the query, including those made outside of operators
+    /// such as writes into stored relations
+    pub(crate) storage: StorageStats,
+    /// the operators of the query with their statistics, set once evaluation is done
+    pub(crate) plan: Mutex<Option<NamedRows>>,
+}
+
+impl EvalProfile {
+    pub(crate) fn record_run(&self, stratum: usize, rule: &MagicSymbol, elapsed: f64) {
+        let mut rules = self.rules.lock().unwrap();
+        let entry = rules.entry((stratum, rule.clone())).or_default();
+        entry.epochs += 1;
+        entry.elapsed += elapsed;
+    }
+    pub(crate) fn record_rows(&self, stratum: usize, rule: &MagicSymbol, rows: usize) {
+        let mut rules = self.rules.lock().unwrap();
+        rules.entry((stratum, rule.clone())).or_default().rows = rows;
+    }
+    /// The statistics of an operator, if it has been iterated.
+    pub(crate) fn operator(&self, rel: &RelAlgebra) -> Option<Arc<OperatorStats>> {
+        let key = rel as *const RelAlgebra as usize;
+        self.operators.lock().unwrap().get(&key).cloned()
+    }
+    /// Wraps the creation and the iteration of `rel` so that the statistics of the
+    /// operator are recorded.
+    pub(crate) fn iter_operator<'a>(
+        &self,
+        rel: &RelAlgebra,
+        make_iter: impl FnOnce() -> Result<TupleIter<'a>>,
+    ) -> Result<TupleIter<'a>> {
+        let key = rel as *const RelAlgebra as usize;
+        let stats = self
+            .operators
+            .lock()
+            .unwrap()
+            .entry(key)
+            .or_default()
+            .clone();
+        let mut inner = stats.measure(make_iter)?;
+        Ok(Box::new(std::iter::from_fn(move || {
+            let item = stats.measure(|| inner.next());
+            if let Some(Ok(_)) = &item {
+                bump(&stats.rows, 1);
+            }
+            item
+        })))
+    }
+    fn record_storage(&self, field: fn(&StorageStats) -> &AtomicU64, n: u64) {
+        bump(field(&self.storage), n);
+        CURRENT_OPERATOR.with(|cur| {
+            if let Some(op) = &*cur.borrow() {
+                bump(field(&op.storage), n);
+            }
+        });
+    }
+    fn record_kv(&self, res: &Result<(Vec<u8>, Vec<u8>)>) {
+        if let Ok((k, v)) = res {
+            self.record_storage(|s| &s.gets, 1);
+            self.record_storage(|s| &s.bytes_read, (k.len() + v.len()) as u64);
+        }
+    }
+}
+
+impl<'a> SessionTx<'a> {
+    /// Runs `f` on a transaction that shares the state of this one, but records the
+    /// evaluation and the storage accesses into `profile`.
+    pub(crate) fn with_profile<T>(
+        &mut self,
+        profile: Arc<EvalProfile>,
+        f: impl FnOnce(&mut SessionTx<'_>) -> Result<T>,
+    ) -> Result<T> {
+        let mut profiled = SessionTx {
+            store_tx: Box::new(ProfiledStoreTx {
+                inner: &mut *self.store_tx,
+                profile: profile.clone(),
+            }),
+            temp_store_tx: mem::take(&mut self.temp_store_tx),
+            relation_store_id: self.relation_store_id.clone(),
+            temp_store_id: self.temp_store_id.load(Ordering::Acquire).into(),
+            tokenizers: self.tokenizers.clone(),
+            parallel_branches: self.parallel_branches,
+            profile: Some(profile),
+            metrics: self.metrics.clone(),
+            rows_written: 0,
+            rows_removed: 0,
+            rows_read: Default::default(),
+        };
+        let res = f(&mut profiled);
+        self.rows_written += profiled.rows_written;
+        self.rows_removed += profiled.rows_removed;
+        self.temp_store_tx = mem::take(&mut profiled.temp_store_tx);
+        self.temp_store_id.store(
+            profiled.temp_store_id.load(Ordering::Acquire),
+            Ordering::Release,
+        );
+        res
+    }
+}
+
+/// Passes everything through to the transaction it wraps, counting the accesses.
+struct ProfiledStoreTx<'b, 'a> {
+    inner: &'b mut (dyn StoreTx<'a> + 'a),
+    profile: Arc<EvalProfile>,
+}
+
+impl<'b, 'a: 'b> StoreTx<'b> for ProfiledStoreTx<'b, 'a> {
+    fn get(&self, key: &[u8], for_update: bool) -> Result<Option<Vec<u8>>> {
+        let ret = self.inner.get(key, for_update)?;
+        self.profile.record_storage(|s| &s.gets, 1);
+        if let Some(val) = &ret {
+            self.profile
+                .record_storage(|s| &s.bytes_read, val.len() as u64);
+        }
+        Ok(ret)
+    }
+
+    fn multi_get(&self, keys: &[Vec<u8>], for_update: bool) -> Result<Vec<Option<Vec<u8>>>> {
+        let ret = self.inner.multi_get(keys, for_update)?;
+        self.profile.record_storage(|s| &s.gets, keys.len() as u64);
+        let bytes: usize = ret.iter().flatten().map(|val| val.len()).sum();
+        self.profile.record_storage(|s| &s.bytes_read, bytes as u64);
+        Ok(ret)
+    }
+
+    fn put(&mut self, key: &[u8], val: &[u8]) -> Result<()> {
+        self.profile.record_storage(|s| &s.puts, 1);
+        self.inner.put(key, val)
+    }
+
+    fn supports_par_put(&self) -> bool {
+        self.inner.supports_par_put()
+    }
+
+    fn par_put(&self, key: &[u8], val: &[u8]) -> Result<()> {
+        self.profile.record_storage(|s| &s.puts, 1);
+        self.inner.par_put(key, val)
+    }
+
+    fn del(&mut self, key: &[u8]) -> Result<()> {
+        self.profile.record_storage(|s| &s.puts, 1);
+        self.inner.del(key)
+    }
+
+    fn par_del(&self, key: &[u8]) -> Result<()> {
+        self.profile.record_storage(|s| &s.puts, 1);
+        self.inner.par_del(key)
+    }
+
+    fn del_range_from_persisted(&mut self, lower: &[u8], upper: &[u8]) -> Result<()> {
+        self.inner.del_range_from_persisted(lower, upper)
+    }
+
+    fn exists(&self, key: &[u8], for_update: bool) -> Result<bool> {
+        self.profile.record_storage(|s| &s.gets, 1);
+        self.inner.exists(key, for_update)
+    }
+
+    fn commit(&mut self) -> Result<()> {
+        self.inner.commit()
+    }
+
+    fn range_skip_scan_tuple<'c>(
+        &'c self,
+        lower: &[u8],
+        upper: &[u8],
+        valid_at: ValidityTs,
+    ) -> Box<dyn Iterator<Item = Result<Tuple>> + 'c> {
+        let profile = self.profile.clone();
+        Box::new(
+            self.inner
+                .range_skip_scan_tuple(lower, upper, valid_at)
+                .inspect(move |res| {
+                    if res.is_ok() {
+                        profile.record_storage(|s| &s.gets, 1);
+                    }
+                }),
+        )
+    }
+
+    fn range_scan<'c>(
+        &'c self,
+        lower: &[u8],
+        upper: &[u8],
+    ) -> Box<dyn Iterator<Item = Result<(Vec<u8>, Vec<u8>)>> + 'c>
+    where
+        'b: 'c,
+    {
+        let profile = self.profile.clone();
+        Box::new(
+            self.inner
+                .range_scan(lower, upper)
+                .inspect(move |res| profile.record_kv(res)),
+        )
+    }
+
+    fn range_count<'c>(&'c self, lower: &[u8], upper: &[u8]) -> Result<usize>
+    where
+        'b: 'c,
+    {
+        self.inner.range_count(lower, upper)
+    }
+
+    fn total_scan<'c>(&'c self) -> Box<dyn Iterator<Item = Result<(Vec<u8>, Vec<u8>)>> + 'c>
+    where
+        'b: 'c,
+    {
+        let profile = self.profile.clone();
+        Box::new(
+            self.inner
+                .total_scan()
+                .inspect(move |res| profile.record_kv(res)),
+        )
+    }
+}
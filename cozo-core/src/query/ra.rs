@@ -1896,11 +1896,62 @@ impl RelAlgebra {
             }
         }
     }
+    /// Name of the operator in tracing spans, as in the output of `::explain`.
+    #[cfg(feature = "tracing")]
+    fn kind(&self) -> &str {
+        match self {
+            RelAlgebra::Fixed(_) => "fixed",
+            RelAlgebra::TempStore(_) => "load_mem",
+            RelAlgebra::Stored(_) => "load_stored",
+            RelAlgebra::StoredWithValidity(_) => "load_stored_with_validity",
+            RelAlgebra::Join(j) => j.join_type(),
+            RelAlgebra::Reorder(_) => "reorder",
+            RelAlgebra::Filter(_) => "filter",
+            RelAlgebra::NegJoin(j) => j.join_type(),
+            RelAlgebra::Unification(u) if u.is_multi => "multi-unify",
+            RelAlgebra::Unification(_) => "unify",
+            RelAlgebra::HnswSearch(_) => "hnsw_index",
+            RelAlgebra::FtsSearch(_) => "fts_index",
+            RelAlgebra::LshSearch(_) => "lsh_index",
+        }
+    }
+    /// Iterates over the tuples of the operator. With the `tracing` feature, the creation
+    /// of the iterator and each step of it are in a `debug` level span of the operator.
     pub(crate) fn iter<'a>(
         &'a self,
         tx: &'a SessionTx<'_>,
         delta_rule: Option<&MagicSymbol>,
         stores: &'a BTreeMap<MagicSymbol, EpochStore>,
+    ) -> Result<TupleIter<'a>> {
+        #[cfg(feature = "tracing")]
+        {
+            let span = tracing::debug_span!("operator", op = self.kind());
+            let mut inner = span.in_scope(|| self.iter_untraced(tx, delta_rule, stores))?;
+            Ok(Box::new(std::iter::from_fn(move || {
+                span.in_scope(|| inner.next())
+            })))
+        }
+        #[cfg(not(feature = "tracing"))]
+        self.iter_untraced(tx, delta_rule, stores)
+    }
+    fn iter_untraced<'a>(
+        &'a self,
+        tx: &'a SessionTx<'_>,
+        delta_rule: Option<&MagicSymbol>,
+        stores: &'a BTreeMap<MagicSymbol, EpochStore>,
+    ) -> Result<TupleIter<'a>> {
+        match &tx.profile {
+            None => self.iter_unprofiled(tx, delta_rule, stores),
+            Some(profile) => {
+                profile.iter_operator(self, || self.iter_unprofiled(tx, delta_rule, stores))
+            }
+        }
+    }
+    fn iter_unprofiled<'a>(
+        &'a self,
+        tx: &'a SessionTx<'_>,
+        delta_rule: Option<&MagicSymbol>,
+        stores: &'a BTreeMap<MagicSymbol, EpochStore>,
     ) -> Result<TupleIter<'a>> {
         match self {
             RelAlgebra::Fixed(f) => Ok(Box::new(f.data.iter().map(|t| Ok(t.clone())))),
@@ -6,14 +6,22 @@
  * You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 use std::mem;
 
+use itertools::Itertools;
 use miette::{bail, Diagnostic, Result};
 use thiserror::Error;
 
-use crate::data::program::{NormalFormAtom, NormalFormInlineRule};
+use crate::data::expr::Expr;
+use crate::data::functions::OP_EQ;
+use crate::data::program::{NormalFormAtom, NormalFormInlineRule, Unification};
+use crate::data::relation::ColType;
+use crate::data::symb::Symbol;
+use crate::data::value::{DataValue, Num};
 use crate::parse::SourceSpan;
+use crate::runtime::relation::{RelationHandle, RelationStats};
+use crate::runtime::transact::SessionTx;
 
 #[derive(Diagnostic, Debug, Error)]
 #[error("Encountered unsafe negation, or empty rule definition")]
@@ -31,13 +39,16 @@ pub(crate) struct UnsafeNegation(#[label] pub(crate) SourceSpan);
 pub(crate) struct UnboundVariable(#[label] pub(crate) SourceSpan);
 
 impl NormalFormInlineRule {
-    pub(crate) fn convert_to_well_ordered_rule(self) -> Result<Self> {
+    pub(crate) fn convert_to_well_ordered_rule(self, tx: &SessionTx<'_>) -> Result<Self> {
         let mut seen_variables = BTreeSet::default();
         let mut round_1_collected = vec![];
         let mut pending = vec![];
 
+        let body = Self::equality_filters_to_unifications(self.body, tx);
+        let body = Self::order_relations_by_stats(body, tx);
+
         // first round: collect all unifications that are completely bounded
-        for atom in self.body {
+        for atom in body {
             match atom {
                 NormalFormAtom::Unification(u) => {
                     if u.is_const() {
@@ -239,4 +250,231 @@ impl NormalFormInlineRule {
             body: collected,
         })
     }
+
+    /// Reorders the stored relations of the body using the statistics collected by `::analyze`.
+    /// The relation expected to read the fewest rows is joined first, then each following one
+    /// is the one expected to read the fewest rows per lookup, given the variables bound
+    /// so far. Reads through a bound prefix of the keys or of an index are counted as lookups.
+    ///
+    /// The relations take the places of one another among the other atoms, whose order
+    /// is fixed later. Unless all the relations have statistics, the body is left alone.
+    fn order_relations_by_stats(
+        mut body: Vec<NormalFormAtom>,
+        tx: &SessionTx<'_>,
+    ) -> Vec<NormalFormAtom> {
+        let slots = body
+            .iter()
+            .positions(|atom| matches!(atom, NormalFormAtom::Relation(_)))
+            .collect_vec();
+        if slots.len() < 2 {
+            return body;
+        }
+        let handles: Option<Vec<(RelationHandle, RelationStats)>> = slots
+            .iter()
+            .map(|i| match &body[*i] {
+                NormalFormAtom::Relation(r) => {
+                    let handle = tx.get_relation(&r.name, false).ok()?;
+                    let stats = tx.get_relation_stats(&handle).ok()??;
+                    Some((handle, stats))
+                }
+                _ => unreachable!(),
+            })
+            .collect();
+        let Some(handles) = handles else {
+            return body;
+        };
+
+        // constants are known before any relation is joined
+        let (mut head, rest): (Vec<_>, Vec<_>) = body
+            .into_iter()
+            .partition(|atom| matches!(atom, NormalFormAtom::Unification(u) if u.is_const()));
+        body = rest;
+        let slots = body
+            .iter()
+            .positions(|atom| matches!(atom, NormalFormAtom::Relation(_)))
+            .collect_vec();
+        let mut bound: BTreeSet<Symbol> = head
+            .iter()
+            .chain(body[..slots[0]].iter())
+            .flat_map(|atom| match atom {
+                NormalFormAtom::Unification(u) if u.is_const() => vec![u.binding.clone()],
+                NormalFormAtom::Rule(r) => r.args.clone(),
+                _ => vec![],
+            })
+            .collect();
+
+        let mut relations = slots
+            .iter()
+            .zip(handles)
+            .map(|(i, handle)| match &body[*i] {
+                NormalFormAtom::Relation(r) => (r.clone(), handle),
+                _ => unreachable!(),
+            })
+            .collect_vec();
+        for slot in slots {
+            let mut best = 0;
+            let mut best_estimate = f64::INFINITY;
+            for (i, (r, (handle, stats))) in relations.iter().enumerate() {
+                let estimate = estimate_rows_read(handle, stats, &r.args, &bound);
+                // ties keep the order of the query
+                if estimate < best_estimate {
+                    best = i;
+                    best_estimate = estimate;
+                }
+            }
+            let (r, _) = relations.remove(best);
+            bound.extend(r.args.iter().cloned());
+            body[slot] = NormalFormAtom::Relation(r);
+        }
+        head.extend(body);
+        head
+    }
+
+    /// Rewrites filters of the form `x == <const>`, where `x` is bound by a stored relation,
+    /// into constant unifications placed at the start of the body, so that the binding is
+    /// known when the relation is joined. The compiler can then use a prefix scan on the
+    /// primary key or on one of the indices instead of a full scan followed by a filter.
+    ///
+    /// `==` considers `1` and `1.0` equal, whereas keys in storage do not. Numbers are
+    /// converted to the type of the columns `x` is bound to, so they are rewritten only
+    /// when all these columns are typed `Int`, or all are typed `Float`.
+    fn equality_filters_to_unifications(
+        body: Vec<NormalFormAtom>,
+        tx: &SessionTx<'_>,
+    ) -> Vec<NormalFormAtom> {
+        // the types of the columns each variable is bound to
+        let mut relation_vars: BTreeMap<Symbol, Vec<ColType>> = BTreeMap::default();
+        let mut unified: BTreeSet<Symbol> = BTreeSet::default();
+        for atom in &body {
+            match atom {
+                NormalFormAtom::Relation(r) => {
+                    let columns = tx.get_relation(&r.name, false).ok().map(|handle| {
+                        handle
+                            .metadata
+                            .keys
+                            .into_iter()
+                            .chain(handle.metadata.non_keys)
+                            .map(|col| col.typing.coltype)
+                            .collect_vec()
+                    });
+                    for (i, arg) in r.args.iter().enumerate() {
+                        let coltype = columns
+                            .as_ref()
+                            .and_then(|cols| cols.get(i).cloned())
+                            .unwrap_or(ColType::Any);
+                        relation_vars.entry(arg.clone()).or_default().push(coltype);
+                    }
+                }
+                NormalFormAtom::Unification(u) => {
+                    unified.insert(u.binding.clone());
+                }
+                _ => {}
+            }
+        }
+        if relation_vars.is_empty() {
+            return body;
+        }
+
+        let mut unifications = vec![];
+        let mut rest = vec![];
+        for atom in body {
+            match atom {
+                NormalFormAtom::Predicate(Expr::Apply { op, args, span })
+                    if op.name == OP_EQ.name && args.len() == 2 =>
+                {
+                    let binding_and_const = match (&args[0], &args[1]) {
+                        (Expr::Binding { var, .. }, Expr::Const { val, .. })
+                        | (Expr::Const { val, .. }, Expr::Binding { var, .. }) => {
+                            Some((var.clone(), val.clone()))
+                        }
+                        _ => None,
+                    };
+                    let exact = binding_and_const.and_then(|(var, val)| {
+                        if unified.contains(&var) {
+                            return None;
+                        }
+                        let val = exact_key_value(val, relation_vars.get(&var)?)?;
+                        Some((var, val))
+                    });
+                    match exact {
+                        Some((var, val)) => {
+                            unified.insert(var.clone());
+                            unifications.push(NormalFormAtom::Unification(Unification {
+                                binding: var,
+                                expr: Expr::Const { val, span },
+                                one_many_unif: false,
+                                span,
+                            }))
+                        }
+                        _ => rest.push(NormalFormAtom::Predicate(Expr::Apply { op, args, span })),
+                    }
+                }
+                atom => rest.push(atom),
+            }
+        }
+        unifications.extend(rest);
+        unifications
+    }
+}
+
+/// Estimated number of rows read from a stored relation for each lookup,
+/// when the variables in `bound` are known.
+fn estimate_rows_read(
+    handle: &RelationHandle,
+    stats: &RelationStats,
+    args: &[Symbol],
+    bound: &BTreeSet<Symbol>,
+) -> f64 {
+    let bound_prefix = |positions: &mut dyn Iterator<Item = usize>| {
+        positions
+            .take_while(|i| args.get(*i).is_some_and(|arg| bound.contains(arg)))
+            .collect_vec()
+    };
+    let mut estimate = stats.estimate_rows(&bound_prefix(&mut (0..handle.metadata.keys.len())));
+    for (_, mapper) in handle.indices.values() {
+        let prefix = bound_prefix(&mut mapper.iter().copied());
+        if !prefix.is_empty() {
+            estimate = estimate.min(stats.estimate_rows(&prefix));
+        }
+    }
+    estimate
+}
+
+/// The value that stored keys in columns of all the types `coltypes` are equal to
+/// exactly when `==` against `val` is true, if there is one.
+fn exact_key_value(val: DataValue, coltypes: &[ColType]) -> Option<DataValue> {
+    if is_exact_key_value(&val) {
+        return Some(val);
+    }
+    let all_of = |coltype: ColType| coltypes.iter().all(|t| *t == coltype);
+    // `==` compares integers with floats after converting them to floats
+    let f = match val {
+        DataValue::Num(Num::Int(_)) if all_of(ColType::Int) => return Some(val),
+        DataValue::Num(Num::Int(i)) => i as f64,
+        DataValue::Num(Num::Float(f)) => f,
+        _ => return None,
+    };
+    if all_of(ColType::Float) {
+        // -0.0 == 0.0 and NaN != NaN, unlike keys
+        (f != 0. && !f.is_nan()).then(|| DataValue::from(f))
+    } else if all_of(ColType::Int) {
+        // beyond 2^53, several integers are converted to the same float
+        (f.fract() == 0. && f.abs() <= (1u64 << 53) as f64).then(|| DataValue::from(f as i64))
+    } else {
+        None
+    }
+}
+
+/// Whether `==` against this value agrees with equality of stored keys of any type.
+fn is_exact_key_value(val: &DataValue) -> bool {
+    match val {
+        DataValue::Null
+        | DataValue::Bool(_)
+        | DataValue::Str(_)
+        | DataValue::Bytes(_)
+        | DataValue::Uuid(_) => true,
+        DataValue::List(l) => l.iter().all(is_exact_key_value),
+        DataValue::Set(s) => s.iter().all(is_exact_key_value),
+        _ => false,
+    }
 }
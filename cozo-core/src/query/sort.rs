@@ -19,6 +19,7 @@ use crate::runtime::temp_store::EpochStore;
 use crate::runtime::transact::SessionTx;
 
 impl<'a> SessionTx<'a> {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
     pub(crate) fn sort_and_collect(
         &mut self,
         original: EpochStore,
@@ -7,6 +7,7 @@
  */
 
 use std::collections::{BTreeMap, BTreeSet};
+use std::mem;
 use std::sync::Arc;
 
 use itertools::Itertools;
@@ -41,6 +42,7 @@ use crate::{Db, NamedRows, SourceSpan, StoreTx};
 struct RelationArityMismatch(String, usize, usize);
 
 impl<'a> SessionTx<'a> {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
     pub(crate) fn execute_relation<'s, S: Storage<'s>>(
         &mut self,
         db: &Db<S>,
@@ -53,6 +55,7 @@ impl<'a> SessionTx<'a> {
         callback_collector: &mut CallbackCollector,
         propagate_triggers: bool,
         force_collect: &str,
+        lenient: bool,
     ) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
         let mut to_clear = vec![];
         let mut replaced_old_triggers = None;
@@ -99,6 +102,7 @@ impl<'a> SessionTx<'a> {
                             callback_targets,
                             callback_collector,
                             false,
+                            None,
                         )
                         .map_err(|err| {
                             if err.source_code().is_some() {
@@ -198,6 +202,7 @@ impl<'a> SessionTx<'a> {
                     dep_bindings,
                     op == RelationOp::Insert,
                     force_collect,
+                    lenient,
                     *span,
                 )?,
         };
@@ -221,6 +226,7 @@ impl<'a> SessionTx<'a> {
         dep_bindings: &[Symbol],
         is_insert: bool,
         force_collect: &str,
+        lenient: bool,
         span: SourceSpan,
     ) -> Result<()> {
         let is_callback_target = callback_targets.contains(&relation_store.name)
@@ -234,12 +240,16 @@ impl<'a> SessionTx<'a> {
             ));
         }
 
+        let mut missing = vec![];
         let mut key_extractors = make_extractors(
+            &relation_store.name,
             &relation_store.metadata.keys,
             &metadata.keys,
             key_bindings,
             headers,
-        )?;
+            lenient,
+            &mut missing,
+        );
 
         let need_to_collect = !force_collect.is_empty()
             || (!relation_store.is_temp
@@ -254,32 +264,46 @@ impl<'a> SessionTx<'a> {
 
         let val_extractors = if metadata.non_keys.is_empty() {
             make_extractors(
+                &relation_store.name,
                 &relation_store.metadata.non_keys,
                 &metadata.keys,
                 key_bindings,
                 headers,
-            )?
+                lenient,
+                &mut missing,
+            )
         } else {
             make_extractors(
+                &relation_store.name,
                 &relation_store.metadata.non_keys,
                 &metadata.non_keys,
                 dep_bindings,
                 headers,
-            )?
+                lenient,
+                &mut missing,
+            )
         };
+        ensure_no_missing_columns(&relation_store.name, missing, span)?;
         key_extractors.extend(val_extractors);
         let mut stack = vec![];
         let hnsw_filters = Self::make_hnsw_filters(relation_store)?;
         let fts_lsh_processors = self.make_fts_lsh_processors(relation_store)?;
         let lsh_perms = self.make_lsh_hash_perms(relation_store);
 
+        // buffers reused across rows, so that the hot loop does not allocate per row
+        let mut extracted: Vec<DataValue> = Vec::with_capacity(key_extractors.len());
+        let mut key = vec![];
+        let mut val = vec![];
+        let mut n_written = 0u64;
+
         for tuple in res_iter {
-            let extracted: Vec<DataValue> = key_extractors
-                .iter()
-                .map(|ex| ex.extract_data(&tuple, cur_vld))
-                .try_collect()?;
+            extracted.clear();
+            extracted.reserve(key_extractors.len());
+            for ex in &key_extractors {
+                extracted.push(ex.extract_data(&tuple, cur_vld)?);
+            }
 
-            let key = relation_store.encode_key_for_store(&extracted, span)?;
+            relation_store.encode_key_into(&extracted, &mut key, span)?;
 
             if is_insert {
                 let already_exists = if relation_store.is_temp {
@@ -289,15 +313,15 @@ impl<'a> SessionTx<'a> {
                 };
 
                 if already_exists {
-                    bail!(TransactAssertionFailure {
-                        relation: relation_store.name.to_string(),
-                        key: extracted,
-                        notice: "key exists in database".to_string()
-                    });
+                    bail!(TransactAssertionFailure::new(
+                        relation_store,
+                        &extracted,
+                        "key exists in database"
+                    ));
                 }
             }
 
-            let val = relation_store.encode_val_for_store(&extracted, span)?;
+            relation_store.encode_val_into(&extracted, &mut val);
 
             if need_to_collect
                 || has_indices
@@ -340,7 +364,9 @@ impl<'a> SessionTx<'a> {
                 )?;
 
                 if need_to_collect {
-                    new_tuples.push(DataValue::List(extracted));
+                    // the collected row keeps the buffer, a new one is allocated for the
+                    // next row only in this case
+                    new_tuples.push(DataValue::List(mem::take(&mut extracted)));
                 }
             }
 
@@ -348,8 +374,10 @@ impl<'a> SessionTx<'a> {
                 self.temp_store_tx.put(&key, &val)?;
             } else {
                 self.store_tx.put(&key, &val)?;
+                n_written += 1;
             }
         }
+        self.rows_written += n_written;
 
         if need_to_collect && !new_tuples.is_empty() {
             self.collect_mutations(
@@ -544,12 +572,16 @@ impl<'a> SessionTx<'a> {
             ));
         }
 
+        let mut missing = vec![];
         let key_extractors = make_extractors(
+            &relation_store.name,
             &relation_store.metadata.keys,
             &metadata.keys,
             key_bindings,
             headers,
-        )?;
+            false,
+            &mut missing,
+        );
 
         let need_to_collect = !force_collect.is_empty()
             || (!relation_store.is_temp
@@ -563,16 +595,20 @@ impl<'a> SessionTx<'a> {
         let mut old_tuples: Vec<DataValue> = vec![];
 
         let val_extractors = make_update_extractors(
+            &relation_store.name,
             &relation_store.metadata.non_keys,
             &metadata.keys,
             key_bindings,
             headers,
-        )?;
+            &mut missing,
+        );
+        ensure_no_missing_columns(&relation_store.name, missing, span)?;
 
         let mut stack = vec![];
         let hnsw_filters = Self::make_hnsw_filters(relation_store)?;
         let fts_lsh_processors = self.make_fts_lsh_processors(relation_store)?;
         let lsh_perms = self.make_lsh_hash_perms(relation_store);
+        let mut n_written = 0u64;
 
         for tuple in res_iter {
             let mut new_kv: Vec<DataValue> = key_extractors
@@ -588,11 +624,11 @@ impl<'a> SessionTx<'a> {
             };
             let original_val: Tuple = match original_val_bytes {
                 None => {
-                    bail!(TransactAssertionFailure {
-                        relation: relation_store.name.to_string(),
-                        key: new_kv,
-                        notice: "key to update does not exist".to_string()
-                    })
+                    bail!(TransactAssertionFailure::new(
+                        relation_store,
+                        &new_kv,
+                        "key to update does not exist"
+                    ))
                 }
                 Some(v) => rmp_serde::from_slice(&v[ENCODED_KEY_MIN_LEN..]).unwrap(),
             };
@@ -646,8 +682,10 @@ impl<'a> SessionTx<'a> {
                 self.temp_store_tx.put(&key, &new_val)?;
             } else {
                 self.store_tx.put(&key, &new_val)?;
+                n_written += 1;
             }
         }
+        self.rows_written += n_written;
 
         if need_to_collect && !new_tuples.is_empty() {
             self.collect_mutations(
@@ -724,6 +762,7 @@ impl<'a> SessionTx<'a> {
                         callback_targets,
                         callback_collector,
                         false,
+                        None,
                     )
                     .map_err(|err| {
                         if err.source_code().is_some() {
@@ -813,12 +852,17 @@ impl<'a> SessionTx<'a> {
             ));
         }
 
+        let mut missing = vec![];
         let key_extractors = make_extractors(
+            &relation_store.name,
             &relation_store.metadata.keys,
             &metadata.keys,
             key_bindings,
             headers,
-        )?;
+            false,
+            &mut missing,
+        );
+        ensure_no_missing_columns(&relation_store.name, missing, span)?;
 
         for tuple in res_iter {
             let extracted: Vec<DataValue> = key_extractors
@@ -832,11 +876,11 @@ impl<'a> SessionTx<'a> {
                 self.store_tx.exists(&key, true)?
             };
             if already_exists {
-                bail!(TransactAssertionFailure {
-                    relation: relation_store.name.to_string(),
-                    key: extracted,
-                    notice: "key exists in database".to_string()
-                })
+                bail!(TransactAssertionFailure::new(
+                    relation_store,
+                    &extracted,
+                    "key exists in database"
+                ))
             }
         }
         Ok(())
@@ -860,19 +904,27 @@ impl<'a> SessionTx<'a> {
             ));
         }
 
+        let mut missing = vec![];
         let mut key_extractors = make_extractors(
+            &relation_store.name,
             &relation_store.metadata.keys,
             &metadata.keys,
             key_bindings,
             headers,
-        )?;
+            false,
+            &mut missing,
+        );
 
         let val_extractors = make_extractors(
+            &relation_store.name,
             &relation_store.metadata.non_keys,
             &metadata.keys,
             key_bindings,
             headers,
-        )?;
+            false,
+            &mut missing,
+        );
+        ensure_no_missing_columns(&relation_store.name, missing, span)?;
         key_extractors.extend(val_extractors);
 
         for tuple in res_iter {
@@ -891,19 +943,19 @@ impl<'a> SessionTx<'a> {
             };
             match existing {
                 None => {
-                    bail!(TransactAssertionFailure {
-                        relation: relation_store.name.to_string(),
-                        key: extracted,
-                        notice: "key does not exist in database".to_string()
-                    })
+                    bail!(TransactAssertionFailure::new(
+                        relation_store,
+                        &extracted,
+                        "key does not exist in database"
+                    ))
                 }
                 Some(v) => {
                     if &v as &[u8] != &val as &[u8] {
-                        bail!(TransactAssertionFailure {
-                            relation: relation_store.name.to_string(),
-                            key: extracted,
-                            notice: "key exists in database, but value does not match".to_string()
-                        })
+                        bail!(TransactAssertionFailure::new(
+                            relation_store,
+                            &extracted,
+                            "key exists in database, but value does not match"
+                        ))
                     }
                 }
             }
@@ -938,12 +990,17 @@ impl<'a> SessionTx<'a> {
                 relation_store.access_level
             ));
         }
+        let mut missing = vec![];
         let key_extractors = make_extractors(
+            &relation_store.name,
             &relation_store.metadata.keys,
             &metadata.keys,
             key_bindings,
             headers,
-        )?;
+            false,
+            &mut missing,
+        );
+        ensure_no_missing_columns(&relation_store.name, missing, span)?;
 
         let need_to_collect = !force_collect.is_empty()
             || (!relation_store.is_temp
@@ -957,6 +1014,7 @@ impl<'a> SessionTx<'a> {
         let mut new_tuples: Vec<DataValue> = vec![];
         let mut old_tuples: Vec<DataValue> = vec![];
         let mut stack = vec![];
+        let mut n_removed = 0u64;
 
         for tuple in res_iter {
             let extracted: Vec<DataValue> = key_extractors
@@ -971,11 +1029,11 @@ impl<'a> SessionTx<'a> {
                     self.store_tx.exists(&key, false)?
                 };
                 if !exists {
-                    bail!(TransactAssertionFailure {
-                        relation: relation_store.name.to_string(),
-                        key: extracted,
-                        notice: "key does not exists in database".to_string()
-                    });
+                    bail!(TransactAssertionFailure::new(
+                        relation_store,
+                        &extracted,
+                        "key does not exists in database"
+                    ));
                 }
             }
             if need_to_collect || has_indices || has_hnsw_indices || has_fts_indices || has_lsh_indices {
@@ -1009,8 +1067,10 @@ impl<'a> SessionTx<'a> {
                 self.temp_store_tx.del(&key)?;
             } else {
                 self.store_tx.del(&key)?;
+                n_removed += 1;
             }
         }
+        self.rows_removed += n_removed;
 
         // triggers and callbacks
         if need_to_collect && !new_tuples.is_empty() {
@@ -1057,6 +1117,7 @@ impl<'a> SessionTx<'a> {
                             callback_targets,
                             callback_collector,
                             false,
+                            None,
                         )
                         .map_err(|err| {
                             if err.source_code().is_some() {
@@ -1109,89 +1170,185 @@ impl<'a> SessionTx<'a> {
 }
 
 #[derive(Debug, Error, Diagnostic)]
-#[error("Assertion failure for {key:?} of {relation}: {notice}")]
+#[error("Assertion failure for key {key} of relation {relation}: {notice}")]
 #[diagnostic(code(transact::assertion_failure))]
 struct TransactAssertionFailure {
     relation: String,
-    key: Vec<DataValue>,
+    key: String,
     notice: String,
 }
 
+impl TransactAssertionFailure {
+    /// `tuple` starts with the key columns of `relation`, any further values are ignored.
+    fn new(relation: &RelationHandle, tuple: &[DataValue], notice: &str) -> Self {
+        let key = relation
+            .metadata
+            .keys
+            .iter()
+            .zip(tuple)
+            .map(|(col, val)| format!("{}: {:?}", col.name, val))
+            .join(", ");
+        Self {
+            relation: relation.name.to_string(),
+            key: format!("{{{key}}}"),
+            notice: notice.to_string(),
+        }
+    }
+}
+
+/// The stored column an extractor produces values for, used in error messages.
+struct ExtractorTarget {
+    relation: SmartString<LazyCompact>,
+    column: SmartString<LazyCompact>,
+    typing: NullableColType,
+}
+
 enum DataExtractor {
-    DefaultExtractor(Expr, NullableColType),
-    IndexExtractor(usize, NullableColType),
+    DefaultExtractor(Expr, ExtractorTarget),
+    IndexExtractor(usize, ExtractorTarget),
 }
 
 impl DataExtractor {
     fn extract_data(&self, tuple: &Tuple, cur_vld: ValidityTs) -> Result<DataValue> {
         Ok(match self {
-            DataExtractor::DefaultExtractor(expr, typ) => typ
+            DataExtractor::DefaultExtractor(expr, target) => target
+                .typing
                 .coerce(expr.clone().eval_to_const()?, cur_vld)
-                .wrap_err_with(|| format!("when processing tuple {tuple:?}"))?,
-            DataExtractor::IndexExtractor(i, typ) => typ
+                .wrap_err_with(|| {
+                    format!(
+                        "cannot put the default value into column '{}' of relation '{}', \
+                         which has type {}, when processing tuple {tuple:?}",
+                        target.column, target.relation, target.typing
+                    )
+                })?,
+            DataExtractor::IndexExtractor(i, target) => target
+                .typing
                 .coerce(tuple[*i].clone(), cur_vld)
-                .wrap_err_with(|| format!("when processing tuple {tuple:?}"))?,
+                .wrap_err_with(|| {
+                    format!(
+                        "cannot put value {:?} into column '{}' of relation '{}', \
+                         which has type {}, when processing tuple {tuple:?}",
+                        tuple[*i], target.column, target.relation, target.typing
+                    )
+                })?,
         })
     }
 }
 
+#[derive(Debug, Error, Diagnostic)]
+#[error("cannot write into relation '{relation}': no value given for required columns {columns}")]
+#[diagnostic(code(eval::required_col_not_provided))]
+#[diagnostic(help("Bind these columns in the query, or give them defaults in the schema"))]
+struct MissingRequiredColumns {
+    relation: String,
+    columns: String,
+    #[label]
+    span: SourceSpan,
+}
+
+/// Fails with a single error listing all the columns that
+/// [`make_extractors`] could not find a value for.
+pub(crate) fn ensure_no_missing_columns(
+    relation: &str,
+    missing: Vec<SmartString<LazyCompact>>,
+    span: SourceSpan,
+) -> Result<()> {
+    if missing.is_empty() {
+        return Ok(());
+    }
+    bail!(MissingRequiredColumns {
+        relation: relation.to_string(),
+        columns: missing.iter().map(|c| format!("'{c}'")).join(", "),
+        span,
+    })
+}
+
+/// Columns that are neither bound nor have a default are collected into `missing`
+/// and get no extractor. When `lenient`, nullable columns among them are set to null instead.
 fn make_extractors(
+    relation: &str,
     stored: &[ColumnDef],
     input: &[ColumnDef],
     bindings: &[Symbol],
     tuple_headers: &[Symbol],
-) -> Result<Vec<DataExtractor>> {
+    lenient: bool,
+    missing: &mut Vec<SmartString<LazyCompact>>,
+) -> Vec<DataExtractor> {
     stored
         .iter()
-        .map(|s| make_extractor(s, input, bindings, tuple_headers))
-        .try_collect()
+        .filter_map(|s| {
+            let ret = make_extractor(relation, s, input, bindings, tuple_headers).or_else(|| {
+                (lenient && s.typing.nullable).then(|| {
+                    DataExtractor::DefaultExtractor(
+                        Expr::Const {
+                            val: DataValue::Null,
+                            span: Default::default(),
+                        },
+                        ExtractorTarget {
+                            relation: SmartString::from(relation),
+                            column: s.name.clone(),
+                            typing: s.typing.clone(),
+                        },
+                    )
+                })
+            });
+            if ret.is_none() {
+                missing.push(s.name.clone());
+            }
+            ret
+        })
+        .collect()
 }
 
 fn make_update_extractors(
+    relation: &str,
     stored: &[ColumnDef],
     input: &[ColumnDef],
     bindings: &[Symbol],
     tuple_headers: &[Symbol],
-) -> Result<Vec<Option<DataExtractor>>> {
+    missing: &mut Vec<SmartString<LazyCompact>>,
+) -> Vec<Option<DataExtractor>> {
     let input_keys: BTreeSet<_> = input.iter().map(|b| &b.name).collect();
     let mut extractors = Vec::with_capacity(stored.len());
     for col in stored.iter() {
         if input_keys.contains(&col.name) {
-            extractors.push(Some(make_extractor(col, input, bindings, tuple_headers)?));
+            let extractor = make_extractor(relation, col, input, bindings, tuple_headers);
+            if extractor.is_none() {
+                missing.push(col.name.clone());
+            }
+            extractors.push(extractor);
         } else {
             extractors.push(None);
         }
     }
-    Ok(extractors)
+    extractors
 }
 
 fn make_extractor(
+    relation: &str,
     stored: &ColumnDef,
     input: &[ColumnDef],
     bindings: &[Symbol],
     tuple_headers: &[Symbol],
-) -> Result<DataExtractor> {
+) -> Option<DataExtractor> {
+    let target = || ExtractorTarget {
+        relation: SmartString::from(relation),
+        column: stored.name.clone(),
+        typing: stored.typing.clone(),
+    };
     for (inp_col, inp_binding) in input.iter().zip(bindings.iter()) {
         if inp_col.name == stored.name {
             for (idx, tuple_head) in tuple_headers.iter().enumerate() {
                 if tuple_head == inp_binding {
-                    return Ok(DataExtractor::IndexExtractor(idx, stored.typing.clone()));
+                    return Some(DataExtractor::IndexExtractor(idx, target()));
                 }
             }
         }
     }
-    if let Some(expr) = &stored.default_gen {
-        Ok(DataExtractor::DefaultExtractor(
-            expr.clone(),
-            stored.typing.clone(),
-        ))
-    } else {
-        #[derive(Debug, Error, Diagnostic)]
-        #[error("cannot make extractor for column {0}")]
-        #[diagnostic(code(eval::unable_to_make_extractor))]
-        struct UnableToMakeExtractor(String);
-        Err(UnableToMakeExtractor(stored.name.to_string()).into())
-    }
+    stored
+        .default_gen
+        .as_ref()
+        .map(|expr| DataExtractor::DefaultExtractor(expr.clone(), target()))
 }
 
 fn make_const_rule(
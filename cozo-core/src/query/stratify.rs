@@ -222,6 +222,7 @@ fn make_scc_reduced_graph(
 
 impl NormalFormProgram {
     /// returns the stratified program and the store lifetimes of the intermediate relations
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
     pub(crate) fn into_stratified_program(
         self,
     ) -> Result<(StratifiedNormalFormProgram, BTreeMap<MagicSymbol, usize>)> {
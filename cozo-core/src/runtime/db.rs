@@ -7,13 +7,18 @@
  */
 
 use std::collections::btree_map::Entry;
+use std::collections::hash_map::DefaultHasher;
 use std::collections::{BTreeMap, BTreeSet};
 use std::default::Default;
 use std::fmt::{Debug, Formatter};
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io;
 use std::iter;
+use std::mem;
 use std::path::Path;
 #[allow(unused_imports)]
-use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicU8, Ordering};
 use std::sync::{Arc, Mutex};
 #[allow(unused_imports)]
 use std::thread;
@@ -28,14 +33,18 @@ use itertools::Itertools;
 use miette::Report;
 #[allow(unused_imports)]
 use miette::{bail, ensure, miette, Diagnostic, IntoDiagnostic, Result, WrapErr};
+use rand::Rng;
+use serde::de::DeserializeOwned;
 use serde_json::json;
 use smartstring::{LazyCompact, SmartString};
 use thiserror::Error;
 
+use crate::data::de::deserialize_row;
 use crate::data::functions::current_validity;
 use crate::data::json::JsonValue;
-use crate::data::program::{InputProgram, QueryAssertion, RelationOp, ReturnMutation};
+use crate::data::program::{InputProgram, MagicSymbol, QueryAssertion, RelationOp, ReturnMutation};
 use crate::data::relation::ColumnDef;
+use crate::data::symb::normalize_ident;
 use crate::data::tuple::{Tuple, TupleT};
 use crate::data::value::{DataValue, ValidityTs, LARGEST_UTF_CHAR};
 use crate::fixed_rule::DEFAULT_FIXED_RULES;
@@ -43,6 +52,7 @@ use crate::fts::TokenizerCache;
 use crate::parse::sys::SysOp;
 use crate::parse::{parse_expressions, parse_script, CozoScript, SourceSpan};
 use crate::query::compile::{CompiledProgram, CompiledRule, CompiledRuleSet};
+use crate::query::profile::EvalProfile;
 use crate::query::ra::{
     FilteredRA, FtsSearchRA, HnswSearchRA, InnerJoin, LshSearchRA, NegJoin, RelAlgebra, ReorderRA,
     StoredRA, StoredWithValidityRA, TempStoreRA, UnificationRA,
@@ -52,11 +62,14 @@ use crate::runtime::callback::{
     CallbackCollector, CallbackDeclaration, CallbackOp, EventCallbackRegistry,
 };
 use crate::runtime::relation::{
-    extend_tuple_from_v, AccessLevel, InsufficientAccessLevel, RelationHandle, RelationId,
+    extend_tuple_from_v, try_decode_tuple_from_kv, AccessLevel, ColumnStats,
+    InsufficientAccessLevel, RelationHandle, RelationId, RelationStats,
 };
-use crate::runtime::transact::SessionTx;
+use crate::runtime::transact::{SessionTx, CURRENT_STORAGE_VERSION};
 use crate::storage::temp::TempStorage;
-use crate::storage::Storage;
+#[cfg(feature = "tracing")]
+use crate::storage::traced::TracedStoreTx;
+use crate::storage::{Storage, StoreTx};
 use crate::{decode_tuple_from_kv, FixedRule, Symbol};
 
 pub(crate) struct RunningQueryHandle {
@@ -73,7 +86,105 @@ impl Drop for RunningQueryCleanup {
     fn drop(&mut self) {
         let mut map = self.running_queries.lock().unwrap();
         if let Some(handle) = map.remove(&self.id) {
-            handle.poison.0.store(true, Ordering::Relaxed);
+            handle.poison.kill();
+        }
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct MetricsCounters {
+    pub(crate) scripts: AtomicU64,
+    pub(crate) failed_scripts: AtomicU64,
+    pub(crate) rows_returned: AtomicU64,
+    pub(crate) rows_written: AtomicU64,
+    pub(crate) rows_removed: AtomicU64,
+    pub(crate) rows_read: AtomicU64,
+    pub(crate) failed_commits: AtomicU64,
+}
+
+impl MetricsCounters {
+    fn record_script(&self, res: &Result<NamedRows>) {
+        self.scripts.fetch_add(1, Ordering::Relaxed);
+        match res {
+            Ok(rows) => {
+                self.rows_returned
+                    .fetch_add(rows.rows.len() as u64, Ordering::Relaxed);
+            }
+            Err(_) => {
+                self.failed_scripts.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+/// Counters describing the activity of a database since it was opened.
+#[derive(serde_derive::Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct DbMetrics {
+    /// Number of scripts run, including failed ones
+    pub scripts: u64,
+    /// Number of scripts that returned an error
+    pub failed_scripts: u64,
+    /// Number of queries started, counting each query of an imperative script separately
+    pub queries: u64,
+    /// Number of queries running right now
+    pub running_queries: u64,
+    /// Number of rows returned by successful scripts
+    pub rows_returned: u64,
+    /// Number of rows put into or updated in stored relations
+    pub rows_written: u64,
+    /// Number of rows removed from stored relations
+    pub rows_removed: u64,
+    /// Number of rows read from stored relations and their indices by scans and key lookups,
+    /// including those of failed scripts. Reads by proximity and full-text searches are not counted
+    pub rows_read: u64,
+    /// Number of transactions that failed to commit. This includes conflicting writes, which is
+    /// how RocksDB with optimistic transactions, SQLite when busy and TiKV report them,
+    /// but also any other error of the storage
+    pub failed_commits: u64,
+}
+
+/// Summary information about a database, as returned by [Db::info].
+#[derive(serde_derive::Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct DbInfo {
+    /// The kind of storage engine backing the database, e.g. `"rocksdb"`
+    pub storage_kind: String,
+    /// The version of the encoding used by stored data
+    pub storage_version: u64,
+    /// Number of stored relations, not counting indices
+    pub relations: u64,
+    /// Number of relations holding indices
+    pub indices: u64,
+    /// Where the data is stored on disk, `None` for storages not on the local disk
+    pub path: Option<String>,
+    /// Approximate size in bytes of the files at `path`
+    pub size_on_disk: Option<u64>,
+}
+
+/// The result of checking the stored data, as returned by [Db::verify].
+#[derive(serde_derive::Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct DbVerification {
+    /// Number of relations checked, including indices
+    pub relations: u64,
+    /// Number of rows checked
+    pub rows: u64,
+    /// Total size in bytes of the keys and values checked
+    pub bytes: u64,
+    /// Problems found, empty if the data is intact. At most [MAX_VERIFY_PROBLEMS]
+    /// are listed.
+    pub problems: Vec<String>,
+    /// Number of problems found but not listed in `problems`
+    pub more_problems: u64,
+}
+
+/// The largest number of problems listed by [Db::verify].
+pub const MAX_VERIFY_PROBLEMS: usize = 1000;
+
+impl DbVerification {
+    fn report(&mut self, problem: String) {
+        if self.problems.len() < MAX_VERIFY_PROBLEMS {
+            self.problems.push(problem);
+        } else {
+            self.more_problems += 1;
         }
     }
 }
@@ -100,8 +211,12 @@ pub struct Db<S> {
     relation_store_id: Arc<AtomicU64>,
     pub(crate) queries_count: Arc<AtomicU64>,
     pub(crate) running_queries: Arc<Mutex<BTreeMap<u64, RunningQueryHandle>>>,
+    pub(crate) metrics: Arc<MetricsCounters>,
     pub(crate) fixed_rules: Arc<ShardedLock<BTreeMap<String, Arc<Box<dyn FixedRule>>>>>,
     pub(crate) tokenizers: Arc<TokenizerCache>,
+    parallel_branches: Arc<AtomicBool>,
+    /// timeout in seconds of queries that do not give one, set with `::set timeout = ...`
+    default_timeout: Arc<Mutex<Option<f64>>>,
     #[cfg(not(target_arch = "wasm32"))]
     callback_count: Arc<AtomicU32>,
     #[cfg(not(target_arch = "wasm32"))]
@@ -184,6 +299,50 @@ impl NamedRows {
             "next": nxt,
         })
     }
+    /// Deserialize the rows into `T`, matching headers to field names.
+    /// Values are handed to `T` as they are, without going through JSON: bytes deserialize
+    /// into `Vec<u8>` or `serde_bytes::ByteBuf`, NaN and infinities stay floats,
+    /// UUIDs deserialize from their hyphenated form and validities are
+    /// `(timestamp, is_assert)` pairs. JSON values deserialize from their JSON.
+    /// The headers must be distinct.
+    /// Only the current rows are converted, `next` is ignored.
+    pub fn into_structs<T: DeserializeOwned>(self) -> Result<Vec<T>> {
+        if let Some(header) = self.headers.iter().duplicates().next() {
+            bail!(DuplicateHeader(header.clone()))
+        }
+        let headers = self.headers;
+        self.rows
+            .into_iter()
+            .map(|row| Ok(deserialize_row(&headers, row)?))
+            .collect()
+    }
+    /// Write the rows as CSV, with the headers as the first record.
+    ///
+    /// Strings are written as they are, and other values as their JSON representation.
+    /// Nulls are written as [CsvOptions::null_repr] and never quoted, whereas strings equal
+    /// to it are always quoted, so that the two can be told apart. For this reason it
+    /// may not contain the delimiter, a quote or a line break.
+    /// Other fields are quoted if [CsvOptions::quote_all] is set or if they contain
+    /// the delimiter, a quote or a line break.
+    /// Only the current rows are written, `next` is ignored.
+    /// To write the result of a query without collecting it first, use [Db::export_csv].
+    pub fn write_csv<W: io::Write>(&self, writer: W, options: &CsvOptions) -> Result<()> {
+        let mut sink = CsvSink::new(writer, options)?;
+        sink.headers(&self.headers)?;
+        for row in &self.rows {
+            sink.write_row(row)?;
+        }
+        sink.flush()
+    }
+    /// Hand the headers and then the rows to `sink`. Only the current rows are handed over,
+    /// `next` is ignored.
+    pub(crate) fn into_sink(self, sink: &mut dyn RowSink) -> Result<()> {
+        sink.headers(&self.headers)?;
+        for row in self.rows {
+            sink.row(row)?;
+        }
+        Ok(())
+    }
     /// Make named rows from JSON
     pub fn from_json(value: &JsonValue) -> Result<Self> {
         let headers = value
@@ -224,6 +383,139 @@ impl NamedRows {
     }
 }
 
+/// Receives the result of a script one row at a time, see [Db::run_script_with_sink].
+pub trait RowSink {
+    /// Called once with the headers of the result, before any row.
+    fn headers(&mut self, headers: &[String]) -> Result<()>;
+    /// Called with each row of the result, in order. Returning an error aborts the script.
+    fn row(&mut self, row: Tuple) -> Result<()>;
+}
+
+#[derive(Debug, Error, Diagnostic)]
+#[error("Header {0:?} occurs more than once")]
+#[diagnostic(code(eval::duplicate_header))]
+#[diagnostic(help("Rows can only be deserialized into structs if their headers are distinct"))]
+struct DuplicateHeader(String);
+
+#[derive(Debug, Error, Diagnostic)]
+#[error("Cannot write nulls as {0:?} in CSV")]
+#[diagnostic(code(eval::bad_null_repr))]
+#[diagnostic(help("It must not contain the delimiter, a quote or a line break"))]
+struct BadNullRepr(String);
+
+/// Write to a new temporary file next to `out_file`, which replaces `out_file` only once
+/// `write` succeeds. If it fails, the temporary file is removed.
+fn write_file_replacing(out_file: &Path, write: impl FnOnce(fs::File) -> Result<()>) -> Result<()> {
+    static TEMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let file_name = out_file
+        .file_name()
+        .ok_or_else(|| miette!("not a file name"))?
+        .to_string_lossy();
+    let temp_file = out_file.with_file_name(format!(
+        ".{file_name}.{}.{}.tmp",
+        std::process::id(),
+        TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    let file = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&temp_file)
+        .into_diagnostic()?;
+    let res = write(file).and_then(|_| fs::rename(&temp_file, out_file).into_diagnostic());
+    if res.is_err() {
+        let _ = fs::remove_file(&temp_file);
+    }
+    res
+}
+
+/// Options for writing CSV, see [NamedRows::write_csv].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsvOptions {
+    /// The byte separating the fields, `,` by default
+    pub delimiter: u8,
+    /// Whether to quote all fields except nulls, and not only those that need it.
+    /// Off by default
+    pub quote_all: bool,
+    /// How nulls are written, the empty string by default
+    pub null_repr: String,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        Self {
+            delimiter: b',',
+            quote_all: false,
+            null_repr: String::new(),
+        }
+    }
+}
+
+/// Writes rows as CSV as they are handed over, see [NamedRows::write_csv].
+pub(crate) struct CsvSink<W: io::Write> {
+    wtr: csv::Writer<W>,
+    delimiter: u8,
+    quote_all: bool,
+    null_repr: String,
+}
+
+impl<W: io::Write> CsvSink<W> {
+    pub(crate) fn new(writer: W, options: &CsvOptions) -> Result<Self> {
+        let ret = Self {
+            wtr: csv::WriterBuilder::new()
+                .delimiter(options.delimiter)
+                .quote_style(csv::QuoteStyle::Never)
+                .from_writer(writer),
+            delimiter: options.delimiter,
+            quote_all: options.quote_all,
+            null_repr: options.null_repr.clone(),
+        };
+        // nulls are never quoted, so their representation must not need quoting
+        ensure!(
+            !ret.needs_quoting(&ret.null_repr),
+            BadNullRepr(ret.null_repr.clone())
+        );
+        Ok(ret)
+    }
+    fn needs_quoting(&self, field: &str) -> bool {
+        field
+            .bytes()
+            .any(|b| b == self.delimiter || b == b'"' || b == b'\n' || b == b'\r')
+    }
+    // the quoting is done here, as it depends on the value and not only on the text
+    fn quote(&self, field: &str, force: bool) -> String {
+        if force || self.quote_all || self.needs_quoting(field) {
+            format!("\"{}\"", field.replace('"', "\"\""))
+        } else {
+            field.to_string()
+        }
+    }
+    fn write_row(&mut self, row: &[DataValue]) -> Result<()> {
+        let record = row
+            .iter()
+            .map(|val| match val {
+                DataValue::Null => self.null_repr.clone(),
+                DataValue::Str(s) => self.quote(s, *s == self.null_repr),
+                v => self.quote(&JsonValue::from(v.clone()).to_string(), false),
+            })
+            .collect_vec();
+        self.wtr.write_record(record).into_diagnostic()
+    }
+    pub(crate) fn flush(&mut self) -> Result<()> {
+        self.wtr.flush().into_diagnostic()
+    }
+}
+
+impl<W: io::Write> RowSink for CsvSink<W> {
+    fn headers(&mut self, headers: &[String]) -> Result<()> {
+        let record = headers.iter().map(|h| self.quote(h, false)).collect_vec();
+        self.wtr.write_record(record).into_diagnostic()
+    }
+    fn row(&mut self, row: Tuple) -> Result<()> {
+        self.write_row(&row)
+    }
+}
+
 const STATUS_STR: &str = "status";
 const OK_STR: &str = "OK";
 
@@ -249,8 +541,11 @@ impl<'s, S: Storage<'s>> Db<S> {
             relation_store_id: Default::default(),
             queries_count: Default::default(),
             running_queries: Default::default(),
+            metrics: Default::default(),
             fixed_rules: Arc::new(ShardedLock::new(DEFAULT_FIXED_RULES.clone())),
             tokenizers: Arc::new(Default::default()),
+            parallel_branches: Arc::new(AtomicBool::new(false)),
+            default_timeout: Default::default(),
             #[cfg(not(target_arch = "wasm32"))]
             callback_count: Default::default(),
             // callback_receiver: Arc::new(receiver),
@@ -262,6 +557,12 @@ impl<'s, S: Storage<'s>> Db<S> {
     }
 
     /// Must be called after creation of the database to initialize the runtime state.
+    ///
+    /// Names in scripts are put in Unicode normalization form C before they are looked up,
+    /// so that names typed with composed and with decomposed characters are the same.
+    /// Earlier versions stored names as they were typed: the relations, columns and indices
+    /// stored with names in another form are renamed here, in place. A relation whose
+    /// normalized name is already taken keeps its name, and a warning is logged.
     pub fn initialize(&'s self) -> Result<()> {
         self.load_last_ids()?;
         Ok(())
@@ -364,6 +665,7 @@ impl<'s, S: Storage<'s>> Db<S> {
                         ts,
                         &callback_targets,
                         &mut callback_collector,
+                        None,
                     );
                     if results.send(res).is_err() {
                         break;
@@ -386,8 +688,82 @@ impl<'s, S: Storage<'s>> Db<S> {
             &params,
             cur_vld,
             mutability == ScriptMutability::Immutable,
+            None,
         )
     }
+    /// Run the CozoScript passed in, handing the rows of the result to `sink` one at a time
+    /// instead of collecting them into [NamedRows].
+    ///
+    /// For a script made of a single query, the rows are read from the result of the evaluation
+    /// as `sink` takes them. The evaluation itself still keeps the rows of the entry rule,
+    /// so this saves the copy made for the returned rows, not the memory of the query.
+    /// Rows of queries with `:order` are all sorted first, and imperative scripts and
+    /// system ops run to completion before their rows are handed over.
+    ///
+    /// For a single query, the transaction stays open while `sink` takes the rows, and
+    /// an error from `sink` aborts it, so that its mutations are not committed.
+    /// Imperative scripts and system ops are already committed when `sink` is called.
+    pub fn run_script_with_sink(
+        &'s self,
+        payload: &str,
+        params: BTreeMap<String, DataValue>,
+        mutability: ScriptMutability,
+        sink: &mut dyn RowSink,
+    ) -> Result<()> {
+        let cur_vld = current_validity();
+        self.do_run_script(
+            payload,
+            &params,
+            cur_vld,
+            mutability == ScriptMutability::Immutable,
+            Some(sink),
+        )?;
+        Ok(())
+    }
+    /// Run the query passed in as a read-only script, writing the rows of its result to
+    /// `out_file` as CSV as they are read from the result, without collecting them first.
+    /// See [Self::run_script_with_sink] for what is kept in memory, and [NamedRows::write_csv]
+    /// for the format and the meaning of the options.
+    ///
+    /// This is only available through the API: scripts may come from remote clients,
+    /// e.g. through the HTTP server, so there is no CozoScript statement writing files.
+    /// The rows are written to a temporary file next to `out_file`, which replaces `out_file`
+    /// only once the export succeeds: if it fails, an existing `out_file` is left untouched.
+    pub fn export_csv(
+        &'s self,
+        payload: &str,
+        params: BTreeMap<String, DataValue>,
+        out_file: impl AsRef<Path>,
+        options: &CsvOptions,
+    ) -> Result<()> {
+        let out_file = out_file.as_ref();
+        write_file_replacing(out_file, |file| {
+            let mut sink = CsvSink::new(io::BufWriter::new(file), options)?;
+            self.run_script_with_sink(payload, params, ScriptMutability::Immutable, &mut sink)?;
+            sink.flush()
+        })
+        .wrap_err_with(|| format!("when exporting CSV to {}", out_file.display()))
+    }
+    /// Run the query passed in as a read-only script, writing the rows of its result to
+    /// `out_file` as Parquet, see [NamedRows::write_parquet].
+    ///
+    /// Unlike [Self::export_csv], the rows are collected first, as the type of each column
+    /// depends on all of its values. As with it, `out_file` is only replaced once the
+    /// export succeeds.
+    #[cfg(feature = "parquet")]
+    pub fn export_parquet(
+        &'s self,
+        payload: &str,
+        params: BTreeMap<String, DataValue>,
+        out_file: impl AsRef<Path>,
+    ) -> Result<()> {
+        let out_file = out_file.as_ref();
+        write_file_replacing(out_file, |file| {
+            self.run_script_read_only(payload, params)?
+                .write_parquet(file)
+        })
+        .wrap_err_with(|| format!("when exporting Parquet to {}", out_file.display()))
+    }
     /// Run the CozoScript passed in. The `params` argument is a map of parameters.
     pub fn run_script_read_only(
         &'s self,
@@ -395,12 +771,269 @@ impl<'s, S: Storage<'s>> Db<S> {
         params: BTreeMap<String, DataValue>,
     ) -> Result<NamedRows> {
         let cur_vld = current_validity();
-        self.do_run_script(payload, &params, cur_vld, true)
+        self.do_run_script(payload, &params, cur_vld, true, None)
+    }
+
+    /// Get a snapshot of the activity counters of the database.
+    ///
+    /// The counters start from zero when the database is opened and are not persisted.
+    pub fn metrics(&self) -> DbMetrics {
+        DbMetrics {
+            scripts: self.metrics.scripts.load(Ordering::Relaxed),
+            failed_scripts: self.metrics.failed_scripts.load(Ordering::Relaxed),
+            queries: self.queries_count.load(Ordering::Relaxed),
+            running_queries: self.running_queries.lock().unwrap().len() as u64,
+            rows_returned: self.metrics.rows_returned.load(Ordering::Relaxed),
+            rows_written: self.metrics.rows_written.load(Ordering::Relaxed),
+            rows_removed: self.metrics.rows_removed.load(Ordering::Relaxed),
+            rows_read: self.metrics.rows_read.load(Ordering::Relaxed),
+            failed_commits: self.metrics.failed_commits.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Set whether the branches of a rule, i.e. the rules sharing a head, may be evaluated
+    /// in parallel. This is off by default and takes effect for transactions started afterwards.
+    /// A query can override it with the `:parallel` option.
+    ///
+    /// Queries with a `:limit` always evaluate the branches of the entry rule one by one.
+    pub fn set_parallel_branches(&self, enabled: bool) {
+        self.parallel_branches.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Sets an execution option for the scripts run afterwards, as `::set <name> = <value>` does.
+    /// Queries can override them with the query option of the same name.
+    ///
+    /// * `parallel`: a boolean, see [`set_parallel_branches`](Self::set_parallel_branches).
+    /// * `timeout`: the timeout of queries in seconds, no timeout if null or zero.
+    fn set_option(&self, name: &Symbol, val: &DataValue) -> Result<()> {
+        #[derive(Debug, Error, Diagnostic)]
+        #[error("Invalid value {1} for option '{0}'")]
+        #[diagnostic(code(eval::invalid_option_value))]
+        struct InvalidOptionValue(String, DataValue, #[label] SourceSpan);
+
+        #[derive(Debug, Error, Diagnostic)]
+        #[error("Unknown option '{0}'")]
+        #[diagnostic(code(eval::unknown_option))]
+        #[diagnostic(help("The options are 'parallel' and 'timeout'"))]
+        struct UnknownOption(String, #[label] SourceSpan);
+
+        let invalid = || InvalidOptionValue(name.to_string(), val.clone(), name.span);
+        match &name.name as &str {
+            "parallel" => {
+                let parallel = val.get_bool().ok_or_else(invalid)?;
+                self.set_parallel_branches(parallel);
+            }
+            "timeout" => {
+                let timeout = match val {
+                    DataValue::Null => None,
+                    val => {
+                        let secs = val.get_float().filter(|secs| *secs >= 0.);
+                        Some(secs.ok_or_else(invalid)?).filter(|secs| *secs > 0.)
+                    }
+                };
+                *self.default_timeout.lock().unwrap() = timeout;
+            }
+            _ => bail!(UnknownOption(name.to_string(), name.span)),
+        }
+        Ok(())
+    }
+
+    /// Get summary information about the database.
+    ///
+    /// This only reads the catalog and the sizes of the files of the storage, and is cheap.
+    /// Use [`verify`](Self::verify) to check the data.
+    pub fn info(&'s self) -> Result<DbInfo> {
+        let tx = self.transact()?;
+        let mut relations = 0;
+        let mut indices = 0;
+        for handle in Self::catalog_entries(&tx) {
+            if handle?.name.contains(':') {
+                indices += 1;
+            } else {
+                relations += 1;
+            }
+        }
+        Ok(DbInfo {
+            storage_kind: self.db.storage_kind().to_string(),
+            storage_version: CURRENT_STORAGE_VERSION[0] as u64,
+            relations,
+            indices,
+            path: self
+                .db
+                .path()
+                .map(|path| path.to_string_lossy().to_string()),
+            size_on_disk: self.db.path().and_then(|path| disk_usage(path).ok()),
+        })
+    }
+
+    /// Check the integrity of the stored data.
+    ///
+    /// Every catalog entry and every row of every stored relation is read back.
+    /// Rows that cannot be decoded, or whose values do not match the declared schema,
+    /// are reported in the result instead of causing errors or panics later.
+    /// Every row of a relation must have its row in each of the relation's indices,
+    /// and every row of an index must be derived from a row of its relation.
+    /// Vector, full-text and LSH indices are not checked.
+    /// Errors reading from the storage are reported as problems too, and the check goes on.
+    /// This scans the whole database and can take a long time.
+    pub fn verify(&'s self) -> Result<DbVerification> {
+        let tx = self.transact()?;
+        let cur_vld = current_validity();
+        let mut ret = DbVerification::default();
+        let mut handles = vec![];
+        for handle in Self::catalog_entries(&tx) {
+            match handle {
+                Ok(h) => handles.push(h),
+                Err(err) => ret.report(format!("bad catalog entry: {err}")),
+            }
+        }
+        // for each index, its relation and the positions of the relation's keys in the index
+        let mut index_bases: BTreeMap<&str, (&RelationHandle, &[usize], Vec<usize>)> =
+            BTreeMap::new();
+        for handle in &handles {
+            for (idx_handle, mapper) in handle.indices.values() {
+                let key_positions = (0..handle.metadata.keys.len())
+                    .filter_map(|i| mapper.iter().position(|j| *j == i))
+                    .collect_vec();
+                index_bases.insert(&idx_handle.name, (handle, mapper, key_positions));
+            }
+        }
+        for handle in &handles {
+            ret.relations += 1;
+            let arity = handle.arity();
+            let is_index = handle.name.contains(':');
+            let columns = handle
+                .metadata
+                .keys
+                .iter()
+                .chain(handle.metadata.non_keys.iter())
+                .collect_vec();
+            let lower = Tuple::default().encode_as_key(handle.id);
+            let upper = Tuple::default().encode_as_key(handle.id.next());
+            for kv_res in tx.store_tx.range_scan(&lower, &upper) {
+                let (k, v) = match kv_res {
+                    Ok(kv) => kv,
+                    Err(err) => {
+                        ret.report(format!("cannot read relation {}: {err}", handle.name));
+                        break;
+                    }
+                };
+                ret.rows += 1;
+                ret.bytes += (k.len() + v.len()) as u64;
+                let tuple = match try_decode_tuple_from_kv(&k, &v, Some(arity)) {
+                    Some(t) => t,
+                    None => {
+                        ret.report(format!(
+                            "cannot decode row of relation {}: {:x?}",
+                            handle.name, k
+                        ));
+                        continue;
+                    }
+                };
+                if is_index {
+                    if let Some((base, mapper, key_positions)) =
+                        index_bases.get(handle.name.as_str())
+                    {
+                        if let Some(problem) = Self::verify_index_row(
+                            &tx,
+                            &handle.name,
+                            base,
+                            mapper,
+                            key_positions,
+                            &tuple,
+                        ) {
+                            ret.report(problem);
+                        }
+                    }
+                    // some kinds of indices do not store rows of their declared shape
+                    continue;
+                }
+                if tuple.len() != arity {
+                    ret.report(format!(
+                        "row of relation {} has {} columns, expected {}: {:?}",
+                        handle.name,
+                        tuple.len(),
+                        arity,
+                        tuple
+                    ));
+                    continue;
+                }
+                for (col, val) in columns.iter().zip(tuple.iter()) {
+                    if col.typing.coerce(val.clone(), cur_vld).is_err() {
+                        ret.report(format!(
+                            "value {:?} in column {} of relation {} does not have type {}",
+                            val, col.name, handle.name, col.typing
+                        ));
+                    }
+                }
+                for (idx_handle, mapper) in handle.indices.values() {
+                    let idx_tup = mapper.iter().map(|i| tuple[*i].clone()).collect_vec();
+                    let idx_key = idx_tup.encode_as_key(idx_handle.id);
+                    match tx.store_tx.exists(&idx_key, false) {
+                        Ok(true) => {}
+                        Ok(false) => ret.report(format!(
+                            "index {} has no row for {:?}",
+                            idx_handle.name, tuple
+                        )),
+                        Err(err) => ret.report(format!(
+                            "cannot read index {} for {:?}: {err}",
+                            idx_handle.name, tuple
+                        )),
+                    }
+                }
+            }
+        }
+        Ok(ret)
+    }
+
+    /// Check that a row of an index is derived from a row of its relation.
+    fn verify_index_row(
+        tx: &SessionTx<'_>,
+        index_name: &str,
+        base: &RelationHandle,
+        mapper: &[usize],
+        key_positions: &[usize],
+        row: &Tuple,
+    ) -> Option<String> {
+        if row.len() < mapper.len() {
+            return Some(format!("row of index {index_name} is too short: {row:?}"));
+        }
+        let base_key = key_positions
+            .iter()
+            .map(|i| row[*i].clone())
+            .collect_vec()
+            .encode_as_key(base.id);
+        let base_val = match tx.store_tx.get(&base_key, false) {
+            Ok(Some(v)) => v,
+            Ok(None) => {
+                return Some(format!(
+                    "index {index_name} has a row {row:?} not found in relation {}",
+                    base.name
+                ))
+            }
+            Err(err) => {
+                return Some(format!(
+                    "cannot read relation {} for row {row:?} of index {index_name}: {err}",
+                    base.name
+                ))
+            }
+        };
+        // undecodable rows of the relation are reported when the relation is checked
+        let base_row = try_decode_tuple_from_kv(&base_key, &base_val, Some(base.arity()))?;
+        let derived = mapper.iter().map(|i| base_row.get(*i));
+        if derived.ne(row.iter().take(mapper.len()).map(Some)) {
+            return Some(format!(
+                "index {index_name} has a stale row {row:?} for row {base_row:?} of relation {}",
+                base.name
+            ));
+        }
+        None
     }
 
     /// Export relations to JSON data.
     ///
-    /// `relations` contains names of the stored relations to export.
+    /// `relations` contains names of the stored relations to export. Like names in scripts,
+    /// they are put in Unicode normalization form C before they are looked up.
     pub fn export_relations<I, T>(&'s self, relations: I) -> Result<BTreeMap<String, NamedRows>>
     where
         T: AsRef<str>,
@@ -409,7 +1042,7 @@ impl<'s, S: Storage<'s>> Db<S> {
         let tx = self.transact()?;
         let mut ret: BTreeMap<String, NamedRows> = BTreeMap::new();
         for rel in relations {
-            let handle = tx.get_relation(rel.as_ref(), false)?;
+            let handle = tx.get_relation(&normalize_ident(rel.as_ref()), false)?;
             let size_hint = handle.metadata.keys.len() + handle.metadata.non_keys.len();
 
             if handle.access_level < AccessLevel::ReadOnly {
@@ -454,6 +1087,9 @@ impl<'s, S: Storage<'s>> Db<S> {
     /// The target stored relations must already exist in the database.
     /// Any associated indices will be updated.
     ///
+    /// Relation names and headers are put in Unicode normalization form C,
+    /// as names in scripts are.
+    ///
     /// Note that triggers and callbacks are _not_ run for the relations, if any exists.
     /// If you need to activate triggers or callbacks, use queries with parameters.
     pub fn import_relations(&'s self, data: BTreeMap<String, NamedRows>) -> Result<()> {
@@ -462,7 +1098,14 @@ impl<'s, S: Storage<'s>> Db<S> {
         #[diagnostic(code(import::bad_data))]
         struct BadDataForRelation(String, JsonValue);
 
-        let rel_names = data.keys().map(SmartString::from).collect_vec();
+        let data: BTreeMap<_, _> = data
+            .into_iter()
+            .map(|(name, rows)| (normalize_ident(&name), rows))
+            .collect();
+        let rel_names = data
+            .keys()
+            .map(|name| SmartString::from(name.strip_prefix('-').unwrap_or(name)))
+            .collect_vec();
         let locks = self.obtain_relation_locks(rel_names.iter());
         let _guards = locks.iter().map(|l| l.read().unwrap()).collect_vec();
 
@@ -500,15 +1143,15 @@ impl<'s, S: Storage<'s>> Db<S> {
                 .headers
                 .iter()
                 .enumerate()
-                .map(|(i, k)| -> Result<(&str, usize)> { Ok((k as &str, i)) })
-                .try_collect()?;
+                .map(|(i, k)| (normalize_ident(k), i))
+                .collect();
 
             let key_indices: Vec<_> = handle
                 .metadata
                 .keys
                 .iter()
                 .map(|col| -> Result<(usize, &ColumnDef)> {
-                    let idx = header2idx.get(&col.name as &str).ok_or_else(|| {
+                    let idx = header2idx.get(&col.name).ok_or_else(|| {
                         miette!(
                             "required header {} not found for relation {}",
                             col.name,
@@ -527,7 +1170,7 @@ impl<'s, S: Storage<'s>> Db<S> {
                     .non_keys
                     .iter()
                     .map(|col| -> Result<(usize, &ColumnDef)> {
-                        let idx = header2idx.get(&col.name as &str).ok_or_else(|| {
+                        let idx = header2idx.get(&col.name).ok_or_else(|| {
                             miette!(
                                 "required header {} not found for relation {}",
                                 col.name,
@@ -643,7 +1286,7 @@ impl<'s, S: Storage<'s>> Db<S> {
     /// Import data from relations in a backup file.
     /// The target stored relations must already exist in the database, and it must not
     /// have any associated indices. If you want to import into relations with indices,
-    /// use [Db::import_relations].
+    /// use [Db::import_relations]. Relation names are put in Unicode normalization form C.
     ///
     /// Note that triggers and callbacks are _not_ run for the relations, if any exists.
     /// If you need to activate triggers or callbacks, use queries with parameters.
@@ -658,15 +1301,18 @@ impl<'s, S: Storage<'s>> Db<S> {
 
         #[cfg(feature = "storage-sqlite")]
         {
-            let rel_names = relations.iter().map(SmartString::from).collect_vec();
-            let locks = self.obtain_relation_locks(rel_names.iter());
+            let relations = relations
+                .iter()
+                .map(|name| normalize_ident(name))
+                .collect_vec();
+            let locks = self.obtain_relation_locks(relations.iter());
             let _guards = locks.iter().map(|l| l.read().unwrap()).collect_vec();
 
             let source_db = crate::new_cozo_sqlite(in_file)?;
             let mut src_tx = source_db.transact()?;
             let mut dst_tx = self.transact_write()?;
 
-            for relation in relations {
+            for relation in &relations {
                 if relation.contains(':') {
                     bail!(ImportIntoIndex(relation.to_string()))
                 }
@@ -712,6 +1358,97 @@ impl<'s, S: Storage<'s>> Db<S> {
             dst_tx.commit_tx()
         }
     }
+    /// Import tables from an SQLite database file created by other programs.
+    ///
+    /// Each table becomes a new stored relation of the same name. Primary key columns
+    /// become the keys of the relation, and tables without a primary key get their
+    /// rowid as the key, named `rowid` (or `rowid_` or `oid` if the table has a column
+    /// named `rowid`). Column types are derived from the declared SQLite types.
+    /// `tables` lists the tables to import; if empty, all tables are imported.
+    ///
+    /// The whole import runs in a single write transaction: if any table fails to import,
+    /// the transaction is rolled back and the database is left as it was, including the
+    /// tables imported before the failing one. Rows are put in batches of 10000.
+    #[allow(unused_variables)]
+    pub fn import_from_sqlite(
+        &'s self,
+        in_file: impl AsRef<Path>,
+        tables: &[String],
+    ) -> Result<()> {
+        #[cfg(not(feature = "storage-sqlite"))]
+        bail!("importing from SQLite requires the 'storage-sqlite' feature to be enabled");
+
+        #[cfg(feature = "storage-sqlite")]
+        {
+            let conn = sqlite::Connection::open(in_file.as_ref()).into_diagnostic()?;
+            let tables = if tables.is_empty() {
+                let mut stmt = conn
+                    .prepare(
+                        "select name from sqlite_master \
+                         where type = 'table' and name not like 'sqlite_%' order by name",
+                    )
+                    .into_diagnostic()?;
+                let mut names = vec![];
+                while stmt.next().into_diagnostic()? == sqlite::State::Row {
+                    names.push(stmt.read::<String, _>(0).into_diagnostic()?);
+                }
+                names
+            } else {
+                tables.to_vec()
+            };
+            let imports = tables
+                .iter()
+                .map(|table| SqliteTableImport::new(&conn, table))
+                .collect::<Result<Vec<_>>>()?;
+
+            let lock_names = tables.iter().map(SmartString::from).collect_vec();
+            let locks = self.obtain_relation_locks(lock_names.iter());
+            let _guards = locks.iter().map(|l| l.read().unwrap()).collect_vec();
+            let cur_vld = current_validity();
+            let callback_targets = self.current_callback_targets();
+            let mut callback_collector = BTreeMap::new();
+            let mut cleanups = vec![];
+            let mut tx = self.transact_write()?;
+            let mut run = |tx: &mut SessionTx<'_>,
+                           script: &str,
+                           params: BTreeMap<String, DataValue>|
+             -> Result<()> {
+                let p = parse_script(script, &params, &self.fixed_rules.read().unwrap(), cur_vld)?
+                    .get_single_program()?;
+                self.execute_single_program(
+                    p,
+                    tx,
+                    &mut cleanups,
+                    cur_vld,
+                    &callback_targets,
+                    &mut callback_collector,
+                    None,
+                )?;
+                Ok(())
+            };
+            // on error, `tx` is dropped without being committed
+            for (table, import) in tables.iter().zip(&imports) {
+                run(&mut tx, &import.create, Default::default())
+                    .and_then(|_| {
+                        import.copy_rows(&conn, |rows| {
+                            let params =
+                                BTreeMap::from([("rows".to_string(), DataValue::List(rows))]);
+                            run(&mut tx, &import.put, params)
+                        })
+                    })
+                    .wrap_err_with(|| format!("importing SQLite table {table} failed"))?;
+            }
+            for (lower, upper) in cleanups {
+                tx.store_tx.del_range_from_persisted(&lower, &upper)?;
+            }
+            tx.commit_tx()?;
+            #[cfg(not(target_arch = "wasm32"))]
+            if !callback_collector.is_empty() {
+                self.send_callbacks(callback_collector)
+            }
+            Ok(())
+        }
+    }
     /// Register a custom fixed rule implementation.
     pub fn register_fixed_rule<R>(&self, name: String, rule_impl: R) -> Result<()>
     where
@@ -825,23 +1562,41 @@ impl<'s, S: Storage<'s>> Db<S> {
         tx.commit_tx()?;
         Ok(())
     }
+    fn store_tx(&'s self, write: bool) -> Result<Box<dyn StoreTx<'s> + 's>> {
+        let tx = self.db.transact(write)?;
+        #[cfg(feature = "tracing")]
+        let tx = TracedStoreTx(tx);
+        Ok(Box::new(tx))
+    }
     pub(crate) fn transact(&'s self) -> Result<SessionTx<'_>> {
         let ret = SessionTx {
-            store_tx: Box::new(self.db.transact(false)?),
+            store_tx: self.store_tx(false)?,
             temp_store_tx: self.temp_db.transact(true)?,
             relation_store_id: self.relation_store_id.clone(),
             temp_store_id: Default::default(),
             tokenizers: self.tokenizers.clone(),
+            parallel_branches: self.parallel_branches.load(Ordering::Relaxed),
+            profile: None,
+            metrics: self.metrics.clone(),
+            rows_written: 0,
+            rows_removed: 0,
+            rows_read: Default::default(),
         };
         Ok(ret)
     }
     pub(crate) fn transact_write(&'s self) -> Result<SessionTx<'_>> {
         let ret = SessionTx {
-            store_tx: Box::new(self.db.transact(true)?),
+            store_tx: self.store_tx(true)?,
             temp_store_tx: self.temp_db.transact(true)?,
             relation_store_id: self.relation_store_id.clone(),
             temp_store_id: Default::default(),
             tokenizers: self.tokenizers.clone(),
+            parallel_branches: self.parallel_branches.load(Ordering::Relaxed),
+            profile: None,
+            metrics: self.metrics.clone(),
+            rows_written: 0,
+            rows_removed: 0,
+            rows_read: Default::default(),
         };
         Ok(ret)
     }
@@ -854,11 +1609,19 @@ impl<'s, S: Storage<'s>> Db<S> {
         cur_vld: ValidityTs,
         callback_targets: &BTreeSet<SmartString<LazyCompact>>,
         callback_collector: &mut CallbackCollector,
+        sink: Option<&mut dyn RowSink>,
     ) -> Result<NamedRows> {
         #[allow(unused_variables)]
         let sleep_opt = p.out_opts.sleep;
-        let (q_res, q_cleanups) =
-            self.run_query(tx, p, cur_vld, callback_targets, callback_collector, true)?;
+        let (q_res, q_cleanups) = self.run_query(
+            tx,
+            p,
+            cur_vld,
+            callback_targets,
+            callback_collector,
+            true,
+            sink,
+        )?;
         cleanups.extend(q_cleanups);
         #[cfg(not(target_arch = "wasm32"))]
         if let Some(secs) = sleep_opt {
@@ -867,22 +1630,36 @@ impl<'s, S: Storage<'s>> Db<S> {
         Ok(q_res)
     }
 
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
     fn do_run_script(
         &'s self,
         payload: &str,
         param_pool: &BTreeMap<String, DataValue>,
         cur_vld: ValidityTs,
         read_only: bool,
+        mut sink: Option<&mut dyn RowSink>,
     ) -> Result<NamedRows> {
-        match parse_script(
+        let res = parse_script(
             payload,
             param_pool,
             &self.fixed_rules.read().unwrap(),
             cur_vld,
-        )? {
-            CozoScript::Single(p) => self.execute_single(cur_vld, p, read_only),
+        )
+        .and_then(|script| match script {
+            CozoScript::Single(p) => self.execute_single(cur_vld, p, read_only, sink.take()),
             CozoScript::Imperative(ps) => self.execute_imperative(cur_vld, &ps, read_only),
-            CozoScript::Sys(op) => self.run_sys_op(op, read_only),
+            CozoScript::Sys(op) => self.run_sys_op(op, cur_vld, read_only),
+        });
+        self.metrics.record_script(&res);
+        match sink {
+            // the script is done, the rows are handed over only now
+            Some(sink) => {
+                let rows = res?;
+                let headers = rows.headers.clone();
+                rows.into_sink(sink)?;
+                Ok(NamedRows::new(headers, vec![]))
+            }
+            None => res,
         }
     }
 
@@ -891,6 +1668,7 @@ impl<'s, S: Storage<'s>> Db<S> {
         cur_vld: ValidityTs,
         p: InputProgram,
         read_only: bool,
+        sink: Option<&mut dyn RowSink>,
     ) -> Result<NamedRows, Report> {
         let mut callback_collector = BTreeMap::new();
         let write_lock_names = p.needs_write_lock();
@@ -925,6 +1703,7 @@ impl<'s, S: Storage<'s>> Db<S> {
                 cur_vld,
                 &callback_targets,
                 &mut callback_collector,
+                sink,
             )?;
 
             for (lower, upper) in cleanups {
@@ -940,7 +1719,13 @@ impl<'s, S: Storage<'s>> Db<S> {
 
         Ok(res)
     }
-    fn explain_compiled(&self, strata: &[CompiledProgram]) -> Result<NamedRows> {
+    /// Describes the operators of a compiled program. With a profile, the runtime
+    /// statistics collected during its evaluation are added as extra columns.
+    fn explain_compiled(
+        &self,
+        strata: &[CompiledProgram],
+        profile: Option<&EvalProfile>,
+    ) -> Result<NamedRows> {
         let mut ret: Vec<JsonValue> = vec![];
         const STRATUM: &str = "stratum";
         const ATOM_IDX: &str = "atom_idx";
@@ -951,8 +1736,15 @@ impl<'s, S: Storage<'s>> Db<S> {
         const OUT_BINDINGS: &str = "out_relation";
         const JOINS_ON: &str = "joins_on";
         const FILTERS: &str = "filters/expr";
-
-        let headers = vec![
+        const ACCESS_PATH: &str = "access_path";
+        const EPOCHS: &str = "epochs";
+        const ROWS: &str = "rows";
+        const TIME: &str = "time";
+        const GETS: &str = "gets";
+        const PUTS: &str = "puts";
+        const BYTES_READ: &str = "bytes_read";
+
+        let mut headers = vec![
             STRATUM.to_string(),
             RULE_IDX.to_string(),
             RULE_NAME.to_string(),
@@ -962,7 +1754,37 @@ impl<'s, S: Storage<'s>> Db<S> {
             JOINS_ON.to_string(),
             FILTERS.to_string(),
             OUT_BINDINGS.to_string(),
+            ACCESS_PATH.to_string(),
         ];
+        if profile.is_some() {
+            headers.extend(
+                [EPOCHS, ROWS, TIME, GETS, PUTS, BYTES_READ]
+                    .into_iter()
+                    .map(|h| h.to_string()),
+            );
+        }
+        let add_rule_stats = |row: &mut JsonValue, stratum: usize, rule: &MagicSymbol| {
+            if let Some(profile) = profile {
+                let rules = profile.rules.lock().unwrap();
+                if let Some(stats) = rules.get(&(stratum, rule.clone())) {
+                    let row = row.as_object_mut().unwrap();
+                    row.insert(EPOCHS.to_string(), json!(stats.epochs));
+                    row.insert(ROWS.to_string(), json!(stats.rows));
+                    row.insert(TIME.to_string(), json!(stats.elapsed));
+                }
+            }
+        };
+        let add_operator_stats = |row: &mut JsonValue, rel: &RelAlgebra| {
+            if let Some(stats) = profile.and_then(|profile| profile.operator(rel)) {
+                let row = row.as_object_mut().unwrap();
+                let load = |counter: &AtomicU64| json!(counter.load(Ordering::Relaxed));
+                row.insert(ROWS.to_string(), load(&stats.rows));
+                row.insert(TIME.to_string(), json!(stats.elapsed().as_secs_f64()));
+                row.insert(GETS.to_string(), load(&stats.storage.gets));
+                row.insert(PUTS.to_string(), load(&stats.storage.puts));
+                row.insert(BYTES_READ.to_string(), load(&stats.storage.bytes_read));
+            }
+        };
 
         for (stratum, p) in strata.iter().enumerate() {
             let mut clause_idx = -1;
@@ -972,7 +1794,9 @@ impl<'s, S: Storage<'s>> Db<S> {
                         for CompiledRule { aggr, relation, .. } in rules.iter() {
                             clause_idx += 1;
                             let mut ret_for_relation = vec![];
-                            let mut rel_stack = vec![relation];
+                            // each relation comes with the bindings its parent joins it on
+                            let mut rel_stack: Vec<(&RelAlgebra, &[Symbol])> =
+                                vec![(relation, &[])];
                             let mut idx = 0;
                             let mut atom_type = "out";
                             for (a, _) in aggr.iter().flatten() {
@@ -985,17 +1809,23 @@ impl<'s, S: Storage<'s>> Db<S> {
                                 }
                             }
 
-                            ret_for_relation.push(json!({
+                            let mut out_row = json!({
                                 STRATUM: stratum,
                                 ATOM_IDX: idx,
                                 OP: atom_type,
                                 RULE_IDX: clause_idx,
                                 RULE_NAME: rule_name.to_string(),
                                 OUT_BINDINGS: relation.bindings_after_eliminate().into_iter().map(|v| v.to_string()).collect_vec()
-                            }));
+                            });
+                            add_rule_stats(&mut out_row, stratum, rule_name);
+                            ret_for_relation.push(out_row);
                             idx += 1;
 
-                            while let Some(rel) = rel_stack.pop() {
+                            // joins with a unit on the left are not shown, their statistics
+                            // are reported on their right side instead
+                            let mut stats_source = None;
+                            while let Some((rel, bound)) = rel_stack.pop() {
+                                let mut access_path = json!(null);
                                 let (atom_type, ref_name, joins_on, filters) = match rel {
                                     r @ RelAlgebra::Fixed(..) => {
                                         if r.is_unit() {
@@ -1014,26 +1844,45 @@ impl<'s, S: Storage<'s>> Db<S> {
                                         json!(filters.iter().map(|f| f.to_string()).collect_vec()),
                                     ),
                                     RelAlgebra::Stored(StoredRA {
-                                        storage, filters, ..
-                                    }) => (
-                                        "load_stored",
-                                        json!(format!(":{}", storage.name)),
-                                        json!(null),
-                                        json!(filters.iter().map(|f| f.to_string()).collect_vec()),
-                                    ),
+                                        bindings,
+                                        storage,
+                                        filters,
+                                        ..
+                                    }) => {
+                                        access_path =
+                                            json!(describe_access_path(storage, bindings, bound));
+                                        (
+                                            "load_stored",
+                                            json!(format!(":{}", storage.name)),
+                                            json!(null),
+                                            json!(filters
+                                                .iter()
+                                                .map(|f| f.to_string())
+                                                .collect_vec()),
+                                        )
+                                    }
                                     RelAlgebra::StoredWithValidity(StoredWithValidityRA {
+                                        bindings,
                                         storage,
                                         filters,
                                         ..
-                                    }) => (
-                                        "load_stored_with_validity",
-                                        json!(format!(":{}", storage.name)),
-                                        json!(null),
-                                        json!(filters.iter().map(|f| f.to_string()).collect_vec()),
-                                    ),
+                                    }) => {
+                                        access_path =
+                                            json!(describe_access_path(storage, bindings, bound));
+                                        (
+                                            "load_stored_with_validity",
+                                            json!(format!(":{}", storage.name)),
+                                            json!(null),
+                                            json!(filters
+                                                .iter()
+                                                .map(|f| f.to_string())
+                                                .collect_vec()),
+                                        )
+                                    }
                                     RelAlgebra::Join(inner) => {
                                         if inner.left.is_unit() {
-                                            rel_stack.push(&inner.right);
+                                            rel_stack.push((&inner.right, &[]));
+                                            stats_source = Some(rel);
                                             continue;
                                         }
                                         let t = inner.join_type();
@@ -1043,8 +1892,8 @@ impl<'s, S: Storage<'s>> Db<S> {
                                             joiner,
                                             ..
                                         } = inner.as_ref();
-                                        rel_stack.push(left);
-                                        rel_stack.push(right);
+                                        rel_stack.push((left, &[]));
+                                        rel_stack.push((right, &joiner.right_keys));
                                         (t, json!(null), json!(joiner.as_map()), json!(null))
                                     }
                                     RelAlgebra::NegJoin(inner) => {
@@ -1055,12 +1904,12 @@ impl<'s, S: Storage<'s>> Db<S> {
                                             joiner,
                                             ..
                                         } = inner.as_ref();
-                                        rel_stack.push(left);
-                                        rel_stack.push(right);
+                                        rel_stack.push((left, &[]));
+                                        rel_stack.push((right, &joiner.right_keys));
                                         (t, json!(null), json!(joiner.as_map()), json!(null))
                                     }
                                     RelAlgebra::Reorder(ReorderRA { relation, .. }) => {
-                                        rel_stack.push(relation);
+                                        rel_stack.push((relation, &[]));
                                         ("reorder", json!(null), json!(null), json!(null))
                                     }
                                     RelAlgebra::Filter(FilteredRA {
@@ -1068,7 +1917,7 @@ impl<'s, S: Storage<'s>> Db<S> {
                                         filters: pred,
                                         ..
                                     }) => {
-                                        rel_stack.push(parent);
+                                        rel_stack.push((parent, &[]));
                                         (
                                             "filter",
                                             json!(null),
@@ -1083,7 +1932,7 @@ impl<'s, S: Storage<'s>> Db<S> {
                                         is_multi,
                                         ..
                                     }) => {
-                                        rel_stack.push(parent);
+                                        rel_stack.push((parent, &[]));
                                         (
                                             if *is_multi { "multi-unify" } else { "unify" },
                                             json!(binding.name),
@@ -1124,7 +1973,7 @@ impl<'s, S: Storage<'s>> Db<S> {
                                             .collect_vec()),
                                     ),
                                 };
-                                ret_for_relation.push(json!({
+                                let mut row = json!({
                                     STRATUM: stratum,
                                     ATOM_IDX: idx,
                                     OP: atom_type,
@@ -1134,20 +1983,27 @@ impl<'s, S: Storage<'s>> Db<S> {
                                     OUT_BINDINGS: rel.bindings_after_eliminate().into_iter().map(|v| v.to_string()).collect_vec(),
                                     JOINS_ON: joins_on,
                                     FILTERS: filters,
-                                }));
+                                    ACCESS_PATH: access_path,
+                                });
+                                add_operator_stats(&mut row, stats_source.take().unwrap_or(rel));
+                                ret_for_relation.push(row);
                                 idx += 1;
                             }
                             ret_for_relation.reverse();
                             ret.extend(ret_for_relation)
                         }
                     }
-                    CompiledRuleSet::Fixed(_) => ret.push(json!({
-                        STRATUM: stratum,
-                        ATOM_IDX: 0,
-                        OP: "algo",
-                        RULE_IDX: 0,
-                        RULE_NAME: rule_name.to_string(),
-                    })),
+                    CompiledRuleSet::Fixed(_) => {
+                        let mut row = json!({
+                            STRATUM: stratum,
+                            ATOM_IDX: 0,
+                            OP: "algo",
+                            RULE_IDX: 0,
+                            RULE_NAME: rule_name.to_string(),
+                        });
+                        add_rule_stats(&mut row, stratum, rule_name);
+                        ret.push(row)
+                    }
                 }
             }
         }
@@ -1164,10 +2020,12 @@ impl<'s, S: Storage<'s>> Db<S> {
 
         Ok(NamedRows::new(headers, rows))
     }
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
     pub(crate) fn run_sys_op_with_tx(
         &'s self,
         tx: &mut SessionTx<'_>,
         op: &SysOp,
+        cur_vld: ValidityTs,
         read_only: bool,
         skip_locking: bool,
     ) -> Result<NamedRows> {
@@ -1177,7 +2035,55 @@ impl<'s, S: Storage<'s>> Db<S> {
                 let (stratified_program, _) = normalized_program.into_stratified_program()?;
                 let program = stratified_program.magic_sets_rewrite(tx)?;
                 let compiled = tx.stratified_magic_compile(program)?;
-                self.explain_compiled(&compiled)
+                self.explain_compiled(&compiled, None)
+            }
+            SysOp::ExplainAnalyze(prog) => {
+                // the program is run for real, and its writes would bypass callbacks
+                #[derive(Debug, Error, Diagnostic)]
+                #[error("Cannot analyze a query that writes to stored relation {0}")]
+                #[diagnostic(code(eval::explain_analyze_write))]
+                #[diagnostic(help("Use ::explain to see the plan of a mutating query"))]
+                struct ExplainAnalyzeWrite(String);
+
+                if let Some(name) = prog.needs_write_lock() {
+                    bail!(ExplainAnalyzeWrite(name.to_string()))
+                }
+
+                let profile = Arc::new(EvalProfile::default());
+                tx.with_profile(profile.clone(), |tx| {
+                    let (_, cleanups) = self.run_query(
+                        tx,
+                        (**prog).clone(),
+                        cur_vld,
+                        &Default::default(),
+                        &mut Default::default(),
+                        true,
+                        None,
+                    )?;
+                    for (lower, upper) in cleanups {
+                        tx.store_tx.del_range_from_persisted(&lower, &upper)?;
+                    }
+                    Ok(())
+                })?;
+
+                // the plan is recorded by `run_query`
+                let mut plan = profile.plan.lock().unwrap().take().unwrap_or_default();
+                let total = plan
+                    .headers
+                    .iter()
+                    .map(|h| {
+                        let counter = match h.as_str() {
+                            "op" => return DataValue::from("total"),
+                            "gets" => &profile.storage.gets,
+                            "puts" => &profile.storage.puts,
+                            "bytes_read" => &profile.storage.bytes_read,
+                            _ => return DataValue::Null,
+                        };
+                        DataValue::from(counter.load(Ordering::Relaxed) as i64)
+                    })
+                    .collect_vec();
+                plan.rows.push(total);
+                Ok(plan)
             }
             SysOp::Compact => {
                 if read_only {
@@ -1335,6 +2241,26 @@ impl<'s, S: Storage<'s>> Db<S> {
             }
             SysOp::ListColumns(rs) => self.list_columns(tx, rs),
             SysOp::ListIndices(rs) => self.list_indices(tx, rs),
+            SysOp::ShowStats(rs) => self.show_relation_stats(tx, rs),
+            SysOp::SetOption(name, val) => {
+                self.set_option(name, val)?;
+                Ok(NamedRows::new(
+                    vec![STATUS_STR.to_string()],
+                    vec![vec![DataValue::from(OK_STR)]],
+                ))
+            }
+            SysOp::Analyze(rs) => {
+                if read_only {
+                    bail!("Cannot store statistics in read-only mode");
+                }
+                let locks = if skip_locking {
+                    vec![]
+                } else {
+                    self.obtain_relation_locks(iter::once(&rs.name))
+                };
+                let _guards = locks.iter().map(|l| l.read().unwrap()).collect_vec();
+                self.analyze_relation(tx, rs)
+            }
             SysOp::RenameRelation(rename_pairs) => {
                 if read_only {
                     bail!("Cannot rename relations in read-only mode");
@@ -1363,7 +2289,7 @@ impl<'s, S: Storage<'s>> Db<S> {
                         vec![vec![DataValue::from("NOT_FOUND")]],
                     ),
                     Some(handle) => {
-                        handle.poison.0.store(true, Ordering::Relaxed);
+                        handle.poison.kill();
                         NamedRows::new(
                             vec![STATUS_STR.to_string()],
                             vec![vec![DataValue::from("KILLING")]],
@@ -1416,17 +2342,18 @@ impl<'s, S: Storage<'s>> Db<S> {
             }
         }
     }
-    fn run_sys_op(&'s self, op: SysOp, read_only: bool) -> Result<NamedRows> {
+    fn run_sys_op(&'s self, op: SysOp, cur_vld: ValidityTs, read_only: bool) -> Result<NamedRows> {
         let mut tx = if read_only {
             self.transact()?
         } else {
             self.transact_write()?
         };
-        let res = self.run_sys_op_with_tx(&mut tx, &op, read_only, false)?;
+        let res = self.run_sys_op_with_tx(&mut tx, &op, cur_vld, read_only, false)?;
         tx.commit_tx()?;
         Ok(res)
     }
     /// This is the entry to query evaluation
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
     pub(crate) fn run_query(
         &self,
         tx: &mut SessionTx<'_>,
@@ -1435,6 +2362,7 @@ impl<'s, S: Storage<'s>> Db<S> {
         callback_targets: &BTreeSet<SmartString<LazyCompact>>,
         callback_collector: &mut CallbackCollector,
         top_level: bool,
+        sink: Option<&mut dyn RowSink>,
     ) -> Result<(NamedRows, Vec<(Vec<u8>, Vec<u8>)>)> {
         // cleanups contain stored relations that should be deleted at the end of query
         let mut clean_ups = vec![];
@@ -1467,6 +2395,7 @@ impl<'s, S: Storage<'s>> Db<S> {
                 existing.ensure_compatible(
                     meta,
                     *op == RelationOp::Rm || *op == RelationOp::Delete || *op == RelationOp::Update,
+                    input_program.out_opts.lenient,
                 )?;
             }
         };
@@ -1480,7 +2409,11 @@ impl<'s, S: Storage<'s>> Db<S> {
 
         // poison is used to terminate queries early
         let poison = Poison::default();
-        if let Some(secs) = out_opts.timeout {
+        let timeout = match out_opts.timeout {
+            Some(secs) => Some(secs).filter(|secs| *secs > 0.),
+            None => *self.default_timeout.lock().unwrap(),
+        };
+        if let Some(secs) = timeout {
             poison.set_timeout(secs)?;
         }
         // give the query an ID and store it so that it can be queried and cancelled
@@ -1513,14 +2446,23 @@ impl<'s, S: Storage<'s>> Db<S> {
             None
         };
 
-        // the real evaluation
-        let (result_store, early_return) = tx.stratified_magic_evaluate(
+        // the real evaluation, with the parallelism asked for by the query if any
+        let db_parallel_branches = tx.parallel_branches;
+        if let Some(parallel) = out_opts.parallel {
+            tx.parallel_branches = parallel;
+        }
+        let evaluated = tx.stratified_magic_evaluate(
             &compiled,
             store_lifetimes,
             total_num_to_take,
             num_to_skip,
-            poison,
-        )?;
+            poison.clone(),
+        );
+        tx.parallel_branches = db_parallel_branches;
+        let (result_store, early_return) = evaluated?;
+        if let Some(profile) = &tx.profile {
+            *profile.plan.lock().unwrap() = Some(self.explain_compiled(&compiled, Some(profile))?);
+        }
 
         // deal with assertions
         if let Some(assertion) = &out_opts.assertion {
@@ -1579,23 +2521,25 @@ impl<'s, S: Storage<'s>> Db<S> {
                         } else {
                             ""
                         },
+                        out_opts.lenient,
                     )
                     .wrap_err_with(|| format!("when executing against relation '{}'", meta.name))?;
                 clean_ups.extend(to_clear);
                 let returned_rows =
                     tx.get_returning_rows(callback_collector, &meta.name, returning)?;
-                Ok((returned_rows, clean_ups))
+                let NamedRows { headers, rows, .. } = returned_rows;
+                Ok((
+                    self.collect_rows(headers, rows.into_iter(), sink, &poison)?,
+                    clean_ups,
+                ))
             } else {
                 // not sorting outputs
-                let rows: Vec<Tuple> = sorted_iter.collect_vec();
+                let headers = entry_head_or_default
+                    .iter()
+                    .map(|s| s.to_string())
+                    .collect_vec();
                 Ok((
-                    NamedRows::new(
-                        entry_head_or_default
-                            .iter()
-                            .map(|s| s.to_string())
-                            .collect_vec(),
-                        rows,
-                    ),
+                    self.collect_rows(headers, sorted_iter, sink, &poison)?,
                     clean_ups,
                 ))
             }
@@ -1635,26 +2579,46 @@ impl<'s, S: Storage<'s>> Db<S> {
                         } else {
                             ""
                         },
+                        out_opts.lenient,
                     )
                     .wrap_err_with(|| format!("when executing against relation '{}'", meta.name))?;
                 clean_ups.extend(to_clear);
                 let returned_rows =
                     tx.get_returning_rows(callback_collector, &meta.name, returning)?;
 
-                Ok((returned_rows, clean_ups))
-            } else {
-                let rows: Vec<Tuple> = scan.collect_vec();
-
+                let NamedRows { headers, rows, .. } = returned_rows;
                 Ok((
-                    NamedRows::new(
-                        entry_head_or_default
-                            .iter()
-                            .map(|s| s.to_string())
-                            .collect_vec(),
-                        rows,
-                    ),
+                    self.collect_rows(headers, rows.into_iter(), sink, &poison)?,
                     clean_ups,
                 ))
+            } else {
+                let headers = entry_head_or_default
+                    .iter()
+                    .map(|s| s.to_string())
+                    .collect_vec();
+                Ok((self.collect_rows(headers, scan, sink, &poison)?, clean_ups))
+            }
+        }
+    }
+    /// Collects the rows of the result of a query, or hands them to `sink` as they are read.
+    fn collect_rows(
+        &self,
+        headers: Vec<String>,
+        rows: impl Iterator<Item = Tuple>,
+        sink: Option<&mut dyn RowSink>,
+        poison: &Poison,
+    ) -> Result<NamedRows> {
+        match sink {
+            None => Ok(NamedRows::new(headers, rows.collect_vec())),
+            Some(sink) => {
+                sink.headers(&headers)?;
+                for row in rows {
+                    // the sink may be slow, the query can still be killed meanwhile
+                    poison.check()?;
+                    sink.row(row)?;
+                    self.metrics.rows_returned.fetch_add(1, Ordering::Relaxed);
+                }
+                Ok(NamedRows::new(headers, vec![]))
             }
         }
     }
@@ -1789,17 +2753,104 @@ impl<'s, S: Storage<'s>> Db<S> {
             rows,
         ))
     }
-    fn list_relations(&'s self, tx: &SessionTx<'_>) -> Result<NamedRows> {
+    /// Collects the statistics of a relation, stores them for the planner, and returns them.
+    fn analyze_relation(&'s self, tx: &mut SessionTx<'_>, name: &str) -> Result<NamedRows> {
+        let handle = tx.get_relation(name, false)?;
+        if handle.access_level < AccessLevel::ReadOnly {
+            bail!(InsufficientAccessLevel(
+                handle.name.to_string(),
+                "analyzing".to_string(),
+                handle.access_level
+            ));
+        }
+        let arity = handle.arity();
+        let mut n_rows = 0usize;
+        let mut n_nulls = vec![0usize; arity];
+        let mut distinct: Vec<DistinctCounter> = (0..arity).map(|_| Default::default()).collect();
+        let mut samples: Vec<ColumnSample> = (0..arity).map(|_| Default::default()).collect();
+        let mut bounds: Vec<Option<(DataValue, DataValue)>> = vec![None; arity];
+        for tuple in handle.scan_all(tx) {
+            let tuple = tuple?;
+            n_rows += 1;
+            for (i, val) in tuple.into_iter().enumerate().take(arity) {
+                if val == DataValue::Null {
+                    n_nulls[i] += 1;
+                    continue;
+                }
+                match &mut bounds[i] {
+                    None => bounds[i] = Some((val.clone(), val.clone())),
+                    Some((min, max)) => {
+                        if val < *min {
+                            *min = val.clone();
+                        } else if val > *max {
+                            *max = val.clone();
+                        }
+                    }
+                }
+                samples[i].insert(&val);
+                distinct[i].insert(val);
+            }
+        }
+        let stats = RelationStats {
+            rows: n_rows as u64,
+            columns: distinct
+                .into_iter()
+                .zip(n_nulls)
+                .zip(bounds)
+                .zip(samples)
+                .map(|(((distinct, nulls), bounds), sample)| {
+                    let (min, max) = bounds.unwrap_or((DataValue::Null, DataValue::Null));
+                    ColumnStats {
+                        nulls: nulls as u64,
+                        distinct: distinct.count() as u64,
+                        distinct_exact: distinct.is_exact(),
+                        min: ColumnStats::bounded_value(min),
+                        max: ColumnStats::bounded_value(max),
+                        histogram: sample.into_histogram(),
+                    }
+                })
+                .collect_vec(),
+        };
+        tx.set_relation_stats(&handle, &stats)?;
+        Ok(stats_rows(&handle, &stats))
+    }
+    /// The statistics stored by the last `::analyze` of a relation, no rows if there are none.
+    fn show_relation_stats(&'s self, tx: &SessionTx<'_>, name: &str) -> Result<NamedRows> {
+        let handle = tx.get_relation(name, false)?;
+        if handle.access_level < AccessLevel::ReadOnly {
+            bail!(InsufficientAccessLevel(
+                handle.name.to_string(),
+                "reading statistics".to_string(),
+                handle.access_level
+            ));
+        }
+        Ok(match tx.get_relation_stats(&handle)? {
+            Some(stats) => stats_rows(&handle, &stats),
+            None => stats_rows(&handle, &RelationStats::default()),
+        })
+    }
+    pub(crate) fn catalog_entries(tx: &SessionTx<'_>) -> Vec<Result<RelationHandle>> {
         let lower = vec![DataValue::from("")].encode_as_key(RelationId::SYSTEM);
         let upper =
             vec![DataValue::from(String::from(LARGEST_UTF_CHAR))].encode_as_key(RelationId::SYSTEM);
-        let mut rows: Vec<Vec<JsonValue>> = vec![];
+        let mut ret = vec![];
         for kv_res in tx.store_tx.range_scan(&lower, &upper) {
-            let (k_slice, v_slice) = kv_res?;
-            if upper <= k_slice {
-                break;
+            match kv_res {
+                Ok((k_slice, v_slice)) => {
+                    if upper <= k_slice {
+                        break;
+                    }
+                    ret.push(RelationHandle::decode(&v_slice));
+                }
+                Err(err) => ret.push(Err(err)),
             }
-            let meta = RelationHandle::decode(&v_slice)?;
+        }
+        ret
+    }
+    fn list_relations(&'s self, tx: &SessionTx<'_>) -> Result<NamedRows> {
+        let mut rows: Vec<Vec<JsonValue>> = vec![];
+        for meta in Self::catalog_entries(tx) {
+            let meta = meta?;
             let n_keys = meta.metadata.keys.len();
             let n_dependents = meta.metadata.non_keys.len();
             let arity = n_keys + n_dependents;
@@ -1842,6 +2893,185 @@ impl<'s, S: Storage<'s>> Db<S> {
     }
 }
 
+/// Number of rows put in each transaction by [Db::import_from_sqlite].
+#[cfg(feature = "storage-sqlite")]
+const SQLITE_IMPORT_BATCH_SIZE: usize = 10000;
+
+/// How an SQLite table is copied into a stored relation.
+#[cfg(feature = "storage-sqlite")]
+struct SqliteTableImport {
+    /// script creating the relation
+    create: String,
+    /// script putting the rows passed as `$rows` into the relation
+    put: String,
+    /// SQLite query reading the rows, with the columns in the order of `put`
+    select: String,
+    n_cols: usize,
+}
+
+#[cfg(feature = "storage-sqlite")]
+impl SqliteTableImport {
+    fn new(conn: &sqlite::Connection, table: &str) -> Result<Self> {
+        fn is_ident(name: &str) -> bool {
+            let mut chars = name.chars();
+            matches!(chars.next(), Some(c) if c.is_alphabetic())
+                && chars.all(|c| c.is_alphanumeric() || c == '_')
+        }
+        fn col_type(declared: &str) -> &'static str {
+            let declared = declared.to_ascii_uppercase();
+            if declared.contains("INT") {
+                "Int"
+            } else if declared.contains("CHAR")
+                || declared.contains("CLOB")
+                || declared.contains("TEXT")
+            {
+                "String"
+            } else if declared.contains("BLOB") {
+                "Bytes"
+            } else if declared.contains("REAL")
+                || declared.contains("FLOA")
+                || declared.contains("DOUB")
+            {
+                "Float"
+            } else {
+                "Any"
+            }
+        }
+
+        ensure!(
+            is_ident(table),
+            "cannot import SQLite table '{}': the name is not a valid relation name",
+            table
+        );
+        // (pk position, name, declared type, not null); columns with pk position 0 are not
+        // part of the primary key
+        let mut cols: Vec<(i64, String, String, bool)> = vec![];
+        let mut stmt = conn
+            .prepare(format!("pragma table_info(\"{table}\")"))
+            .into_diagnostic()?;
+        while stmt.next().into_diagnostic()? == sqlite::State::Row {
+            let name = stmt.read::<String, _>("name").into_diagnostic()?;
+            ensure!(
+                is_ident(&name),
+                "cannot import SQLite table '{}': column name '{}' is not a valid column name",
+                table,
+                name
+            );
+            let declared = stmt.read::<String, _>("type").into_diagnostic()?;
+            let not_null = stmt.read::<i64, _>("notnull").into_diagnostic()? != 0;
+            let pk = stmt.read::<i64, _>("pk").into_diagnostic()?;
+            cols.push((pk, name, declared, not_null));
+        }
+        ensure!(!cols.is_empty(), "SQLite table '{}' not found", table);
+
+        let mut keys = cols.iter().filter(|(pk, ..)| *pk > 0).collect_vec();
+        keys.sort_by_key(|(pk, ..)| *pk);
+        // a single `INTEGER PRIMARY KEY` column is the rowid and cannot hold nulls, other
+        // primary key columns can unless declared `NOT NULL`
+        let is_rowid_alias = keys.len() == 1 && keys[0].2.eq_ignore_ascii_case("INTEGER");
+        let rowid_key;
+        let mut rowid_alias = None;
+        if keys.is_empty() {
+            // a real column shadows the name, but not all of the aliases of the rowid;
+            // column names cannot start with an underscore, so `_rowid_` becomes `rowid_`
+            let (alias, name) = [("rowid", "rowid"), ("_rowid_", "rowid_"), ("oid", "oid")]
+                .into_iter()
+                .find(|(alias, name)| {
+                    !cols.iter().any(|(_, col, ..)| {
+                        col.eq_ignore_ascii_case(alias) || col.eq_ignore_ascii_case(name)
+                    })
+                })
+                .ok_or_else(|| {
+                    miette!(
+                        "cannot import SQLite table '{}': it has no primary key, \
+                         and columns shadowing all names of its rowid",
+                        table
+                    )
+                })?;
+            // tables created `WITHOUT ROWID` always have a primary key, this catches views
+            // and virtual tables
+            conn.prepare(format!("select {alias} from \"{table}\" limit 0"))
+                .map_err(|_| {
+                    miette!(
+                        "cannot import SQLite table '{}': it has neither a primary key nor a rowid",
+                        table
+                    )
+                })?;
+            rowid_key = (1, name.to_string(), "INTEGER".to_string(), true);
+            keys.push(&rowid_key);
+            rowid_alias = Some(alias);
+        }
+        let non_keys = cols.iter().filter(|(pk, ..)| *pk == 0).collect_vec();
+
+        let col_def = |(pk, name, declared, not_null): &(i64, String, String, bool)| {
+            let typ = col_type(declared);
+            let nullable = !not_null && typ != "Any" && !(*pk > 0 && is_rowid_alias);
+            format!("{name}: {typ}{}", if nullable { "?" } else { "" })
+        };
+        let key_names = keys.iter().map(|(_, name, ..)| name.as_str()).join(", ");
+        let non_key_names = non_keys
+            .iter()
+            .map(|(_, name, ..)| name.as_str())
+            .join(", ");
+        let key_defs = keys.iter().copied().map(col_def).join(", ");
+        let non_key_defs = non_keys.iter().copied().map(col_def).join(", ");
+        let all_names = if non_keys.is_empty() {
+            key_names.clone()
+        } else {
+            format!("{key_names}, {non_key_names}")
+        };
+        let mut select_cols = keys
+            .iter()
+            .chain(non_keys.iter())
+            .map(|(_, name, ..)| format!("\"{name}\""))
+            .collect_vec();
+        if let Some(alias) = rowid_alias {
+            // the rowid is then the only key
+            select_cols[0] = alias.to_string();
+        }
+        let select_cols = select_cols.join(", ");
+        Ok(Self {
+            create: format!(":create {table} {{{key_defs} => {non_key_defs}}}"),
+            put: format!("?[{all_names}] <- $rows :put {table} {{{key_names} => {non_key_names}}}"),
+            select: format!("select {select_cols} from \"{table}\""),
+            n_cols: keys.len() + non_keys.len(),
+        })
+    }
+
+    /// Read the rows of the table, handing them to `put_rows` in batches.
+    fn copy_rows(
+        &self,
+        conn: &sqlite::Connection,
+        mut put_rows: impl FnMut(Vec<DataValue>) -> Result<()>,
+    ) -> Result<()> {
+        let mut stmt = conn.prepare(&self.select).into_diagnostic()?;
+        let mut rows = Vec::with_capacity(SQLITE_IMPORT_BATCH_SIZE);
+        while stmt.next().into_diagnostic()? == sqlite::State::Row {
+            let mut row = Vec::with_capacity(self.n_cols);
+            for i in 0..self.n_cols {
+                row.push(match stmt.read::<sqlite::Value, _>(i).into_diagnostic()? {
+                    sqlite::Value::Binary(b) => DataValue::Bytes(b),
+                    sqlite::Value::Float(f) => DataValue::from(f),
+                    sqlite::Value::Integer(n) => DataValue::from(n),
+                    sqlite::Value::String(s) => DataValue::from(s),
+                    sqlite::Value::Null => DataValue::Null,
+                });
+            }
+            rows.push(DataValue::List(row));
+            if rows.len() == SQLITE_IMPORT_BATCH_SIZE {
+                put_rows(mem::replace(
+                    &mut rows,
+                    Vec::with_capacity(SQLITE_IMPORT_BATCH_SIZE),
+                ))?;
+            }
+        }
+        if !rows.is_empty() {
+            put_rows(rows)?;
+        }
+        Ok(())
+    }
+}
+
 /// Evaluate a string expression in the context of a set of parameters and variables
 pub fn evaluate_expressions(
     src: &str,
@@ -1868,6 +3098,169 @@ pub fn get_variables(src: &str, params: &BTreeMap<String, DataValue>) -> Result<
     })
 }
 
+/// One row per column of `handle`, as returned by `::analyze` and `::stats`.
+fn stats_rows(handle: &RelationHandle, stats: &RelationStats) -> NamedRows {
+    let rows = handle
+        .metadata
+        .keys
+        .iter()
+        .chain(handle.metadata.non_keys.iter())
+        .zip(stats.columns.iter())
+        .enumerate()
+        .map(|(idx, (col, col_stats))| {
+            vec![
+                DataValue::from(col.name.as_str()),
+                DataValue::from(idx < handle.metadata.keys.len()),
+                DataValue::from(stats.rows as i64),
+                DataValue::from(col_stats.nulls as i64),
+                DataValue::from(col_stats.distinct as i64),
+                DataValue::from(col_stats.distinct_exact),
+                col_stats.min.clone(),
+                col_stats.max.clone(),
+                DataValue::List(col_stats.histogram.clone()),
+            ]
+        })
+        .collect_vec();
+    NamedRows::new(
+        vec![
+            "column".to_string(),
+            "is_key".to_string(),
+            "rows".to_string(),
+            "nulls".to_string(),
+            "distinct".to_string(),
+            "distinct_exact".to_string(),
+            "min".to_string(),
+            "max".to_string(),
+            "histogram".to_string(),
+        ],
+        rows,
+    )
+}
+
+/// Keeps a uniform sample of the values of a column for `::analyze`, from which
+/// the histogram is computed.
+#[derive(Default)]
+struct ColumnSample {
+    seen: usize,
+    values: Vec<DataValue>,
+}
+
+impl ColumnSample {
+    const SIZE: usize = 1000;
+    const BUCKETS: usize = 10;
+
+    /// Reservoir sampling: the `n`th value replaces a random one with probability `SIZE / n`.
+    fn insert(&mut self, val: &DataValue) {
+        self.seen += 1;
+        if self.values.len() < Self::SIZE {
+            self.values.push(val.clone());
+        } else {
+            let idx = rand::thread_rng().gen_range(0..self.seen);
+            if idx < Self::SIZE {
+                self.values[idx] = val.clone();
+            }
+        }
+    }
+    fn into_histogram(mut self) -> Vec<DataValue> {
+        if self.values.is_empty() {
+            return vec![];
+        }
+        self.values.sort();
+        let n = self.values.len();
+        let buckets = Self::BUCKETS.min(n);
+        (1..=buckets)
+            .map(|i| ColumnStats::bounded_value(self.values[i * n / buckets - 1].clone()))
+            .collect()
+    }
+}
+
+/// Counts the distinct values of a column for `::analyze`.
+///
+/// Values are kept in a set until there are more than [`DistinctCounter::MAX_EXACT`]
+/// of them, after which the count is estimated with a HyperLogLog sketch so that
+/// memory use stays bounded however large the relation is.
+enum DistinctCounter {
+    Exact(BTreeSet<DataValue>),
+    Estimated(Box<[u8; DistinctCounter::REGISTERS]>),
+}
+
+impl Default for DistinctCounter {
+    fn default() -> Self {
+        Self::Exact(BTreeSet::new())
+    }
+}
+
+impl DistinctCounter {
+    const MAX_EXACT: usize = 10000;
+    const PRECISION: u32 = 12;
+    const REGISTERS: usize = 1 << Self::PRECISION;
+
+    fn insert(&mut self, val: DataValue) {
+        match self {
+            Self::Exact(set) => {
+                set.insert(val);
+                if set.len() > Self::MAX_EXACT {
+                    let mut registers = Box::new([0u8; Self::REGISTERS]);
+                    for val in mem::take(set) {
+                        Self::add_hashed(&mut registers, &val);
+                    }
+                    *self = Self::Estimated(registers);
+                }
+            }
+            Self::Estimated(registers) => Self::add_hashed(registers, &val),
+        }
+    }
+    fn add_hashed(registers: &mut [u8; Self::REGISTERS], val: &DataValue) {
+        let mut hasher = DefaultHasher::new();
+        val.hash(&mut hasher);
+        let hash = hasher.finish();
+        let idx = (hash >> (64 - Self::PRECISION)) as usize;
+        let rank = ((hash << Self::PRECISION) | (1 << (Self::PRECISION - 1))).leading_zeros() + 1;
+        registers[idx] = registers[idx].max(rank as u8);
+    }
+    fn is_exact(&self) -> bool {
+        matches!(self, Self::Exact(_))
+    }
+    fn count(&self) -> usize {
+        match self {
+            Self::Exact(set) => set.len(),
+            Self::Estimated(registers) => {
+                let m = Self::REGISTERS as f64;
+                let alpha = 0.7213 / (1. + 1.079 / m);
+                let sum: f64 = registers.iter().map(|r| 2f64.powi(-(*r as i32))).sum();
+                let estimate = alpha * m * m / sum;
+                let zeros = registers.iter().filter(|r| **r == 0).count();
+                if estimate <= 2.5 * m && zeros > 0 {
+                    (m * (m / zeros as f64).ln()).round() as usize
+                } else {
+                    estimate.round() as usize
+                }
+            }
+        }
+    }
+}
+
+/// Describes how a stored relation is read when the parent join binds `bound`:
+/// a prefix scan over the leading key columns that are bound, or a full scan.
+fn describe_access_path(storage: &RelationHandle, bindings: &[Symbol], bound: &[Symbol]) -> String {
+    let prefix = bindings
+        .iter()
+        .zip(storage.metadata.keys.iter())
+        .take_while(|(binding, _)| bound.contains(binding))
+        .map(|(_, col)| col.name.as_str())
+        .collect_vec();
+    let target = if storage.name.contains(':') {
+        format!("index :{}", storage.name)
+    } else {
+        "primary key".to_string()
+    };
+    if prefix.is_empty() {
+        format!("full scan on {target}")
+    } else {
+        format!("prefix scan on {target}, bound: {}", prefix.join(", "))
+    }
+}
+
 fn _evaluate_expressions(
     src: &str,
     params: &BTreeMap<String, DataValue>,
@@ -1890,8 +3283,15 @@ fn _get_variables(src: &str, params: &BTreeMap<String, DataValue>) -> Result<BTr
 }
 
 /// Used for user-initiated termination of running queries
+///
+/// Holds one of the `POISON_*` states. Only the first termination is recorded,
+/// so that a query is reported as killed for the reason that actually stopped it.
 #[derive(Clone, Default)]
-pub struct Poison(pub(crate) Arc<AtomicBool>);
+pub struct Poison(pub(crate) Arc<AtomicU8>);
+
+const POISON_RUNNING: u8 = 0;
+const POISON_KILLED: u8 = 1;
+const POISON_TIMED_OUT: u8 = 2;
 
 impl Poison {
     /// Will return `Err` if user has initiated termination.
@@ -1903,10 +3303,26 @@ impl Poison {
         #[diagnostic(help("A query may be killed by timeout, or explicit command"))]
         struct ProcessKilled;
 
-        if self.0.load(Ordering::Relaxed) {
-            bail!(ProcessKilled)
+        #[derive(Debug, Error, Diagnostic)]
+        #[error("Running query is killed as it exceeded its timeout")]
+        #[diagnostic(code(eval::timeout))]
+        #[diagnostic(help("Raise the ':timeout' option of the query, or make the query cheaper"))]
+        struct QueryTimeout;
+
+        match self.0.load(Ordering::Relaxed) {
+            POISON_RUNNING => Ok(()),
+            POISON_TIMED_OUT => bail!(QueryTimeout),
+            _ => bail!(ProcessKilled),
         }
-        Ok(())
+    }
+    pub(crate) fn kill(&self) {
+        self.terminate(POISON_KILLED);
+    }
+    fn terminate(&self, state: u8) {
+        // a query that is already terminated keeps its reason
+        let _ =
+            self.0
+                .compare_exchange(POISON_RUNNING, state, Ordering::Relaxed, Ordering::Relaxed);
     }
     #[cfg(target_arch = "wasm32")]
     pub(crate) fn set_timeout(&self, _secs: f64) -> Result<()> {
@@ -1917,12 +3333,25 @@ impl Poison {
         let pill = self.clone();
         thread::spawn(move || {
             thread::sleep(Duration::from_micros((secs * 1000000.) as u64));
-            pill.0.store(true, Ordering::Relaxed);
+            pill.terminate(POISON_TIMED_OUT);
         });
         Ok(())
     }
 }
 
+/// Total size of the file at `path`, or of all the files under it if it is a directory.
+fn disk_usage(path: &Path) -> io::Result<u64> {
+    let meta = fs::metadata(path)?;
+    if !meta.is_dir() {
+        return Ok(meta.len());
+    }
+    let mut total = 0;
+    for entry in fs::read_dir(path)? {
+        total += disk_usage(&entry?.path())?;
+    }
+    Ok(total)
+}
+
 pub(crate) fn seconds_since_the_epoch() -> Result<f64> {
     #[cfg(not(target_arch = "wasm32"))]
     let now = SystemTime::now();
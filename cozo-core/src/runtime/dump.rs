@@ -0,0 +1,414 @@
+/*
+ * Copyright 2022, The Cozo Project Authors.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public License, v. 2.0.
+ * If a copy of the MPL was not distributed with this file,
+ * You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use std::fmt::Write;
+use std::io;
+
+use itertools::Itertools;
+use miette::{bail, IntoDiagnostic, Result};
+use pest::Parser;
+
+use crate::data::expr::Expr;
+use crate::data::relation::{ColumnDef, VecElementType};
+use crate::data::tuple::Tuple;
+use crate::data::value::{DataValue, Num, Vector};
+use crate::fts::TokenizerConfig;
+use crate::parse::expr::build_expr;
+use crate::parse::sys::HnswDistance;
+use crate::parse::{CozoScriptParser, Rule};
+use crate::runtime::relation::{AccessLevel, RelationHandle};
+use crate::{Db, ScriptMutability, Storage};
+
+/// Number of rows put by each statement of a dump.
+const DUMP_BATCH_SIZE: usize = 1000;
+
+impl<'s, S: Storage<'s>> Db<S> {
+    /// Dump the whole database as a CozoScript script, written to `out`.
+    ///
+    /// The script creates all stored relations, puts their rows, creates the indices
+    /// and then restores triggers and access levels. It does not depend on the storage
+    /// engine or the storage version, so it can be used to move data between releases.
+    /// Each statement is on a line of its own, except for the trailing triggers, which
+    /// may span several lines. Strings are escaped so that the script is plain ASCII.
+    /// Replay it with [`restore_dump`](Self::restore_dump).
+    ///
+    /// LSH indices are recreated with the default false positive and false negative weights.
+    pub fn dump(&'s self, mut out: impl io::Write) -> Result<()> {
+        let tx = self.transact()?;
+        let handles: Vec<RelationHandle> = Self::catalog_entries(&tx)
+            .into_iter()
+            .filter_ok(|handle| !handle.name.contains(':'))
+            .try_collect()?;
+
+        for handle in &handles {
+            writeln!(out, "{{{}}}", relation_ddl(handle)).into_diagnostic()?;
+        }
+        for handle in &handles {
+            let columns = handle
+                .metadata
+                .keys
+                .iter()
+                .chain(handle.metadata.non_keys.iter())
+                .map(|col| col.name.as_str())
+                .join(", ");
+            let put = put_op(handle);
+            for chunk in &handle.scan_all(&tx).chunks(DUMP_BATCH_SIZE) {
+                let rows: Vec<Tuple> = chunk.try_collect()?;
+                let rows = rows
+                    .iter()
+                    .map(|row| format!("[{}]", row.iter().map(literal).join(", ")))
+                    .join(", ");
+                writeln!(out, "{{?[{columns}] <- [{rows}] {put}}}").into_diagnostic()?;
+            }
+        }
+        for stmt in handles.iter().flat_map(index_ddls) {
+            writeln!(out, "{{{stmt}}}").into_diagnostic()?;
+        }
+        for stmt in handles.iter().flat_map(settings_ddls) {
+            writeln!(out, "{{{stmt}}}").into_diagnostic()?;
+        }
+        out.flush().into_diagnostic()
+    }
+
+    /// The schema of the database as a CozoScript script, without any data.
+    ///
+    /// The script creates all stored relations and their indices, and then restores
+    /// triggers and access levels, in the same form as [`dump`](Self::dump).
+    /// Apply it to another database with [`apply_schema_script`](Self::apply_schema_script).
+    pub fn schema_script(&'s self) -> Result<String> {
+        let tx = self.transact()?;
+        let handles: Vec<RelationHandle> = Self::catalog_entries(&tx)
+            .into_iter()
+            .filter_ok(|handle| !handle.name.contains(':'))
+            .try_collect()?;
+
+        let mut ret = String::new();
+        for handle in &handles {
+            writeln!(ret, "{{{}}}", relation_ddl(handle)).unwrap();
+        }
+        for stmt in handles.iter().flat_map(index_ddls) {
+            writeln!(ret, "{{{stmt}}}").unwrap();
+        }
+        for stmt in handles.iter().flat_map(settings_ddls) {
+            writeln!(ret, "{{{stmt}}}").unwrap();
+        }
+        Ok(ret)
+    }
+
+    /// Apply a script produced by [`schema_script`](Self::schema_script).
+    ///
+    /// The whole script runs in a single transaction: if any relation already exists,
+    /// nothing is created.
+    pub fn apply_schema_script(&'s self, script: &str) -> Result<()> {
+        if !script.trim().is_empty() {
+            self.run_script(script, Default::default(), ScriptMutability::Mutable)?;
+        }
+        Ok(())
+    }
+
+    /// Replay a script produced by [`dump`](Self::dump), read from `dump`.
+    ///
+    /// The current database must not contain any stored relations.
+    /// Each line of the script runs in its own transaction, so that the rows are put
+    /// in batches and the script is never held in memory as a whole. The triggers and
+    /// access levels at the end run together in a last transaction.
+    /// If restoring fails, the database is left partially restored.
+    pub fn restore_dump(&'s self, dump: impl io::BufRead) -> Result<()> {
+        {
+            let tx = self.transact()?;
+            if !Self::catalog_entries(&tx).is_empty() {
+                bail!("Cannot restore dump: the database is not empty");
+            }
+        }
+        let mut settings = String::new();
+        for line in dump.lines() {
+            let line = line.into_diagnostic()?;
+            // trigger bodies span several lines
+            if !settings.is_empty()
+                || line.starts_with("{::set_triggers")
+                || line.starts_with("{::access_level")
+            {
+                settings.push_str(&line);
+                settings.push('\n');
+            } else if !line.trim().is_empty() {
+                self.run_script(&line, Default::default(), ScriptMutability::Mutable)?;
+            }
+        }
+        if !settings.is_empty() {
+            self.run_script(&settings, Default::default(), ScriptMutability::Mutable)?;
+        }
+        Ok(())
+    }
+}
+
+/// The `:create` statement for a stored relation.
+pub(crate) fn relation_ddl(handle: &RelationHandle) -> String {
+    let keys = handle.metadata.keys.iter().map(column_ddl).join(", ");
+    let non_keys = handle.metadata.non_keys.iter().map(column_ddl).join(", ");
+    if non_keys.is_empty() {
+        format!(":create {} {{{keys}}}", handle.name)
+    } else {
+        format!(":create {} {{{keys} => {non_keys}}}", handle.name)
+    }
+}
+
+/// The statements creating the indices of a stored relation.
+pub(crate) fn index_ddls(handle: &RelationHandle) -> Vec<String> {
+    let col_names = handle
+        .metadata
+        .keys
+        .iter()
+        .chain(handle.metadata.non_keys.iter())
+        .map(|col| col.name.as_str())
+        .collect_vec();
+    let mut ret = vec![];
+    for (name, (_, extractor)) in &handle.indices {
+        let cols = index_columns(extractor, handle.metadata.keys.len())
+            .iter()
+            .map(|i| col_names[*i])
+            .join(", ");
+        ret.push(format!("::index create {}:{name} {{{cols}}}", handle.name));
+    }
+    for (name, (_, manifest)) in &handle.hnsw_indices {
+        let fields = manifest.vec_fields.iter().map(|i| col_names[*i]).join(", ");
+        let dtype = match manifest.dtype {
+            VecElementType::F32 => "F32",
+            VecElementType::F64 => "F64",
+        };
+        let distance = match manifest.distance {
+            HnswDistance::L2 => "L2",
+            HnswDistance::InnerProduct => "IP",
+            HnswDistance::Cosine => "Cosine",
+        };
+        let mut opts = format!(
+            "dim: {}, m: {}, ef: {}, dtype: {dtype}, fields: [{fields}], distance: {distance}, \
+             extend_candidates: {}, keep_pruned_connections: {}",
+            manifest.vec_dim,
+            manifest.m_neighbours,
+            manifest.ef_construction,
+            manifest.extend_candidates,
+            manifest.keep_pruned_connections
+        );
+        if let Some(filter) = &manifest.index_filter {
+            write!(opts, ", filter: {}", reprint_expr(filter)).unwrap();
+        }
+        ret.push(format!("::hnsw create {}:{name} {{{opts}}}", handle.name));
+    }
+    for (name, (_, manifest)) in &handle.fts_indices {
+        ret.push(format!(
+            "::fts create {}:{name} {{extractor: {}, tokenizer: {}, filters: [{}]}}",
+            handle.name,
+            reprint_expr(&manifest.extractor),
+            tokenizer_ddl(&manifest.tokenizer),
+            manifest.filters.iter().map(tokenizer_ddl).join(", ")
+        ));
+    }
+    for (name, (_, _, manifest)) in &handle.lsh_indices {
+        ret.push(format!(
+            "::lsh create {}:{name} {{extractor: {}, tokenizer: {}, filters: [{}], \
+             n_gram: {}, n_perm: {}, target_threshold: {}}}",
+            handle.name,
+            reprint_expr(&manifest.extractor),
+            tokenizer_ddl(&manifest.tokenizer),
+            manifest.filters.iter().map(tokenizer_ddl).join(", "),
+            manifest.n_gram,
+            manifest.num_perm,
+            literal(&DataValue::from(manifest.threshold))
+        ));
+    }
+    ret
+}
+
+/// The columns given when an index was created, without the key columns that were
+/// appended to them. `extractor` holds the positions of all the columns of the index,
+/// and the first `n_keys` positions are the keys of the relation.
+fn index_columns(extractor: &[usize], n_keys: usize) -> &[usize] {
+    (1..=extractor.len())
+        .map(|len| &extractor[..len])
+        .find(|given| {
+            let appended = (0..n_keys).filter(|k| !given.contains(k));
+            given
+                .iter()
+                .copied()
+                .chain(appended)
+                .eq(extractor.iter().copied())
+        })
+        .unwrap_or(extractor)
+}
+
+/// The statements restoring the triggers and the access level of a stored relation.
+fn settings_ddls(handle: &RelationHandle) -> Vec<String> {
+    let mut ret = vec![];
+    let has_triggers = !handle.put_triggers.is_empty()
+        || !handle.rm_triggers.is_empty()
+        || !handle.replace_triggers.is_empty();
+    if has_triggers {
+        let mut stmt = format!("::set_triggers {}", handle.name);
+        for (kind, triggers) in [
+            ("put", &handle.put_triggers),
+            ("rm", &handle.rm_triggers),
+            ("replace", &handle.replace_triggers),
+        ] {
+            for trigger in triggers {
+                write!(stmt, "\non {kind} {{\n{trigger}\n}}").unwrap();
+            }
+        }
+        ret.push(stmt);
+    }
+    if handle.access_level != AccessLevel::Normal {
+        ret.push(format!(
+            "::access_level {} {}",
+            handle.access_level, handle.name
+        ));
+    }
+    ret
+}
+
+fn put_op(handle: &RelationHandle) -> String {
+    let keys = handle
+        .metadata
+        .keys
+        .iter()
+        .map(|col| col.name.as_str())
+        .join(", ");
+    let non_keys = handle
+        .metadata
+        .non_keys
+        .iter()
+        .map(|col| col.name.as_str())
+        .join(", ");
+    if non_keys.is_empty() {
+        format!(":put {} {{{keys}}}", handle.name)
+    } else {
+        format!(":put {} {{{keys} => {non_keys}}}", handle.name)
+    }
+}
+
+fn column_ddl(col: &ColumnDef) -> String {
+    match &col.default_gen {
+        None => format!("{}: {}", col.name, col.typing),
+        Some(expr) => format!("{}: {} default {}", col.name, col.typing, expr_script(expr)),
+    }
+}
+
+fn tokenizer_ddl(config: &TokenizerConfig) -> String {
+    if config.args.is_empty() {
+        config.name.to_string()
+    } else {
+        format!(
+            "{}({})",
+            config.name,
+            config.args.iter().map(literal).join(", ")
+        )
+    }
+}
+
+/// CozoScript source of an expression, which parses back to the same expression.
+///
+/// Function applications are always written as `name(args)`, also without arguments,
+/// and constants as [literal]s. This is unlike the [`Display`](std::fmt::Display) form,
+/// which is meant for humans and writes `now()` as `now`.
+pub(crate) fn expr_script(expr: &Expr) -> String {
+    let apply = |name: &str, args: &mut dyn Iterator<Item = &Expr>| {
+        format!("{name}({})", args.map(expr_script).join(", "))
+    };
+    match expr {
+        Expr::Binding { var, .. } => var.name.to_string(),
+        Expr::Const { val, .. } => literal(val),
+        // inserted by the parser around the patterns passed to the regex functions
+        Expr::Apply { op, args, .. } if op.name == "OP_REGEX" => expr_script(&args[0]),
+        Expr::Apply { op, args, .. } => apply(
+            &op.name.strip_prefix("OP_").unwrap().to_lowercase(),
+            &mut args.iter(),
+        ),
+        Expr::UnboundApply { op, args, .. } => apply(op, &mut args.iter()),
+        Expr::Cond { clauses, .. } => apply(
+            "cond",
+            &mut clauses.iter().flat_map(|(cond, val)| [cond, val]),
+        ),
+    }
+}
+
+/// Parse an expression stored as source, and write it back with [expr_script].
+/// Falls back to the stored source if it cannot be parsed.
+fn reprint_expr(src: &str) -> String {
+    let parsed = match CozoScriptParser::parse(Rule::expr, src) {
+        Ok(mut pairs) => pairs.next().unwrap(),
+        Err(_) => return src.to_string(),
+    };
+    match build_expr(parsed, &Default::default()) {
+        Ok(expr) => expr_script(&expr),
+        Err(_) => src.to_string(),
+    }
+}
+
+/// A CozoScript string literal for `s`, made of ASCII characters only.
+///
+/// The literal is single-quoted, as double-quoted strings do not support escapes. Characters outside of printable ASCII are written as `\uXXXX` escapes, using a
+/// surrogate pair for characters outside of the basic multilingual plane.
+pub(crate) fn string_literal(s: &str) -> String {
+    let mut ret = String::with_capacity(s.len() + 2);
+    ret.push('\'');
+    for c in s.chars() {
+        match c {
+            '\'' => ret.push_str("\\'"),
+            '\\' => ret.push_str("\\\\"),
+            '\n' => ret.push_str("\\n"),
+            '\r' => ret.push_str("\\r"),
+            '\t' => ret.push_str("\\t"),
+            ' '..='~' => ret.push(c),
+            c => {
+                let mut units = [0u16; 2];
+                for unit in c.encode_utf16(&mut units) {
+                    write!(ret, "\\u{unit:04x}").unwrap();
+                }
+            }
+        }
+    }
+    ret.push('\'');
+    ret
+}
+
+fn float_literal(f: f64) -> String {
+    if f.is_nan() {
+        r#"to_float("NAN")"#.to_string()
+    } else if f == f64::INFINITY {
+        r#"to_float("INF")"#.to_string()
+    } else if f == f64::NEG_INFINITY {
+        r#"to_float("NEG_INF")"#.to_string()
+    } else {
+        // the display form drops the fractional part of whole floats, which would read back
+        // as integers
+        format!("{f:?}")
+    }
+}
+
+/// A CozoScript literal that evaluates back to exactly `val`.
+fn literal(val: &DataValue) -> String {
+    match val {
+        DataValue::Num(Num::Float(f)) => float_literal(*f),
+        DataValue::Str(s) => string_literal(s),
+        // the regex functions compile patterns given as strings
+        DataValue::Regex(r) => string_literal(r.0.as_str()),
+        DataValue::Vec(Vector::F32(v)) => {
+            format!(
+                "vec([{}])",
+                v.iter().map(|f| float_literal(*f as f64)).join(", ")
+            )
+        }
+        DataValue::Vec(Vector::F64(v)) => format!(
+            "vec([{}], \"F64\")",
+            v.iter().map(|f| float_literal(*f)).join(", ")
+        ),
+        DataValue::List(l) => format!("[{}]", l.iter().map(literal).join(", ")),
+        DataValue::Set(s) => format!("[{}]", s.iter().map(literal).join(", ")),
+        DataValue::Json(j) => format!("parse_json({})", string_literal(&j.0.to_string())),
+        DataValue::Validity(vld) => format!("[{}, {}]", vld.timestamp.0 .0, vld.is_assert.0),
+        v => v.to_string(),
+    }
+}
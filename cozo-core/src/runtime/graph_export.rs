@@ -0,0 +1,284 @@
+/*
+ * Copyright 2022, The Cozo Project Authors.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public License, v. 2.0.
+ * If a copy of the MPL was not distributed with this file,
+ * You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Export of query results as graphs, for visualization in tools such as Graphviz or Gephi.
+//!
+//! Edges are given as rows whose first two columns are the source and the target,
+//! nodes (optionally) as rows whose first column is the node. All remaining columns
+//! become attributes of the node or edge, named by the headers.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use miette::{bail, ensure, Result};
+
+use crate::data::value::{DataValue, Num};
+use crate::NamedRows;
+
+impl NamedRows {
+    /// Write the graph with these rows as edges in the Graphviz DOT language.
+    ///
+    /// Nodes that only appear in the edges are included without attributes.
+    /// Only the current rows are used, `next` is ignored.
+    pub fn to_dot(&self, nodes: Option<&NamedRows>, directed: bool) -> Result<String> {
+        let edges = self;
+        check_shape(nodes, edges)?;
+        let mut ids = NodeIds::default();
+        let (kind, arrow) = if directed {
+            ("digraph", "->")
+        } else {
+            ("graph", "--")
+        };
+        let mut ret = format!("{kind} {{\n");
+        if let Some(nodes) = nodes {
+            for row in &nodes.rows {
+                let id = ids.declare(&row[0])?;
+                write!(ret, "  {}", dot_quote(&id)).unwrap();
+                write_dot_attrs(&mut ret, &nodes.headers[1..], &row[1..]);
+                ret.push_str(";\n");
+            }
+        }
+        for row in &edges.rows {
+            write!(
+                ret,
+                "  {} {arrow} {}",
+                dot_quote(&ids.get(&row[0])),
+                dot_quote(&ids.get(&row[1]))
+            )
+            .unwrap();
+            write_dot_attrs(&mut ret, &edges.headers[2..], &row[2..]);
+            ret.push_str(";\n");
+        }
+        ret.push_str("}\n");
+        Ok(ret)
+    }
+
+    /// Write the graph with these rows as edges in the GraphML format.
+    ///
+    /// Attribute types are inferred from the values of each column. Null values are omitted.
+    /// Nodes that only appear in the edges are included without attributes.
+    /// Only the current rows are used, `next` is ignored.
+    pub fn to_graphml(&self, nodes: Option<&NamedRows>, directed: bool) -> Result<String> {
+        let edges = self;
+        check_shape(nodes, edges)?;
+        let mut ids = NodeIds::default();
+        let mut ret = String::from(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n",
+        );
+        if let Some(nodes) = nodes {
+            write_graphml_keys(&mut ret, "node", &nodes.headers[1..], &nodes.rows, 1);
+        }
+        write_graphml_keys(&mut ret, "edge", &edges.headers[2..], &edges.rows, 2);
+        let edge_default = if directed { "directed" } else { "undirected" };
+        writeln!(ret, "  <graph id=\"G\" edgedefault=\"{edge_default}\">").unwrap();
+        if let Some(nodes) = nodes {
+            for row in &nodes.rows {
+                let id = ids.declare(&row[0])?;
+                write!(ret, "    <node id=\"{}\">", xml_escape(&id)).unwrap();
+                write_graphml_data(&mut ret, "node", &row[1..]);
+                ret.push_str("</node>\n");
+            }
+        }
+        for row in &edges.rows {
+            for val in &row[..2] {
+                if !ids.contains(val) {
+                    writeln!(ret, "    <node id=\"{}\"/>", xml_escape(&ids.get(val))).unwrap();
+                }
+            }
+        }
+        for row in &edges.rows {
+            write!(
+                ret,
+                "    <edge source=\"{}\" target=\"{}\">",
+                xml_escape(&ids.get(&row[0])),
+                xml_escape(&ids.get(&row[1]))
+            )
+            .unwrap();
+            write_graphml_data(&mut ret, "edge", &row[2..]);
+            ret.push_str("</edge>\n");
+        }
+        ret.push_str("  </graph>\n</graphml>\n");
+        Ok(ret)
+    }
+}
+
+fn check_shape(nodes: Option<&NamedRows>, edges: &NamedRows) -> Result<()> {
+    ensure!(
+        edges.headers.len() >= 2,
+        "edges must have at least two columns: the source and the target"
+    );
+    if let Some(nodes) = nodes {
+        ensure!(
+            !nodes.headers.is_empty(),
+            "nodes must have at least one column"
+        );
+    }
+    let mut tables = vec![("edges", edges)];
+    tables.extend(nodes.map(|nodes| ("nodes", nodes)));
+    for (what, table) in tables {
+        if let Some(row) = table
+            .rows
+            .iter()
+            .find(|row| row.len() != table.headers.len())
+        {
+            bail!(
+                "a row of {what} has {} values, but there are {} headers",
+                row.len(),
+                table.headers.len()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// The textual ids of the nodes. Strings are used as they are, other values in their
+/// display form. Distinct values with the same text, such as `1` and `'1'`, get
+/// a `#2`, `#3`... suffix in the order they are met.
+#[derive(Default)]
+struct NodeIds {
+    ids: BTreeMap<DataValue, String>,
+    taken: BTreeSet<String>,
+}
+
+impl NodeIds {
+    fn contains(&self, val: &DataValue) -> bool {
+        self.ids.contains_key(val)
+    }
+    fn get(&mut self, val: &DataValue) -> String {
+        if let Some(id) = self.ids.get(val) {
+            return id.clone();
+        }
+        let text = value_str(val);
+        let mut id = text.clone();
+        let mut n = 1;
+        while self.taken.contains(&id) {
+            n += 1;
+            id = format!("{text}#{n}");
+        }
+        self.taken.insert(id.clone());
+        self.ids.insert(val.clone(), id.clone());
+        id
+    }
+    /// The id of a node given by a row of nodes, which must be its only row.
+    fn declare(&mut self, val: &DataValue) -> Result<String> {
+        ensure!(
+            !self.contains(val),
+            "node {val} appears in more than one row of nodes"
+        );
+        Ok(self.get(val))
+    }
+}
+
+/// Strings are used as they are, other values in their display form.
+fn value_str(val: &DataValue) -> String {
+    match val {
+        DataValue::Str(s) => s.to_string(),
+        v => v.to_string(),
+    }
+}
+
+fn dot_quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn write_dot_attrs(out: &mut String, headers: &[String], vals: &[DataValue]) {
+    let attrs = headers
+        .iter()
+        .zip(vals)
+        .filter(|(_, v)| **v != DataValue::Null)
+        .map(|(k, v)| format!("{}={}", dot_quote(k), dot_quote(&value_str(v))))
+        .collect::<Vec<_>>();
+    if !attrs.is_empty() {
+        write!(out, " [{}]", attrs.join(", ")).unwrap();
+    }
+}
+
+/// Escapes text for attributes and character data. Tabs and line breaks become character
+/// references so that they survive in attributes, and the characters XML cannot contain at all,
+/// such as other control characters, are replaced by U+FFFD.
+fn xml_escape(s: &str) -> String {
+    let mut ret = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => ret.push_str("&amp;"),
+            '<' => ret.push_str("&lt;"),
+            '>' => ret.push_str("&gt;"),
+            '"' => ret.push_str("&quot;"),
+            '\'' => ret.push_str("&apos;"),
+            '\t' | '\n' | '\r' => write!(ret, "&#x{:X};", c as u32).unwrap(),
+            '\u{0}'..='\u{1f}' | '\u{fffe}' | '\u{ffff}' => ret.push('\u{fffd}'),
+            c => ret.push(c),
+        }
+    }
+    ret
+}
+
+fn write_graphml_keys(
+    out: &mut String,
+    target: &str,
+    headers: &[String],
+    rows: &[Vec<DataValue>],
+    offset: usize,
+) {
+    for (i, header) in headers.iter().enumerate() {
+        let mut vals = rows
+            .iter()
+            .map(|row| &row[i + offset])
+            .filter(|v| **v != DataValue::Null)
+            .peekable();
+        let typ = if vals.peek().is_none() {
+            "string"
+        } else if vals.clone().all(|v| matches!(v, DataValue::Bool(_))) {
+            "boolean"
+        } else if vals
+            .clone()
+            .all(|v| matches!(v, DataValue::Num(Num::Int(_))))
+        {
+            "long"
+        } else if vals.all(|v| matches!(v, DataValue::Num(_))) {
+            "double"
+        } else {
+            "string"
+        };
+        writeln!(
+            out,
+            "  <key id=\"{target}_{i}\" for=\"{target}\" attr.name=\"{}\" attr.type=\"{typ}\"/>",
+            xml_escape(header)
+        )
+        .unwrap();
+    }
+}
+
+fn write_graphml_data(out: &mut String, target: &str, vals: &[DataValue]) {
+    for (i, val) in vals.iter().enumerate() {
+        if *val != DataValue::Null {
+            write!(
+                out,
+                "<data key=\"{target}_{i}\">{}</data>",
+                xml_escape(&graphml_value(val))
+            )
+            .unwrap();
+        }
+    }
+}
+
+/// Like [value_str], but with non-finite floats written as GraphML readers parse them.
+fn graphml_value(val: &DataValue) -> String {
+    match val {
+        DataValue::Num(Num::Float(f)) if f.is_nan() => "NaN".to_string(),
+        DataValue::Num(Num::Float(f)) if f.is_infinite() => {
+            if f.is_sign_negative() {
+                "-Infinity".to_string()
+            } else {
+                "Infinity".to_string()
+            }
+        }
+        v => value_str(v),
+    }
+}
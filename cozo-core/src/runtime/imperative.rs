@@ -53,6 +53,7 @@ impl<'s, S: Storage<'s>> Db<S> {
                 cur_vld,
                 callback_targets,
                 callback_collector,
+                None,
             )?,
         };
         if let Right(pg) = &p {
@@ -98,6 +99,7 @@ impl<'s, S: Storage<'s>> Db<S> {
                                 cur_vld,
                                 callback_targets,
                                 callback_collector,
+                                None,
                             )?,
                             Right(rel) => {
                                 let relation = tx.get_relation(rel, false)?;
@@ -120,7 +122,7 @@ impl<'s, S: Storage<'s>> Db<S> {
                     ret = NamedRows::default();
                 }
                 ImperativeStmt::SysOp { sysop, .. } => {
-                    ret = self.run_sys_op_with_tx(tx, &sysop.sysop, readonly, true)?;
+                    ret = self.run_sys_op_with_tx(tx, &sysop.sysop, cur_vld, readonly, true)?;
                     if let Some(store_as) = &sysop.store_as {
                         tx.script_store_as_relation(self, store_as, &ret, cur_vld)?;
                     }
@@ -133,6 +135,7 @@ impl<'s, S: Storage<'s>> Db<S> {
                         cur_vld,
                         callback_targets,
                         callback_collector,
+                        None,
                     )?;
                     if let Some(store_as) = &prog.store_as {
                         tx.script_store_as_relation(self, store_as, &ret, cur_vld)?;
@@ -146,6 +149,7 @@ impl<'s, S: Storage<'s>> Db<S> {
                         cur_vld,
                         callback_targets,
                         callback_collector,
+                        None,
                     ) {
                         Ok(res) => {
                             if let Some(store_as) = &prog.store_as {
@@ -393,6 +397,7 @@ impl SessionTx<'_> {
             &mut Default::default(),
             true,
             "",
+            false,
         )?;
         Ok(())
     }
@@ -8,6 +8,8 @@
 
 pub(crate) mod callback;
 pub(crate) mod db;
+pub(crate) mod dump;
+pub(crate) mod graph_export;
 pub(crate) mod imperative;
 pub(crate) mod relation;
 pub(crate) mod temp_store;
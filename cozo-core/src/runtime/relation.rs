@@ -8,6 +8,7 @@
 
 use std::collections::BTreeMap;
 use std::fmt::{Debug, Display, Formatter};
+use std::mem;
 use std::sync::atomic::Ordering;
 
 use itertools::Itertools;
@@ -21,14 +22,17 @@ use thiserror::Error;
 
 use crate::data::memcmp::MemCmpEncoder;
 use crate::data::relation::{ColType, ColumnDef, NullableColType, StoredRelationMetadata};
-use crate::data::symb::Symbol;
-use crate::data::tuple::{decode_tuple_from_key, Tuple, TupleT, ENCODED_KEY_MIN_LEN};
-use crate::data::value::{DataValue, ValidityTs};
+use crate::data::symb::{normalize_ident, Symbol};
+use crate::data::tuple::{
+    decode_tuple_from_key, try_decode_tuple_from_key, Tuple, TupleT, ENCODED_KEY_MIN_LEN,
+};
+use crate::data::value::{DataValue, ValidityTs, LARGEST_UTF_CHAR};
 use crate::fts::FtsIndexManifest;
 use crate::parse::expr::build_expr;
 use crate::parse::sys::{FtsIndexConfig, HnswIndexConfig, MinHashLshConfig};
 use crate::parse::{CozoScriptParser, Rule, SourceSpan};
 use crate::query::compile::IndexPositionUse;
+use crate::query::stored::ensure_no_missing_columns;
 use crate::runtime::hnsw::HnswIndexManifest;
 use crate::runtime::minhash_lsh::{HashPermutations, LshParams, MinHashLshIndexManifest, Weights};
 use crate::runtime::transact::SessionTx;
@@ -92,7 +96,162 @@ pub(crate) struct RelationHandle {
     pub(crate) description: SmartString<LazyCompact>,
 }
 
+/// Statistics of a stored relation collected by `::analyze` and used by the planner.
+/// They are a snapshot: later writes do not update them.
+///
+/// They are stored apart from the catalog, under [`relation_stats_key`], so that they
+/// are only decoded when needed.
+#[derive(Clone, Debug, Default, PartialEq, serde_derive::Serialize, serde_derive::Deserialize)]
+pub(crate) struct RelationStats {
+    pub(crate) rows: u64,
+    /// one per column, keys first
+    pub(crate) columns: Vec<ColumnStats>,
+}
+
+#[derive(Clone, Debug, PartialEq, serde_derive::Serialize, serde_derive::Deserialize)]
+pub(crate) struct ColumnStats {
+    pub(crate) nulls: u64,
+    /// number of distinct non-null values, estimated for large relations
+    pub(crate) distinct: u64,
+    pub(crate) distinct_exact: bool,
+    /// the smallest and the largest non-null values, null if there are none
+    /// or if they are too large to keep, see [`ColumnStats::bounded_value`]
+    pub(crate) min: DataValue,
+    pub(crate) max: DataValue,
+    /// equi-depth histogram of the non-null values: the upper bounds of buckets holding
+    /// about the same number of rows, computed from a sample for large relations
+    pub(crate) histogram: Vec<DataValue>,
+}
+
+impl ColumnStats {
+    /// Longest string or byte array kept as a bound or in a histogram.
+    pub(crate) const MAX_VALUE_LEN: usize = 256;
+
+    /// The value if it is small enough to be kept in the statistics, otherwise null.
+    /// Lists, vectors and JSON values are never kept.
+    pub(crate) fn bounded_value(val: DataValue) -> DataValue {
+        match &val {
+            DataValue::Str(s) if s.len() > Self::MAX_VALUE_LEN => DataValue::Null,
+            DataValue::Bytes(b) if b.len() > Self::MAX_VALUE_LEN => DataValue::Null,
+            DataValue::List(_)
+            | DataValue::Set(_)
+            | DataValue::Vec(_)
+            | DataValue::Json(_)
+            | DataValue::Regex(_) => DataValue::Null,
+            _ => val,
+        }
+    }
+}
+
+/// Key of the statistics of a relation, in the system keyspace next to the storage version,
+/// where scans of the catalog do not reach.
+pub(crate) fn relation_stats_key(id: RelationId) -> Vec<u8> {
+    vec![
+        DataValue::Null,
+        DataValue::from("RELATION_STATS"),
+        DataValue::from(id.0 as i64),
+    ]
+    .encode_as_key(RelationId::SYSTEM)
+}
+
+impl RelationStats {
+    /// Estimated number of rows matching given values for all the columns at `positions`,
+    /// assuming the columns are independent and the values uniformly distributed.
+    pub(crate) fn estimate_rows(&self, positions: &[usize]) -> f64 {
+        positions.iter().fold(self.rows as f64, |est, i| {
+            let distinct = self.columns.get(*i).map_or(1, |col| col.distinct.max(1));
+            est / distinct as f64
+        })
+    }
+}
+
 impl RelationHandle {
+    /// Names of columns or of indices of the relation that are distinct, but become the same
+    /// once put in Unicode normalization form C, as a message.
+    fn name_collision(&self) -> Option<String> {
+        fn colliding<'a>(names: impl Iterator<Item = &'a str>) -> Option<(String, String)> {
+            let mut seen: BTreeMap<SmartString<LazyCompact>, &str> = BTreeMap::new();
+            for name in names {
+                if let Some(other) = seen.insert(normalize_ident(name), name) {
+                    return Some((other.to_string(), name.to_string()));
+                }
+            }
+            None
+        }
+
+        let columns = self
+            .metadata
+            .keys
+            .iter()
+            .chain(self.metadata.non_keys.iter())
+            .map(|col| col.name.as_str());
+        if let Some((a, b)) = colliding(columns) {
+            return Some(format!(
+                "its columns {a:?} and {b:?} have the same normalized name"
+            ));
+        }
+        let indices = self
+            .indices
+            .keys()
+            .chain(self.hnsw_indices.keys())
+            .chain(self.fts_indices.keys())
+            .chain(self.lsh_indices.keys())
+            .map(|name| name.as_str());
+        if let Some((a, b)) = colliding(indices) {
+            return Some(format!(
+                "its indices {a:?} and {b:?} have the same normalized name"
+            ));
+        }
+        None
+    }
+    /// Puts the names of the relation, of its columns and of its indices in Unicode
+    /// normalization form C, as names in scripts are.
+    fn normalize_names(&mut self) {
+        self.name = normalize_ident(&self.name);
+        for col in self
+            .metadata
+            .keys
+            .iter_mut()
+            .chain(self.metadata.non_keys.iter_mut())
+        {
+            col.name = normalize_ident(&col.name);
+        }
+        self.indices = mem::take(&mut self.indices)
+            .into_iter()
+            .map(|(name, (mut handle, mapper))| {
+                handle.normalize_names();
+                (normalize_ident(&name), (handle, mapper))
+            })
+            .collect();
+        self.hnsw_indices = mem::take(&mut self.hnsw_indices)
+            .into_iter()
+            .map(|(name, (mut handle, mut manifest))| {
+                handle.normalize_names();
+                manifest.base_relation = normalize_ident(&manifest.base_relation);
+                manifest.index_name = normalize_ident(&manifest.index_name);
+                (normalize_ident(&name), (handle, manifest))
+            })
+            .collect();
+        self.fts_indices = mem::take(&mut self.fts_indices)
+            .into_iter()
+            .map(|(name, (mut handle, mut manifest))| {
+                handle.normalize_names();
+                manifest.base_relation = normalize_ident(&manifest.base_relation);
+                manifest.index_name = normalize_ident(&manifest.index_name);
+                (normalize_ident(&name), (handle, manifest))
+            })
+            .collect();
+        self.lsh_indices = mem::take(&mut self.lsh_indices)
+            .into_iter()
+            .map(|(name, (mut handle, mut inv_handle, mut manifest))| {
+                handle.normalize_names();
+                inv_handle.normalize_names();
+                manifest.base_relation = normalize_ident(&manifest.base_relation);
+                manifest.index_name = normalize_ident(&manifest.index_name);
+                (normalize_ident(&name), (handle, inv_handle, manifest))
+            })
+            .collect();
+    }
     pub(crate) fn has_index(&self, index_name: &str) -> bool {
         self.indices.contains_key(index_name)
             || self.hnsw_indices.contains_key(index_name)
@@ -195,6 +354,7 @@ impl RelationHandle {
     }
     pub(crate) fn choose_index(
         &self,
+        stats: Option<&RelationStats>,
         arg_uses: &[IndexPositionUse],
         validity_query: bool,
     ) -> Option<(RelationHandle, Vec<usize>, bool)> {
@@ -216,6 +376,8 @@ impl RelationHandle {
                 }
             })
             .collect_vec();
+        // with statistics, the index expected to match the fewest rows is chosen
+        let mut min_estimate = f64::INFINITY;
         let mut chosen = None;
         for (manifest, mapper) in self.indices.values() {
             if validity_query && *mapper.last().unwrap() != self.metadata.keys.len() - 1 {
@@ -230,7 +392,22 @@ impl RelationHandle {
                     break;
                 }
             }
-            if cur_prefix_len > max_prefix_len {
+            if cur_prefix_len == 0 {
+                continue;
+            }
+            let better = match stats {
+                None => cur_prefix_len > max_prefix_len,
+                Some(stats) => {
+                    let estimate = stats.estimate_rows(&mapper[..cur_prefix_len]);
+                    if estimate < min_estimate {
+                        min_estimate = estimate;
+                        true
+                    } else {
+                        false
+                    }
+                }
+            };
+            if better {
                 max_prefix_len = cur_prefix_len;
                 let mut need_join = false;
                 for need_pos in required_positions.iter() {
@@ -249,6 +426,17 @@ impl RelationHandle {
         tuple: &[DataValue],
         span: SourceSpan,
     ) -> Result<Vec<u8>> {
+        let mut ret = Vec::with_capacity(4 + 14 * self.metadata.keys.len());
+        self.encode_key_into(tuple, &mut ret, span)?;
+        Ok(ret)
+    }
+    /// Same as `encode_key_for_store`, but writes into `buf` so that its allocation can be reused
+    pub(crate) fn encode_key_into(
+        &self,
+        tuple: &[DataValue],
+        buf: &mut Vec<u8>,
+        span: SourceSpan,
+    ) -> Result<()> {
         let len = self.metadata.keys.len();
         ensure!(
             tuple.len() >= len,
@@ -259,11 +447,12 @@ impl RelationHandle {
                 span
             }
         );
-        let mut ret = self.encode_key_prefix(len);
+        buf.clear();
+        buf.extend(self.id.0.to_be_bytes());
         for val in &tuple[0..len] {
-            ret.encode_datavalue(val);
+            buf.encode_datavalue(val);
         }
-        Ok(ret)
+        Ok(())
     }
     pub(crate) fn encode_partial_key_for_store(&self, tuple: &[DataValue]) -> Vec<u8> {
         let mut ret = self.encode_key_prefix(tuple.len());
@@ -277,14 +466,17 @@ impl RelationHandle {
         tuple: &[DataValue],
         _span: SourceSpan,
     ) -> Result<Vec<u8>> {
-        let start = self.metadata.keys.len();
-        let len = self.metadata.non_keys.len();
-        let mut ret = self.encode_key_prefix(len);
-        tuple[start..]
-            .serialize(&mut Serializer::new(&mut ret))
-            .unwrap();
+        let mut ret = Vec::with_capacity(4 + 14 * self.metadata.non_keys.len());
+        self.encode_val_into(tuple, &mut ret);
         Ok(ret)
     }
+    /// Same as `encode_val_for_store`, but writes into `buf` so that its allocation can be reused
+    pub(crate) fn encode_val_into(&self, tuple: &[DataValue], buf: &mut Vec<u8>) {
+        let start = self.metadata.keys.len();
+        buf.clear();
+        buf.extend(self.id.0.to_be_bytes());
+        tuple[start..].serialize(&mut Serializer::new(buf)).unwrap();
+    }
     pub(crate) fn encode_val_only_for_store(
         &self,
         tuple: &[DataValue],
@@ -298,6 +490,7 @@ impl RelationHandle {
         &self,
         inp: &InputRelationHandle,
         is_remove_or_update: bool,
+        lenient: bool,
     ) -> Result<()> {
         let InputRelationHandle { metadata, .. } = inp;
         // check that every given key is found and compatible
@@ -305,15 +498,17 @@ impl RelationHandle {
             self.metadata.compatible_with_col(col)?
         }
         // check that every key is provided or has default
-        for col in &self.metadata.keys {
-            metadata.satisfied_by_required_col(col)?;
-        }
+        let mut required = self.metadata.keys.iter().collect_vec();
         if !is_remove_or_update {
-            for col in &self.metadata.non_keys {
-                metadata.satisfied_by_required_col(col)?;
-            }
+            required.extend(self.metadata.non_keys.iter());
         }
-        Ok(())
+        let missing = required
+            .into_iter()
+            .filter(|col| !(lenient && col.typing.nullable))
+            .filter(|col| !metadata.satisfies_required_col(col))
+            .map(|col| col.name.clone())
+            .collect_vec();
+        ensure_no_missing_columns(&self.name, missing, inp.span)
     }
 }
 
@@ -354,17 +549,31 @@ impl RelationHandle {
             RelationDeserError
         })?)
     }
+    /// Counts the rows read through `it` in `tx`, unless this is a temporary relation.
+    fn count_reads<'a>(
+        &self,
+        tx: &'a SessionTx<'_>,
+        it: impl Iterator<Item = Result<Tuple>> + 'a,
+    ) -> impl Iterator<Item = Result<Tuple>> + 'a {
+        let rows_read = (!self.is_temp).then_some(&tx.rows_read);
+        it.inspect(move |row| {
+            if let (Some(rows_read), Ok(_)) = (rows_read, row) {
+                rows_read.fetch_add(1, Ordering::Relaxed);
+            }
+        })
+    }
     pub(crate) fn scan_all<'a>(
         &self,
         tx: &'a SessionTx<'_>,
     ) -> impl Iterator<Item = Result<Tuple>> + 'a {
         let lower = Tuple::default().encode_as_key(self.id);
         let upper = Tuple::default().encode_as_key(self.id.next());
-        if self.is_temp {
+        let it = if self.is_temp {
             tx.temp_store_tx.range_scan_tuple(&lower, &upper)
         } else {
             tx.store_tx.range_scan_tuple(&lower, &upper)
-        }
+        };
+        self.count_reads(tx, it)
     }
 
     pub(crate) fn skip_scan_all<'a>(
@@ -374,12 +583,13 @@ impl RelationHandle {
     ) -> impl Iterator<Item = Result<Tuple>> + 'a {
         let lower = Tuple::default().encode_as_key(self.id);
         let upper = Tuple::default().encode_as_key(self.id.next());
-        if self.is_temp {
+        let it = if self.is_temp {
             tx.temp_store_tx
                 .range_skip_scan_tuple(&lower, &upper, valid_at)
         } else {
             tx.store_tx.range_skip_scan_tuple(&lower, &upper, valid_at)
-        }
+        };
+        self.count_reads(tx, it)
     }
 
     pub(crate) fn get(&self, tx: &SessionTx<'_>, key: &[DataValue]) -> Result<Option<Tuple>> {
@@ -390,10 +600,14 @@ impl RelationHandle {
                 .get(&key_data, false)?
                 .map(|val_data| decode_tuple_from_kv(&key_data, &val_data, Some(self.arity()))))
         } else {
-            Ok(tx
+            let ret = tx
                 .store_tx
                 .get(&key_data, false)?
-                .map(|val_data| decode_tuple_from_kv(&key_data, &val_data, Some(self.arity()))))
+                .map(|val_data| decode_tuple_from_kv(&key_data, &val_data, Some(self.arity())));
+            if ret.is_some() {
+                tx.rows_read.fetch_add(1, Ordering::Relaxed);
+            }
+            Ok(ret)
         }
     }
 
@@ -409,10 +623,14 @@ impl RelationHandle {
                 .get(&key_data, false)?
                 .map(|val_data| rmp_serde::from_slice(&val_data[ENCODED_KEY_MIN_LEN..]).unwrap()))
         } else {
-            Ok(tx
+            let ret = tx
                 .store_tx
                 .get(&key_data, false)?
-                .map(|val_data| rmp_serde::from_slice(&val_data[ENCODED_KEY_MIN_LEN..]).unwrap()))
+                .map(|val_data| rmp_serde::from_slice(&val_data[ENCODED_KEY_MIN_LEN..]).unwrap());
+            if ret.is_some() {
+                tx.rows_read.fetch_add(1, Ordering::Relaxed);
+            }
+            Ok(ret)
         }
     }
 
@@ -436,13 +654,14 @@ impl RelationHandle {
         upper.push(DataValue::Bot);
         let prefix_encoded = lower.encode_as_key(self.id);
         let upper_encoded = upper.encode_as_key(self.id);
-        if self.is_temp {
+        let it = if self.is_temp {
             tx.temp_store_tx
                 .range_scan_tuple(&prefix_encoded, &upper_encoded)
         } else {
             tx.store_tx
                 .range_scan_tuple(&prefix_encoded, &upper_encoded)
-        }
+        };
+        self.count_reads(tx, it)
     }
 
     pub(crate) fn skip_scan_prefix<'a>(
@@ -457,13 +676,14 @@ impl RelationHandle {
         upper.push(DataValue::Bot);
         let prefix_encoded = lower.encode_as_key(self.id);
         let upper_encoded = upper.encode_as_key(self.id);
-        if self.is_temp {
+        let it = if self.is_temp {
             tx.temp_store_tx
                 .range_skip_scan_tuple(&prefix_encoded, &upper_encoded, valid_at)
         } else {
             tx.store_tx
                 .range_skip_scan_tuple(&prefix_encoded, &upper_encoded, valid_at)
-        }
+        };
+        self.count_reads(tx, it)
     }
 
     pub(crate) fn scan_bounded_prefix<'a>(
@@ -480,12 +700,13 @@ impl RelationHandle {
         upper_t.push(DataValue::Bot);
         let lower_encoded = lower_t.encode_as_key(self.id);
         let upper_encoded = upper_t.encode_as_key(self.id);
-        if self.is_temp {
+        let it = if self.is_temp {
             tx.temp_store_tx
                 .range_scan_tuple(&lower_encoded, &upper_encoded)
         } else {
             tx.store_tx.range_scan_tuple(&lower_encoded, &upper_encoded)
-        }
+        };
+        self.count_reads(tx, it)
     }
     pub(crate) fn skip_scan_bounded_prefix<'a>(
         &self,
@@ -502,13 +723,14 @@ impl RelationHandle {
         upper_t.push(DataValue::Bot);
         let lower_encoded = lower_t.encode_as_key(self.id);
         let upper_encoded = upper_t.encode_as_key(self.id);
-        if self.is_temp {
+        let it = if self.is_temp {
             tx.temp_store_tx
                 .range_skip_scan_tuple(&lower_encoded, &upper_encoded, valid_at)
         } else {
             tx.store_tx
                 .range_skip_scan_tuple(&lower_encoded, &upper_encoded, valid_at)
-        }
+        };
+        self.count_reads(tx, it)
     }
 }
 
@@ -523,6 +745,20 @@ pub fn decode_tuple_from_kv(key: &[u8], val: &[u8], size_hint: Option<usize>) ->
     tup
 }
 
+/// Same as [decode_tuple_from_kv], but returns `None` instead of panicking on malformed data.
+pub(crate) fn try_decode_tuple_from_kv(
+    key: &[u8],
+    val: &[u8],
+    size_hint: Option<usize>,
+) -> Option<Tuple> {
+    let mut tup = try_decode_tuple_from_key(key, size_hint.unwrap_or(DEFAULT_SIZE_HINT))?;
+    if !val.is_empty() {
+        let vals: Vec<DataValue> = rmp_serde::from_slice(val.get(ENCODED_KEY_MIN_LEN..)?).ok()?;
+        tup.extend(vals);
+    }
+    Some(tup)
+}
+
 pub fn extend_tuple_from_v(key: &mut Tuple, val: &[u8]) {
     if !val.is_empty() {
         let vals: Vec<DataValue> = rmp_serde::from_slice(&val[ENCODED_KEY_MIN_LEN..]).unwrap();
@@ -666,6 +902,85 @@ impl<'a> SessionTx<'a> {
         let mut meta = self.get_relation(name, true)?;
 
         meta.description = SmartString::from(description);
+        self.put_relation_handle(&meta)
+    }
+    pub(crate) fn set_relation_stats(
+        &mut self,
+        handle: &RelationHandle,
+        stats: &RelationStats,
+    ) -> Result<()> {
+        let key = relation_stats_key(handle.id);
+        let mut val = vec![];
+        stats
+            .serialize(&mut Serializer::new(&mut val).with_struct_map())
+            .unwrap();
+        if handle.is_temp {
+            self.temp_store_tx.put(&key, &val)
+        } else {
+            self.store_tx.put(&key, &val)
+        }
+    }
+    /// The statistics last stored by `::analyze`, if any.
+    pub(crate) fn get_relation_stats(
+        &self,
+        handle: &RelationHandle,
+    ) -> Result<Option<RelationStats>> {
+        let key = relation_stats_key(handle.id);
+        let found = if handle.is_temp {
+            self.temp_store_tx.get(&key, false)?
+        } else {
+            self.store_tx.get(&key, false)?
+        };
+        found
+            .map(|val| rmp_serde::from_slice(&val).into_diagnostic())
+            .transpose()
+    }
+    /// Renames the relations, columns and indices whose names were stored by versions
+    /// that did not normalize identifiers, so that the names typed in scripts find them.
+    /// Relations are stored under their IDs, so only the catalog is rewritten.
+    /// A relation is left as it is if renaming it would merge two names into one: if a
+    /// relation with its normalized name already exists, or if two of its columns or two of
+    /// its indices have the same normalized name. Returns why each of these was left alone.
+    pub(crate) fn normalize_catalog_names(&mut self) -> Result<Vec<String>> {
+        let lower = vec![DataValue::from("")].encode_as_key(RelationId::SYSTEM);
+        let upper =
+            vec![DataValue::from(String::from(LARGEST_UTF_CHAR))].encode_as_key(RelationId::SYSTEM);
+        let mut to_rename = vec![];
+        let mut conflicts = vec![];
+        for kv in self.store_tx.range_scan(&lower, &upper) {
+            let (key, val) = kv?;
+            let handle = RelationHandle::decode(&val)?;
+            if let Some(collision) = handle.name_collision() {
+                conflicts.push(format!(
+                    "relation '{}' cannot be renamed: {collision}",
+                    handle.name
+                ));
+                continue;
+            }
+            let mut normalized = handle.clone();
+            normalized.normalize_names();
+            if normalized != handle {
+                to_rename.push((key, normalized));
+            }
+        }
+        for (old_key, handle) in to_rename {
+            let new_key =
+                vec![DataValue::Str(handle.name.clone())].encode_as_key(RelationId::SYSTEM);
+            if new_key != old_key {
+                if self.store_tx.exists(&new_key, true)? {
+                    conflicts.push(format!(
+                        "relation '{}' cannot be renamed: another relation has the normalized name",
+                        handle.name
+                    ));
+                    continue;
+                }
+                self.store_tx.del(&old_key)?;
+            }
+            self.put_relation_handle(&handle)?;
+        }
+        Ok(conflicts)
+    }
+    fn put_relation_handle(&mut self, meta: &RelationHandle) -> Result<()> {
         let name_key = vec![DataValue::Str(meta.name.clone())].encode_as_key(RelationId::SYSTEM);
         let mut meta_val = vec![];
         meta.serialize(&mut Serializer::new(&mut meta_val).with_struct_map())
@@ -712,10 +1027,13 @@ impl<'a> SessionTx<'a> {
 
         let key = DataValue::from(name);
         let encoded = vec![key].encode_as_key(RelationId::SYSTEM);
+        let stats_key = relation_stats_key(store.id);
         if is_temp {
             self.temp_store_tx.del(&encoded)?;
+            self.temp_store_tx.del(&stats_key)?;
         } else {
             self.store_tx.del(&encoded)?;
+            self.store_tx.del(&stats_key)?;
         }
         let lower_bound = Tuple::default().encode_as_key(store.id);
         let upper_bound = Tuple::default().encode_as_key(store.id.next());
@@ -62,6 +62,14 @@ impl RegularTempStore {
     pub(crate) fn put_with_skip(&mut self, tuple: Tuple) {
         self.inner.insert(tuple, true);
     }
+    /// Merges two stores, moving the tuples of the smaller one into the larger one.
+    pub(crate) fn union(mut self, mut other: Self) -> Self {
+        if self.inner.len() < other.inner.len() {
+            mem::swap(&mut self, &mut other);
+        }
+        self.inner.extend(other.inner);
+        self
+    }
     // returns true if prev is guaranteed to be the same as self after this function call,
     // false if we are not sure.
     pub(crate) fn merge_in(&mut self, prev: &mut Self, mut new: Self) -> bool {
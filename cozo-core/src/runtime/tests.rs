@@ -17,13 +17,18 @@ use smartstring::{LazyCompact, SmartString};
 
 use crate::data::expr::Expr;
 use crate::data::symb::Symbol;
+use crate::data::tuple::{Tuple, TupleT};
 use crate::data::value::DataValue;
 use crate::fixed_rule::FixedRulePayload;
 use crate::fts::{TokenizerCache, TokenizerConfig};
 use crate::parse::SourceSpan;
 use crate::runtime::callback::CallbackOp;
 use crate::runtime::db::Poison;
-use crate::{DbInstance, FixedRule, RegularTempStore, ScriptMutability};
+use crate::runtime::relation::{ColumnStats, RelationId};
+use crate::{
+    CsvOptions, Db, DbInstance, DbMetrics, FixedRule, MemStorage, NamedRows, RegularTempStore,
+    RowSink, ScriptMutability, MAX_VERIFY_PROBLEMS,
+};
 
 #[test]
 fn test_limit_offset() {
@@ -1532,3 +1537,1304 @@ fn hnsw_index() {
     "#).unwrap();
     println!("{}", res.into_json()["rows"][0][4]);
 }
+
+#[test]
+fn equality_filter_uses_index() {
+    let db = DbInstance::default();
+    db.run_default(":create users {id: Int => name: String, city: String}")
+        .unwrap();
+    db.run_default(
+        r"?[id, name, city] <- [[1, 'alice', 'paris'], [2, 'bob', 'london'], [3, 'carol', 'paris']]
+        :put users {id => name, city}",
+    )
+    .unwrap();
+    db.run_default("::index create users:city {city}").unwrap();
+
+    let res = db
+        .run_default("?[name] := *users{name, city}, city == 'paris'")
+        .unwrap();
+    assert_eq!(res.into_json()["rows"], json!([["alice"], ["carol"]]));
+
+    let expl = db
+        .run_default("::explain { ?[name] := *users{name, city}, city == 'paris' }")
+        .unwrap()
+        .into_json();
+    let rows = expl["rows"].as_array().unwrap();
+    assert!(rows.iter().any(|row| row[4] == json!("load_stored")
+        && row[5] == json!(":users:city")
+        && row[9] == json!("prefix scan on index :users:city, bound: city")));
+
+    // numeric constants are converted to the type of the column
+    let res = db
+        .run_default("?[name] := *users{id, name}, id == 2.0")
+        .unwrap();
+    assert_eq!(res.into_json()["rows"], json!([["bob"]]));
+    let expl = db
+        .run_default("::explain { ?[name] := *users{id, name}, id == 2.0 }")
+        .unwrap()
+        .into_json();
+    let rows = expl["rows"].as_array().unwrap();
+    assert!(rows
+        .iter()
+        .any(|row| row[9] == json!("prefix scan on primary key, bound: id")));
+    let res = db
+        .run_default("?[name] := *users{id, name}, id == 2.5")
+        .unwrap();
+    assert_eq!(res.into_json()["rows"], json!([]));
+
+    // `==` compares loosely, so in untyped columns numbers stay as filters
+    db.run_default(":create loose {k: Any => v: Int}").unwrap();
+    db.run_default("?[k, v] <- [[1.0, 1]] :put loose {k => v}")
+        .unwrap();
+    let res = db.run_default("?[v] := *loose{k, v}, k == 1").unwrap();
+    assert_eq!(res.into_json()["rows"], json!([[1]]));
+}
+
+#[test]
+fn analyze_relation() {
+    let db = DbInstance::default();
+    db.run_default(":create stats {k: Int => v: String?}")
+        .unwrap();
+    db.run_default(
+        r"?[k, v] <- [[1, 'a'], [2, 'b'], [3, 'a'], [4, null]]
+        :put stats {k => v}",
+    )
+    .unwrap();
+    let res = db.run_default("::stats stats").unwrap();
+    assert_eq!(res.into_json()["rows"], json!([]));
+    let res = db.run_default("::analyze stats").unwrap();
+    let expected = json!([
+        ["k", true, 4, 0, 4, true, 1, 4, [1, 2, 3, 4]],
+        ["v", false, 4, 1, 2, true, "a", "b", ["a", "a", "b"]]
+    ]);
+    assert_eq!(res.into_json()["rows"], expected);
+    // they are stored, and can be read back
+    let res = db.run_default("::stats stats").unwrap();
+    assert_eq!(res.into_json()["rows"], expected);
+
+    // past the exact limit the distinct count is estimated
+    db.run_default(":create many {k: Int => v: Int}").unwrap();
+    db.run_default("?[k, v] := k in int_range(50000), v = k % 20000 :put many {k => v}")
+        .unwrap();
+    let res = db.run_default("::analyze many").unwrap().into_json();
+    let rows = res["rows"].as_array().unwrap();
+    assert_eq!(rows[0][5], json!(false));
+    assert_eq!(rows[1][5], json!(false));
+    assert_eq!(rows[1][6], json!(0));
+    assert_eq!(rows[1][7], json!(19999));
+    let estimate = rows[1][4].as_i64().unwrap();
+    assert!((19000..21000).contains(&estimate), "{estimate}");
+    // the histogram has ten buckets, computed from a sample
+    assert_eq!(rows[0][8].as_array().unwrap().len(), 10);
+
+    // values too large to keep are left out
+    db.run_default(":create long {k: String}").unwrap();
+    db.run_default("?[k] := x in int_range(300), k = concat('a', to_string(x)) :put long {k}")
+        .unwrap();
+    let long = "z".repeat(ColumnStats::MAX_VALUE_LEN + 1);
+    db.run_default(&format!("?[k] <- [['{long}']] :put long {{k}}"))
+        .unwrap();
+    let res = db.run_default("::analyze long").unwrap().into_json();
+    assert_eq!(res["rows"][0][6], json!("a0"));
+    assert_eq!(res["rows"][0][7], json!(null));
+
+    // removing a relation removes its statistics
+    db.run_default("::remove long").unwrap();
+    db.run_default(":create long {k: String}").unwrap();
+    let res = db.run_default("::stats long").unwrap();
+    assert_eq!(res.into_json()["rows"], json!([]));
+
+    // the statistics are stored, so it needs write access
+    assert!(db
+        .run_script(
+            "::analyze stats",
+            Default::default(),
+            ScriptMutability::Immutable
+        )
+        .is_err());
+}
+
+#[test]
+fn planner_uses_stats() {
+    let db = DbInstance::default();
+    let stored_loads = |script: &str| -> Vec<serde_json::Value> {
+        let expl = db.run_default(script).unwrap().into_json();
+        expl["rows"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|row| row[4] == json!("load_stored"))
+            .map(|row| row[5].clone())
+            .collect()
+    };
+    db.run_default(":create big {k: Int => v: Int}").unwrap();
+    db.run_default("?[k, v] := k in int_range(1000), v = k % 7 :put big {k => v}")
+        .unwrap();
+    db.run_default(":create small {k: Int}").unwrap();
+    db.run_default("?[k] <- [[3], [5]] :put small {k}").unwrap();
+
+    // without statistics, the relations are joined in the order of the query
+    let join = "::explain { ?[k, v] := *big{k, v}, *small{k} }";
+    assert_eq!(stored_loads(join), vec![json!(":big"), json!(":small")]);
+    db.run_default("::analyze small").unwrap();
+    assert_eq!(stored_loads(join), vec![json!(":big"), json!(":small")]);
+    // with them, the small relation is scanned and the big one looked up by key
+    db.run_default("::analyze big").unwrap();
+    assert_eq!(stored_loads(join), vec![json!(":small"), json!(":big")]);
+    let res = db.run_default("?[k, v] := *big{k, v}, *small{k}").unwrap();
+    assert_eq!(res.into_json()["rows"], json!([[3, 3], [5, 5]]));
+
+    // among indices, the most selective one is chosen
+    db.run_default(":create people {id: Int => group: String, name: String}")
+        .unwrap();
+    db.run_default(
+        "?[id, group, name] := id in int_range(100), group = if(id % 2 == 0, 'a', 'b'), \
+        name = to_string(id) :put people {id => group, name}",
+    )
+    .unwrap();
+    db.run_default("::index create people:by_group {group}")
+        .unwrap();
+    db.run_default("::index create people:by_name {name}")
+        .unwrap();
+    let lookup = "::explain { ?[id] := *people{id, group, name}, group == 'a', name == '42' }";
+    // the index is joined with the relation for the column it does not have
+    assert_eq!(
+        stored_loads(lookup),
+        vec![json!(":people:by_group"), json!(":people")]
+    );
+    db.run_default("::analyze people").unwrap();
+    assert_eq!(
+        stored_loads(lookup),
+        vec![json!(":people:by_name"), json!(":people")]
+    );
+    let res = db
+        .run_default("?[id] := *people{id, group, name}, group == 'a', name == '42'")
+        .unwrap();
+    assert_eq!(res.into_json()["rows"], json!([[42]]));
+}
+
+#[test]
+fn explain_analyze() {
+    let db = DbInstance::default();
+    let res = db
+        .run_default(
+            r"::explain analyze {
+                edge[a, b] <- [[1, 2], [2, 3], [3, 4]]
+                path[a, b] := edge[a, b]
+                path[a, b] := path[a, c], edge[c, b]
+                ?[a, b] := path[a, b]
+            }",
+        )
+        .unwrap();
+    let col = |name: &str| res.headers.iter().position(|h| h == name).unwrap();
+    let (op, rule, epochs, rows) = (col("op"), col("rule"), col("epochs"), col("rows"));
+    let (gets, puts) = (col("gets"), col("puts"));
+    let json = res.clone().into_json();
+    let rows_out = json["rows"].as_array().unwrap();
+    let path = rows_out
+        .iter()
+        .find(|row| row[rule].as_str().unwrap().contains("path") && row[op] == json!("out"))
+        .unwrap();
+    assert_eq!(path[rows], json!(6));
+    assert!(path[epochs].as_i64().unwrap() > 1);
+    // the fixed rule is only evaluated in the first epoch
+    let edge = rows_out
+        .iter()
+        .find(|row| row[op] == json!("algo"))
+        .unwrap();
+    assert_eq!(edge[epochs], json!(1));
+    assert!(rows_out
+        .iter()
+        .any(|row| row[op] == json!("load_mem") && row[rows] == json!(3)));
+    assert_eq!(rows_out.last().unwrap()[op], json!("total"));
+
+    // queries writing to stored relations are rejected, and write nothing
+    db.run_default("{:create e {a: Int, b: Int}} {?[a, b] <- [[1, 2], [3, 4]] :put e {a, b}}")
+        .unwrap();
+    let err = db
+        .run_default("::explain analyze { ?[a, b] <- [[5, 6]] :put e {a, b} }")
+        .unwrap_err();
+    assert!(err.to_string().contains("writes to stored relation e"));
+    assert!(db
+        .run_default("::explain analyze { ?[a, b] <- [[1, 2]] :rm e {a, b} }")
+        .is_err());
+    let res = db.run_default("?[a, b] := *e[a, b]").unwrap();
+    assert_eq!(res.into_json()["rows"], json!([[1, 2], [3, 4]]));
+    // other out options are honoured
+    assert!(db
+        .run_default("::explain analyze { ?[a] := *e[a, b] :assert none }")
+        .is_err());
+
+    // storage reads are attributed to the operator doing them
+    let res = db
+        .run_default("::explain analyze { ?[a] := *e[a, b] }")
+        .unwrap()
+        .into_json();
+    let scan = res["rows"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|row| row[op] == json!("load_stored"))
+        .unwrap()
+        .clone();
+    assert_eq!(scan[rows], json!(2));
+    assert_eq!(scan[gets], json!(2));
+    assert_eq!(scan[puts], json!(0));
+    assert!(scan[col("bytes_read")].as_i64().unwrap() > 0);
+}
+
+#[test]
+fn rows_into_structs() {
+    #[derive(serde_derive::Deserialize, Debug, PartialEq)]
+    struct Person {
+        name: String,
+        age: i64,
+        nick: Option<String>,
+    }
+
+    let db = DbInstance::default();
+    let people: Vec<Person> = db
+        .run_as(
+            "?[name, age, nick] <- [['alice', 30, null], ['bob', 25, 'bobby']]",
+            Default::default(),
+            ScriptMutability::Immutable,
+        )
+        .unwrap();
+    assert_eq!(
+        people,
+        vec![
+            Person {
+                name: "alice".to_string(),
+                age: 30,
+                nick: None
+            },
+            Person {
+                name: "bob".to_string(),
+                age: 25,
+                nick: Some("bobby".to_string())
+            }
+        ]
+    );
+    assert!(db
+        .run_as::<Person>(
+            "?[name] <- [['alice']]",
+            Default::default(),
+            ScriptMutability::Immutable
+        )
+        .is_err());
+
+    // values that have no faithful JSON representation
+    #[derive(serde_derive::Deserialize, Debug, PartialEq)]
+    struct Values {
+        b: Vec<u8>,
+        f: f64,
+        u: uuid::Uuid,
+        l: Vec<(i64, String)>,
+        j: serde_json::Value,
+    }
+    let values: Vec<Values> = db
+        .run_as(
+            "?[b, f, u, l, j] := b = decode_base64('AQI='), f = to_float('NAN'), \
+                u = to_uuid('e0f6b5c4-3a3c-11ed-9a4a-0242ac120002'), \
+                l = [[1, 'a']], j = json({'a': [1]})",
+            Default::default(),
+            ScriptMutability::Immutable,
+        )
+        .unwrap();
+    assert_eq!(values[0].b, vec![1, 2]);
+    assert!(values[0].f.is_nan());
+    assert_eq!(
+        values[0].u.to_string(),
+        "e0f6b5c4-3a3c-11ed-9a4a-0242ac120002"
+    );
+    assert_eq!(values[0].l, vec![(1, "a".to_string())]);
+    assert_eq!(values[0].j, json!({"a": [1]}));
+    // bytes are not silently turned into base64 strings
+    #[derive(serde_derive::Deserialize, Debug)]
+    struct Text {
+        #[allow(dead_code)]
+        b: String,
+    }
+    assert!(db
+        .run_as::<Text>(
+            "?[b] := b = decode_base64('/w==')",
+            Default::default(),
+            ScriptMutability::Immutable
+        )
+        .is_err());
+
+    let rows = NamedRows::new(
+        vec!["name".to_string(), "name".to_string()],
+        vec![vec![DataValue::from("a"), DataValue::from("b")]],
+    );
+    assert!(rows.into_structs::<Person>().is_err());
+}
+
+#[test]
+fn timeout_interrupts_long_rule() {
+    let db = DbInstance::default();
+    let res = db.run_default(
+        r"
+        r[x] := x in int_range(100000)
+        ?[a, b] := r[a], r[b]
+        :timeout 0.2
+    ",
+    );
+    assert_eq!(
+        res.unwrap_err().code().unwrap().to_string(),
+        "eval::timeout"
+    );
+
+    // the first reason to stop is the one reported
+    let poison = Poison::default();
+    poison.set_timeout(0.).unwrap();
+    while poison.check().is_ok() {
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    poison.kill();
+    assert_eq!(
+        poison.check().unwrap_err().code().unwrap().to_string(),
+        "eval::timeout"
+    );
+    let poison = Poison::default();
+    poison.kill();
+    poison.set_timeout(0.).unwrap();
+    std::thread::sleep(Duration::from_millis(50));
+    assert_eq!(
+        poison.check().unwrap_err().code().unwrap().to_string(),
+        "eval::killed"
+    );
+}
+
+#[test]
+fn set_options() {
+    let db = DbInstance::default();
+    let slow = r"
+        r[x] := x in int_range(100000)
+        ?[a, b] := r[a], r[b]
+    ";
+    db.run_default("::set timeout = 0.2").unwrap();
+    let err = db.run_default(slow).unwrap_err();
+    assert_eq!(err.code().unwrap().to_string(), "eval::timeout");
+    db.run_default("::set timeout = null").unwrap();
+    db.run_default("::set parallel = true").unwrap();
+    let res = db.run_default("?[x] := x in [1, 2]").unwrap();
+    assert_eq!(res.into_json()["rows"], json!([[1], [2]]));
+
+    let err = db.run_default("::set parallel = 1").unwrap_err();
+    assert_eq!(
+        err.code().unwrap().to_string(),
+        "eval::invalid_option_value"
+    );
+    let err = db.run_default("::set memory = 1").unwrap_err();
+    assert_eq!(err.code().unwrap().to_string(), "eval::unknown_option");
+}
+
+#[test]
+fn index_option_errors_have_spans() {
+    let db = DbInstance::default();
+    let err = db
+        .run_default("::hnsw create a:b {dim: 'x', m: 50, ef: 20, fields: [v]}")
+        .unwrap_err();
+    assert_eq!(
+        err.code().unwrap().to_string(),
+        "parser::invalid_index_option"
+    );
+    assert!(err.labels().unwrap().next().is_some());
+
+    db.run_default(":create a {k: Int => v: <F32; 8>}").unwrap();
+    for script in [
+        "::hnsw create a:b {dim: 0, m: 50, ef: 20, fields: [v]}",
+        "::hnsw create a:b {dim: 8, m: -1, ef: 20, fields: [v]}",
+        "::hnsw create a:b {dim: 8, m: 50, fields: [v]}",
+        "::hnsw create a:b {dim: 8, ef: 20, fields: [v]}",
+        "::lsh create a:c {extractor: k, n_gram: 0}",
+        "::lsh create a:c {extractor: k, target_threshold: 1.5}",
+    ] {
+        let err = db.run_default(script).unwrap_err();
+        assert_eq!(
+            err.code().unwrap().to_string(),
+            "parser::invalid_index_option"
+        );
+        assert!(err.labels().unwrap().next().is_some());
+    }
+    let err = db.run_default("::kill 'x'").unwrap_err();
+    assert_eq!(err.code().unwrap().to_string(), "parser::not_proc_id");
+    assert!(err.labels().unwrap().next().is_some());
+}
+
+#[test]
+fn metrics_counters() {
+    let db = DbInstance::default();
+    assert_eq!(db.metrics(), DbMetrics::default());
+    db.run_default(":create a {x: Int}").unwrap();
+    db.run_default("?[x] <- [[1], [2], [3]] :put a {x}")
+        .unwrap();
+    db.run_default("?[x] <- [[1]] :rm a {x}").unwrap();
+    db.run_default("?[x] := *a[x]").unwrap();
+    assert!(db.run_default("?[x] := nope[x]").is_err());
+    // rows written by a failed transaction are not counted
+    assert!(db
+        .run_default("{?[x] <- [[4]] :put a {x}} {?[x] := *a[x] :assert none}")
+        .is_err());
+    let metrics = db.metrics();
+    assert_eq!(metrics.scripts, 6);
+    assert_eq!(metrics.failed_scripts, 2);
+    assert_eq!(metrics.running_queries, 0);
+    assert_eq!(metrics.rows_written, 3);
+    assert_eq!(metrics.rows_removed, 1);
+    assert!(metrics.rows_returned >= 2);
+    assert_eq!(metrics.failed_commits, 0);
+
+    let rows_read = |db: &DbInstance| db.metrics().rows_read;
+    let before = rows_read(&db);
+    db.run_default("?[x] := *a[x]").unwrap();
+    assert_eq!(rows_read(&db) - before, 2);
+    let before = rows_read(&db);
+    db.run_default("?[x] := x = 3, *a[x]").unwrap();
+    assert_eq!(rows_read(&db) - before, 1);
+}
+
+/// The database behind `db`, for tests reaching into the storage of an in-memory database.
+fn mem_db(db: &DbInstance) -> &Db<MemStorage> {
+    match db {
+        DbInstance::Mem(mem_db) => mem_db,
+        // only reachable when other storage engines are enabled
+        #[allow(unreachable_patterns)]
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn info_and_verify() {
+    let db = DbInstance::default();
+    db.run_default(":create a {x: Int => y: String}").unwrap();
+    db.run_default("?[x, y] <- [[1, 'a'], [2, 'b']] :put a {x => y}")
+        .unwrap();
+    db.run_default("::index create a:idx {y}").unwrap();
+    let info = db.info().unwrap();
+    assert_eq!(info.storage_kind, "mem");
+    assert_eq!(info.relations, 1);
+    assert_eq!(info.indices, 1);
+    assert_eq!(info.path, None);
+    let verification = db.verify().unwrap();
+    assert_eq!(verification.relations, 2);
+    assert_eq!(verification.rows, 4);
+    assert!(verification.problems.is_empty());
+
+    // corrupt the data behind the back of the database
+    let mem_db = mem_db(&db);
+    let mut tx = mem_db.transact_write().unwrap();
+    let handle = tx.get_relation("a", false).unwrap();
+    let idx_handle = handle.indices.values().next().unwrap().0.clone();
+    let mut garbage = vec![DataValue::from(10)].encode_as_key(handle.id);
+    garbage.truncate(garbage.len() - 3);
+    tx.store_tx.put(&garbage, &[]).unwrap();
+    let idx_key = vec![DataValue::from("a"), DataValue::from(1)].encode_as_key(idx_handle.id);
+    tx.store_tx.del(&idx_key).unwrap();
+    // index rows for a changed row and for a row that does not exist
+    for (y, x) in [("q", 2), ("z", 5)] {
+        let key = vec![DataValue::from(y), DataValue::from(x)].encode_as_key(idx_handle.id);
+        tx.store_tx.put(&key, &[]).unwrap();
+    }
+    tx.commit_tx().unwrap();
+    drop(tx);
+
+    let problems = db.verify().unwrap().problems;
+    assert_eq!(problems.len(), 4, "{problems:?}");
+    assert!(problems[0].starts_with("index a:idx has no row for [1, \"a\"]"));
+    assert!(problems[1].starts_with("cannot decode row of relation a"));
+    assert!(problems[2].starts_with("index a:idx has a stale row [\"q\", 2]"));
+    assert!(problems[3].starts_with("index a:idx has a row [\"z\", 5] not found in relation a"));
+
+    // the list of problems is capped
+    let mut tx = mem_db.transact_write().unwrap();
+    for i in 0..MAX_VERIFY_PROBLEMS {
+        let key = vec![DataValue::from(i as i64)].encode_as_key(handle.id);
+        tx.store_tx.put(&key, &[]).unwrap();
+    }
+    tx.commit_tx().unwrap();
+    drop(tx);
+    let verification = db.verify().unwrap();
+    assert_eq!(verification.problems.len(), MAX_VERIFY_PROBLEMS);
+    assert!(verification.more_problems > 0);
+}
+
+#[test]
+fn normalizes_legacy_catalog_names() {
+    use rmp_serde::Serializer;
+    use serde::Serialize;
+    use unicode_normalization::UnicodeNormalization;
+
+    use crate::runtime::transact::{storage_version_key, CURRENT_STORAGE_VERSION};
+
+    let db = DbInstance::default();
+    db.run_default(":create café {né: Int => v: Int}").unwrap();
+    db.run_default("?[né, v] <- [[1, 2]] :put café {né => v}")
+        .unwrap();
+    db.run_default(":create résumé {x: Int}").unwrap();
+    db.run_default(":create naïve {é: Int => x: Int}").unwrap();
+    db.run_default(":create indexed {a: Int => b: Int}")
+        .unwrap();
+    db.run_default("::index create indexed:ï {b}").unwrap();
+
+    // store the names decomposed, as versions that did not normalize identifiers did
+    let mem_db = mem_db(&db);
+    let mut tx = mem_db.transact_write().unwrap();
+    for (name, new_name) in [("café", "résumé"), ("café", "café")] {
+        let mut handle = tx.get_relation(name, false).unwrap();
+        if name == new_name {
+            let old_key = vec![DataValue::from(name)].encode_as_key(RelationId::SYSTEM);
+            tx.store_tx.del(&old_key).unwrap();
+        }
+        handle.name = new_name.nfd().collect();
+        for col in handle.metadata.keys.iter_mut() {
+            col.name = col.name.nfd().collect();
+        }
+        let key = vec![DataValue::Str(handle.name.clone())].encode_as_key(RelationId::SYSTEM);
+        let mut val = vec![];
+        handle
+            .serialize(&mut Serializer::new(&mut val).with_struct_map())
+            .unwrap();
+        tx.store_tx.put(&key, &val).unwrap();
+    }
+    // relations that would end up with two columns or two indices of the same name
+    let mut naive = tx.get_relation("naïve", false).unwrap();
+    let old_key = vec![DataValue::from("naïve")].encode_as_key(RelationId::SYSTEM);
+    tx.store_tx.del(&old_key).unwrap();
+    naive.name = "naïve".nfd().collect();
+    naive.metadata.non_keys[0].name = "é".nfd().collect();
+    let mut indexed = tx.get_relation("indexed", false).unwrap();
+    let index = indexed.indices["ï"].clone();
+    indexed.indices.insert("ï".nfd().collect(), index);
+    for handle in [naive, indexed] {
+        let key = vec![DataValue::Str(handle.name.clone())].encode_as_key(RelationId::SYSTEM);
+        let mut val = vec![];
+        handle
+            .serialize(&mut Serializer::new(&mut val).with_struct_map())
+            .unwrap();
+        tx.store_tx.put(&key, &val).unwrap();
+    }
+    // as stored by the versions that did not normalize identifiers
+    tx.store_tx.put(&storage_version_key(), &[0x00]).unwrap();
+    tx.commit_tx().unwrap();
+    drop(tx);
+    assert!(db.run_default("?[v] := *café{né: 1, v}").is_err());
+
+    mem_db.initialize().unwrap();
+    let tx = mem_db.transact().unwrap();
+    assert_eq!(
+        tx.store_tx.get(&storage_version_key(), false).unwrap(),
+        Some(CURRENT_STORAGE_VERSION.to_vec())
+    );
+    // the colliding names are kept as they were
+    let indexed = tx.get_relation("indexed", false).unwrap();
+    assert_eq!(indexed.indices.len(), 2);
+    drop(tx);
+    let res = db.run_default("?[v] := *café{né: 1, v}").unwrap();
+    assert_eq!(res.into_json()["rows"], json!([[2]]));
+    // the decomposed copy of résumé clashes with the existing relation and is left alone
+    let res = db.run_default("::relations").unwrap();
+    let names = res
+        .rows
+        .iter()
+        .map(|row| row[0].get_str().unwrap().to_string())
+        .sorted()
+        .collect_vec();
+    let mut expected = vec![
+        "café".to_string(),
+        "indexed".to_string(),
+        "indexed:ï".to_string(),
+        "naïve".nfd().collect::<String>(),
+        "résumé".to_string(),
+        "résumé".nfd().collect::<String>(),
+    ];
+    expected.sort();
+    assert_eq!(names, expected);
+}
+
+#[test]
+fn csv_reader_skips_bad_rows() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("data.csv");
+    std::fs::write(&path, "a,b\n1,x\noops,y\n3,z\n").unwrap();
+    let db = DbInstance::default();
+    let script = |path: &std::path::Path, on_error: &str| {
+        format!(
+            "?[i, a, b] <~ CsvReader(types: ['Int', 'String'], url: 'file://{}', \
+                on_error: '{}', prepend_index: true)",
+            path.display(),
+            on_error
+        )
+    };
+    assert!(db.run_default(&script(&path, "fail")).is_err());
+    let res = db.run_default(&script(&path, "skip")).unwrap();
+    assert_eq!(res.into_json()["rows"], json!([[0, 1, "x"], [1, 3, "z"]]));
+    // failing to read the file is not a bad row
+    assert!(db.run_default(&script(dir.path(), "skip")).is_err());
+}
+
+#[test]
+fn rows_to_csv() {
+    let db = DbInstance::default();
+    let res = db
+        .run_default(r#"?[a, b, c] <- [[1, 'x, y', null], [2.5, 'z', [1, 2]]]"#)
+        .unwrap();
+    let mut out = vec![];
+    res.write_csv(&mut out, &CsvOptions::default()).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "a,b,c\n1,\"x, y\",\n2.5,z,\"[1,2]\"\n"
+    );
+
+    let res = db
+        .run_default(r#"?[a, b] <- [['', null], ['NA', 'say "hi"']]"#)
+        .unwrap();
+    let mut out = vec![];
+    res.write_csv(&mut out, &CsvOptions::default()).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "a,b\n\"\",\nNA,\"say \"\"hi\"\"\"\n"
+    );
+    let mut out = vec![];
+    let options = CsvOptions {
+        delimiter: b';',
+        quote_all: true,
+        null_repr: "NA".to_string(),
+    };
+    res.write_csv(&mut out, &options).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "\"a\";\"b\"\n\"\";NA\n\"NA\";\"say \"\"hi\"\"\"\n"
+    );
+    // a null written as any of these could not be told apart from a string
+    for null_repr in [";", "\"", "a\nb"] {
+        let options = CsvOptions {
+            delimiter: b';',
+            null_repr: null_repr.to_string(),
+            ..Default::default()
+        };
+        assert!(res.write_csv(vec![], &options).is_err());
+    }
+}
+
+#[test]
+fn export_csv() {
+    let db = DbInstance::default();
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("out.csv");
+    let options = CsvOptions {
+        null_repr: "NULL".to_string(),
+        ..Default::default()
+    };
+    db.export_csv(
+        "?[x, y] := x in int_range(3), y = if(x == 1, null, to_string(x))",
+        Default::default(),
+        &path,
+        &options,
+    )
+    .unwrap();
+    assert_eq!(
+        std::fs::read_to_string(&path).unwrap(),
+        "x,y\n0,0\n1,NULL\n2,2\n"
+    );
+
+    assert!(db
+        .export_csv("?[x] := *nope[x]", Default::default(), &path, &options)
+        .is_err());
+    // a failed export leaves the existing file alone, and no temporary file behind
+    assert_eq!(
+        std::fs::read_to_string(&path).unwrap(),
+        "x,y\n0,0\n1,NULL\n2,2\n"
+    );
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+    assert!(db
+        .export_csv("?[x] <- [[1]", Default::default(), &path, &options)
+        .is_err());
+    assert!(path.exists());
+    // exports are read-only
+    assert!(db
+        .export_csv(":create t {a}", Default::default(), &path, &options)
+        .is_err());
+}
+
+#[test]
+#[cfg(feature = "arrow")]
+fn rows_to_record_batch() {
+    use arrow_schema::DataType;
+
+    let db = DbInstance::default();
+    let res = db
+        .run_default(
+            r#"?[i, f, s, u, v, n, j] <- [[1, 1, 'a', rand_uuid_v1(), vec([1, 2]), null, [1]],
+                                          [2, 2.5, null, null, null, null, 'x']]"#,
+        )
+        .unwrap();
+    let batch = res.to_record_batch().unwrap();
+    let types = batch
+        .schema()
+        .fields()
+        .iter()
+        .map(|f| f.data_type().clone())
+        .collect_vec();
+    assert_eq!(types[0], DataType::Int64);
+    assert_eq!(types[1], DataType::Float64);
+    assert_eq!(types[2], DataType::Utf8);
+    assert_eq!(types[3], DataType::FixedSizeBinary(16));
+    assert!(matches!(types[4], DataType::FixedSizeList(_, 2)));
+    assert_eq!(types[5], DataType::Null);
+    assert_eq!(types[6], DataType::Utf8);
+
+    let back = NamedRows::from_record_batch(&batch).unwrap();
+    assert_eq!(back.headers, res.headers);
+    for (row, orig) in back.rows.iter().zip(&res.rows) {
+        for i in [0, 2, 3, 4, 5] {
+            assert_eq!(row[i], orig[i]);
+        }
+    }
+    // the integer in a column with floats becomes a float
+    assert_eq!(back.rows[0][1], DataValue::from(1.0));
+    // the JSON text of columns with no Arrow counterpart stays a string
+    assert_eq!(back.rows[0][6], DataValue::from("[1]"));
+    assert_eq!(back.rows[1][6], DataValue::from("\"x\""));
+}
+
+#[test]
+#[cfg(feature = "parquet")]
+fn parquet_export_and_read() {
+    let db = DbInstance::default();
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("out.parquet");
+    db.export_parquet(
+        "?[x, y, z] := x in int_range(3), y = if(x == 1, null, to_string(x)), z = x * 0.5",
+        Default::default(),
+        &path,
+    )
+    .unwrap();
+    assert!(db
+        .export_parquet(":create t {a}", Default::default(), &path)
+        .is_err());
+
+    let read = |opts: &str| {
+        db.run_default(&format!(
+            "?[a, b] <~ ParquetReader(url: 'file://{}', {})",
+            path.display(),
+            opts
+        ))
+    };
+    // columns are projected in the order asked for
+    let res = read("columns: ['y', 'x']").unwrap().into_json();
+    assert_eq!(res["rows"], json!([[null, 1], ["0", 0], ["2", 2]]));
+    let res = read("columns: ['z', 'x'], row_groups: [0]")
+        .unwrap()
+        .into_json();
+    assert_eq!(res["rows"], json!([[0.0, 0], [0.5, 1], [1.0, 2]]));
+    assert!(read("columns: ['y', 'x'], row_groups: [1]").is_err());
+    assert!(read("columns: ['y', 'w']").is_err());
+}
+
+#[test]
+#[cfg(feature = "storage-sqlite")]
+fn import_from_sqlite() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("import.db");
+    {
+        let conn = sqlite::Connection::open(&path).unwrap();
+        conn.execute(
+            "
+            create table person (id integer primary key, name text not null, score real);
+            insert into person values (1, 'alice', 1.5), (2, 'bob', null);
+            create table tag (label text);
+            insert into tag values ('x'), ('y');
+            create table log (rowid text, msg text);
+            insert into log values ('a', 'hello');
+            create table code (name text primary key, v int);
+            insert into code values (null, 1), ('a', 2);
+            create table num (n integer primary key);
+            with recursive c(x) as (select 1 union all select x + 1 from c where x < 25000)
+            insert into num select x from c;
+            ",
+        )
+        .unwrap();
+    }
+    let db = DbInstance::default();
+    db.import_from_sqlite(&path, &[]).unwrap();
+    let res = db
+        .run_default("?[id, name, score] := *person{id, name, score}")
+        .unwrap();
+    assert_eq!(
+        res.into_json()["rows"],
+        json!([[1, "alice", 1.5], [2, "bob", null]])
+    );
+    let res = db
+        .run_default("?[rowid, label] := *tag{rowid, label}")
+        .unwrap();
+    assert_eq!(res.into_json()["rows"], json!([[1, "x"], [2, "y"]]));
+    // the real `rowid` column shadows the rowid
+    let res = db
+        .run_default("?[rowid_, rowid, msg] := *log{rowid_, rowid, msg}")
+        .unwrap();
+    assert_eq!(res.into_json()["rows"], json!([[1, "a", "hello"]]));
+    let res = db.run_default("?[name, v] := *code{name, v}").unwrap();
+    assert_eq!(res.into_json()["rows"], json!([[null, 1], ["a", 2]]));
+    let res = db.run_default("?[count(n)] := *num{n}").unwrap();
+    assert_eq!(res.into_json()["rows"], json!([[25000]]));
+
+    // a failing table rolls back the tables imported before it
+    let path = dir.path().join("bad.db");
+    {
+        let conn = sqlite::Connection::open(&path).unwrap();
+        conn.execute(
+            "
+            create table a_good (id integer primary key);
+            insert into a_good values (1);
+            create table b_bad (id integer primary key, v integer);
+            insert into b_bad values (1, 'not a number');
+            ",
+        )
+        .unwrap();
+    }
+    let db = DbInstance::default();
+    assert!(db.import_from_sqlite(&path, &[]).is_err());
+    assert!(db.run_default("?[id] := *a_good{id}").is_err());
+    assert!(db.run_default("?[id] := *b_bad{id}").is_err());
+    db.import_from_sqlite(&path, &["a_good".to_string()])
+        .unwrap();
+    let res = db.run_default("?[id] := *a_good{id}").unwrap();
+    assert_eq!(res.into_json()["rows"], json!([[1]]));
+}
+
+#[test]
+fn dump_and_restore() {
+    let db = DbInstance::default();
+    db.run_default(
+        r"
+        {:create a {k: Int => v: Any, b: Bytes?, n: Float default 0.5, t: Float default now()}}
+        {?[k, v, b] <- [[1, 1.0, null], [2, [2, 'x'], decode_base64('AAE=')]] :put a {k => v, b}}
+        {::index create a:by_n {n}}
+        {::access_level protected a}
+        {:create s {k: String => f: Float}}
+        {?[k] <- [[1]] :create many {k: Int}}
+        {?[k] := k in int_range(2500) :put many {k}}
+    ",
+    )
+    .unwrap();
+    let strings = [
+        "quote \" and back\\slash",
+        "line\nbreak\r\ttab",
+        "nul \0 and \x1f",
+        "emoji 😀",
+        "中文 é",
+    ];
+    let floats = [f64::NAN, f64::INFINITY, f64::NEG_INFINITY, -0.0, 1.0];
+    let rows = strings
+        .iter()
+        .zip(floats)
+        .map(|(s, f)| DataValue::List(vec![DataValue::from(*s), DataValue::from(f)]))
+        .collect_vec();
+    db.run_script(
+        "?[k, f] <- $rows :put s {k => f}",
+        BTreeMap::from([("rows".into(), DataValue::List(rows))]),
+        ScriptMutability::Mutable,
+    )
+    .unwrap();
+    let mut dump = vec![];
+    db.dump(&mut dump).unwrap();
+    assert!(dump.is_ascii());
+    assert!(String::from_utf8_lossy(&dump).contains("default now()"));
+
+    let restored = DbInstance::default();
+    restored.restore_dump(&dump[..]).unwrap();
+    for rel in ["a", "s", "many"] {
+        assert_eq!(
+            db.export_relations([rel].iter()).unwrap()[rel].rows,
+            restored.export_relations([rel].iter()).unwrap()[rel].rows
+        );
+    }
+    let res = restored.run_default("?[k] := *s{k, f}, is_nan(f)").unwrap();
+    assert_eq!(
+        res.into_json()["rows"],
+        json!([["quote \" and back\\slash"]])
+    );
+    let res = restored.run_default("?[count(k)] := *many{k}").unwrap();
+    assert_eq!(res.into_json()["rows"], json!([[2500]]));
+    let res = restored.run_default("::indices a").unwrap();
+    assert_eq!(res.rows.len(), 1);
+    assert!(restored.run_default("::remove a").is_err());
+    assert!(restored.restore_dump(&dump[..]).is_err());
+    // unpaired surrogates are not characters
+    assert!(db.run_default(r"?[s] <- [['\ud83d']]").is_err());
+    assert!(db.run_default(r"?[s] <- [['\ude00\ud83d']]").is_err());
+}
+
+#[test]
+fn schema_script_round_trip() {
+    let db = DbInstance::default();
+    db.run_default(
+        r"
+        {:create a {k: Int => v: String?}}
+        {?[k, v] <- [[1, 'x']] :put a {k => v}}
+        {::index create a:by_v {v}}
+    ",
+    )
+    .unwrap();
+    let script = db.schema_script().unwrap();
+    assert_eq!(
+        script,
+        "{:create a {k: Int => v: String?}}\n{::index create a:by_v {v}}\n"
+    );
+
+    let other = DbInstance::default();
+    other.apply_schema_script(&script).unwrap();
+    assert_eq!(other.schema_script().unwrap(), script);
+    let res = other.run_default("?[k] := *a{k}").unwrap();
+    assert!(res.rows.is_empty());
+    assert!(other.apply_schema_script(&script).is_err());
+
+    // defaults calling functions without arguments
+    let db = DbInstance::default();
+    db.run_default(
+        ":create b {k: Int => ts: Float default now(), id: Uuid default rand_uuid_v1()}",
+    )
+    .unwrap();
+    let script = db.schema_script().unwrap();
+    assert_eq!(
+        script,
+        "{:create b {k: Int => ts: Float default now(), id: Uuid default rand_uuid_v1()}}\n"
+    );
+    let other = DbInstance::default();
+    other.apply_schema_script(&script).unwrap();
+    assert_eq!(other.schema_script().unwrap(), script);
+    other.run_default("?[k] <- [[1]] :put b {k}").unwrap();
+    let res = other
+        .run_default("?[k] := *b{k, ts, id}, ts > 0, is_uuid(id)")
+        .unwrap();
+    assert_eq!(res.into_json()["rows"], json!([[1]]));
+}
+
+#[test]
+fn graph_export() {
+    let db = DbInstance::default();
+    let nodes = db
+        .run_default("?[id, label] <- [['a', 'Alice'], ['b', null]]")
+        .unwrap();
+    let edges = db
+        .run_default("?[src, dst, weight] <- [['a', 'b', 2], ['b', 'c', 1]]")
+        .unwrap();
+    let dot = edges.to_dot(Some(&nodes), true).unwrap();
+    assert_eq!(
+        dot,
+        "digraph {\n  \"a\" [\"label\"=\"Alice\"];\n  \"b\";\n  \"a\" -> \"b\" [\"weight\"=\"2\"];\n  \"b\" -> \"c\" [\"weight\"=\"1\"];\n}\n"
+    );
+    let graphml = edges.to_graphml(Some(&nodes), true).unwrap();
+    assert!(graphml
+        .contains("<key id=\"edge_0\" for=\"edge\" attr.name=\"weight\" attr.type=\"long\"/>"));
+    assert!(graphml.contains("<node id=\"a\"><data key=\"node_0\">Alice</data></node>"));
+    assert!(graphml.contains("<node id=\"c\"/>"));
+    assert!(
+        graphml.contains("<edge source=\"a\" target=\"b\"><data key=\"edge_0\">2</data></edge>")
+    );
+    assert!(nodes.to_dot(None, false).is_ok());
+
+    // distinct values with the same text get distinct ids
+    let edges = db
+        .run_default("?[src, dst, w] <- [[1, '1', 1.5], ['1', 2, to_float('NAN')]]")
+        .unwrap();
+    let graphml = edges.to_graphml(None, true).unwrap();
+    assert!(graphml.contains("<edge source=\"1\" target=\"1#2\">"));
+    assert!(graphml.contains("<edge source=\"1#2\" target=\"2\">"));
+    assert!(graphml.contains("attr.type=\"double\""));
+    assert!(graphml.contains("<data key=\"edge_0\">NaN</data>"));
+    // control characters cannot appear in XML
+    let nodes = db
+        .run_default("?[id, note] <- [[1, 'a\\u0001b\\nc']]")
+        .unwrap();
+    let graphml = edges.to_graphml(Some(&nodes), true).unwrap();
+    assert!(graphml.contains("<data key=\"node_0\">a\u{fffd}b&#xA;c</data>"));
+    // a node may only have one row
+    let nodes = db
+        .run_default("?[id, note] <- [[1, 'x'], [1, 'y']]")
+        .unwrap();
+    assert!(edges.to_graphml(Some(&nodes), true).is_err());
+    assert!(edges.to_dot(Some(&nodes), true).is_err());
+    // rows must match the headers
+    let bad = NamedRows::new(
+        vec!["src".to_string(), "dst".to_string()],
+        vec![vec![DataValue::from(1)]],
+    );
+    assert!(bad.to_dot(None, true).is_err());
+}
+
+#[test]
+fn type_mismatch_names_column() {
+    let db = DbInstance::default();
+    db.run_default(":create a {k: Int => v: Int}").unwrap();
+    let err = db
+        .run_default("?[k, v] <- [[1, 'x']] :put a {k => v}")
+        .unwrap_err();
+    // the innermost errors are wrapped in the context of the relation
+    let msg = err.chain().map(|e| e.to_string()).join("\n");
+    assert!(msg.contains("column 'v'"));
+    assert!(msg.contains("relation 'a'"));
+    assert!(msg.contains("Int"));
+    assert!(msg.contains("\"x\""));
+}
+
+#[test]
+fn key_conflict_names_key_columns() {
+    let db = DbInstance::default();
+    db.run_default(":create a {k: Int, l: String => v: Int}")
+        .unwrap();
+    db.run_default("?[k, l, v] <- [[1, 'x', 2]] :put a {k, l => v}")
+        .unwrap();
+    let err = db
+        .run_default("?[k, l, v] <- [[1, 'x', 3]] :insert a {k, l => v}")
+        .unwrap_err();
+    assert!(err.chain().any(|e| e.to_string()
+        == r#"Assertion failure for key {k: 1, l: "x"} of relation a: key exists in database"#));
+}
+
+#[test]
+fn duplicate_named_field() {
+    let db = DbInstance::default();
+    db.run_default(":create a {k: Int => v: Int}").unwrap();
+    let err = db.run_default("?[x, y] := *a{k: x, k: y}").unwrap_err();
+    assert_eq!(
+        err.code().unwrap().to_string(),
+        "parser::duplicate_named_field"
+    );
+    let err = db.run_default("?[k, y] := *a{k, k: y}").unwrap_err();
+    assert_eq!(
+        err.code().unwrap().to_string(),
+        "parser::duplicate_named_field"
+    );
+    let err = db.run_default(":create b {k: Int, k: Int}").unwrap_err();
+    assert_eq!(err.code().unwrap().to_string(), "parser::dup_name_in_cols");
+}
+
+#[test]
+fn missing_columns_listed() {
+    let db = DbInstance::default();
+    db.run_default(":create a {k: Int, l: Int => v: Int, w: Int default 0, x: Int?}")
+        .unwrap();
+    let err = db.run_default("?[k] <- [[1]] :put a {k}").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "cannot write into relation 'a': no value given for required columns 'l', 'v', 'x'"
+    );
+    assert_eq!(
+        err.code().unwrap().to_string(),
+        "eval::required_col_not_provided"
+    );
+    db.run_default("?[k, l, v, x] <- [[1, 2, 3, null]] :put a {k, l => v, x}")
+        .unwrap();
+    let res = db.run_default("?[w] := *a{w}").unwrap();
+    assert_eq!(res.into_json()["rows"], json!([[0]]));
+
+    // lenient writes fill nullable columns with null, other columns are still required
+    let err = db
+        .run_default("?[k] <- [[2]] :put a {k} :lenient")
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "cannot write into relation 'a': no value given for required columns 'l', 'v'"
+    );
+    db.run_default("?[k, l, v] <- [[2, 3, 4]] :put a {k, l => v} :lenient")
+        .unwrap();
+    let res = db.run_default("?[w, x] := *a{k: 2, w, x}").unwrap();
+    assert_eq!(res.into_json()["rows"], json!([[0, null]]));
+}
+
+#[test]
+fn identifiers_are_nfc_normalized() {
+    let db = DbInstance::default();
+    // composed and decomposed forms of 'é'
+    db.run_default(":create caf\u{e9} {n\u{e9}: Int}").unwrap();
+    db.run_default("?[ne\u{301}] <- [[1]] :put cafe\u{301} {ne\u{301}}")
+        .unwrap();
+    let res = db.run_default("?[x] := *caf\u{e9}{n\u{e9}: x}").unwrap();
+    assert_eq!(res.into_json()["rows"], json!([[1]]));
+    let res = db.run_default("::columns cafe\u{301}").unwrap();
+    assert_eq!(res.rows[0][0], DataValue::from("n\u{e9}"));
+
+    // names passed to the API are normalized as well
+    let exported = db.export_relations(["cafe\u{301}"].iter()).unwrap();
+    assert_eq!(exported["cafe\u{301}"].rows, vec![vec![DataValue::from(1)]]);
+    let rows = NamedRows::new(
+        vec!["ne\u{301}".to_string()],
+        vec![vec![DataValue::from(2)]],
+    );
+    db.import_relations(BTreeMap::from([("cafe\u{301}".to_string(), rows)]))
+        .unwrap();
+    let res = db.run_default("?[x] := *caf\u{e9}{n\u{e9}: x}").unwrap();
+    assert_eq!(res.into_json()["rows"], json!([[1], [2]]));
+}
+
+#[test]
+fn parallel_branches_match_serial() {
+    let db = DbInstance::default();
+    db.run_default(":create edge {fr: Int, to: Int}").unwrap();
+    db.run_default(
+        r"?[fr, to] := fr in int_range(200), to = (fr * 7 + 3) % 200
+        ?[fr, to] := fr in int_range(200), to = (fr * 13 + 1) % 200
+        :put edge {fr, to}",
+    )
+    .unwrap();
+    let script = r"
+        reach[fr, to] := *edge{fr, to}
+        reach[fr, to] := reach[fr, mid], *edge{fr: mid, to}
+        reach[fr, to] := reach[fr, mid], reach[mid, to], fr < 5
+        ?[fr, to] := reach[fr, to], fr < 10
+        ?[fr, to] := fr = 199, to = -1
+        ?[fr, to] := *edge{fr, to}, to < 3
+    ";
+
+    let serial = db.run_default(script).unwrap().into_json()["rows"].clone();
+    let parallel = db
+        .run_default(&format!("{script} :parallel true"))
+        .unwrap()
+        .into_json()["rows"]
+        .clone();
+    assert_eq!(parallel, serial);
+    assert!(parallel.as_array().unwrap().len() > 200);
+    db.set_parallel_branches(true);
+    let parallel = db.run_default(script).unwrap().into_json()["rows"].clone();
+    assert_eq!(parallel, serial);
+    let serial = db
+        .run_default(&format!("{script} :parallel false"))
+        .unwrap()
+        .into_json()["rows"]
+        .clone();
+    assert_eq!(parallel, serial);
+}
+
+#[test]
+fn run_script_with_sink() {
+    #[derive(Default)]
+    struct Collect {
+        headers: Vec<String>,
+        rows: Vec<Tuple>,
+        fail_after: Option<usize>,
+    }
+
+    impl RowSink for Collect {
+        fn headers(&mut self, headers: &[String]) -> miette::Result<()> {
+            self.headers = headers.to_vec();
+            Ok(())
+        }
+        fn row(&mut self, row: Tuple) -> miette::Result<()> {
+            if self.fail_after == Some(self.rows.len()) {
+                miette::bail!("sink is full")
+            }
+            self.rows.push(row);
+            Ok(())
+        }
+    }
+
+    let db = DbInstance::default();
+    let mut sink = Collect::default();
+    db.run_script_with_sink(
+        "?[x, y] := x in [3, 1, 2], y = x * 10",
+        Default::default(),
+        ScriptMutability::Immutable,
+        &mut sink,
+    )
+    .unwrap();
+    assert_eq!(sink.headers, vec!["x", "y"]);
+    assert_eq!(
+        NamedRows::new(sink.headers, sink.rows).into_json()["rows"],
+        json!([[1, 10], [2, 20], [3, 30]])
+    );
+    assert_eq!(db.metrics().rows_returned, 3);
+
+    // an error from the sink aborts the query before its mutations are committed
+    db.run_default(":create t {a}").unwrap();
+    let mut sink = Collect {
+        fail_after: Some(0),
+        ..Default::default()
+    };
+    assert!(db
+        .run_script_with_sink(
+            "?[a] <- [[1], [2]] :put t {a} :returning",
+            Default::default(),
+            ScriptMutability::Mutable,
+            &mut sink,
+        )
+        .is_err());
+    let res = db.run_default("?[a] := *t[a]").unwrap();
+    assert!(res.rows.is_empty());
+
+    // system ops are handed over once done
+    let mut sink = Collect::default();
+    db.run_script_with_sink(
+        "::relations",
+        Default::default(),
+        ScriptMutability::Immutable,
+        &mut sink,
+    )
+    .unwrap();
+    assert_eq!(sink.headers[0], "name");
+    assert_eq!(sink.rows.len(), 1);
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn run_script_async_and_stream() {
+    let db = DbInstance::default();
+    let res = db
+        .run_script_async(
+            "?[x] := x in [1, 2, 3]".to_string(),
+            Default::default(),
+            ScriptMutability::Immutable,
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.into_json()["rows"], json!([[1], [2], [3]]));
+
+    let (headers, mut rows) = db
+        .run_script_stream(
+            "?[x] := x in int_range(100)".to_string(),
+            Default::default(),
+            ScriptMutability::Immutable,
+            8,
+        )
+        .await
+        .unwrap();
+    assert_eq!(headers, vec!["x"]);
+    let mut n = 0;
+    while let Some(row) = rows.recv().await {
+        assert_eq!(row.unwrap(), vec![DataValue::from(n)]);
+        n += 1;
+    }
+    assert_eq!(n, 100);
+
+    // dropping the receiver stops the query
+    let (_, mut rows) = db
+        .run_script_stream(
+            "?[x] := x in int_range(100000)".to_string(),
+            Default::default(),
+            ScriptMutability::Immutable,
+            1,
+        )
+        .await
+        .unwrap();
+    assert!(rows.recv().await.unwrap().is_ok());
+    drop(rows);
+    for _ in 0..100 {
+        if db.metrics().running_queries == 0 {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    assert_eq!(db.metrics().running_queries, 0);
+
+    assert!(db
+        .run_script_stream(
+            "?[x] := nope[x]".to_string(),
+            Default::default(),
+            ScriptMutability::Immutable,
+            8,
+        )
+        .await
+        .is_err());
+}
@@ -6,7 +6,8 @@
  * You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 
-use std::sync::atomic::{AtomicU32, AtomicU64};
+use std::mem;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
 use std::sync::Arc;
 
 use miette::{bail, Result};
@@ -17,6 +18,8 @@ use crate::data::value::DataValue;
 use crate::fts::TokenizerCache;
 use crate::{CallbackOp, NamedRows};
 use crate::runtime::callback::CallbackCollector;
+use crate::query::profile::EvalProfile;
+use crate::runtime::db::MetricsCounters;
 use crate::runtime::relation::RelationId;
 use crate::storage::temp::TempTx;
 use crate::storage::StoreTx;
@@ -27,11 +30,25 @@ pub struct SessionTx<'a> {
     pub(crate) relation_store_id: Arc<AtomicU64>,
     pub(crate) temp_store_id: AtomicU32,
     pub(crate) tokenizers: Arc<TokenizerCache>,
+    /// whether the branches of a rule may be evaluated in parallel
+    pub(crate) parallel_branches: bool,
+    /// set while the transaction runs a query for `::explain analyze`
+    pub(crate) profile: Option<Arc<EvalProfile>>,
+    pub(crate) metrics: Arc<MetricsCounters>,
+    /// rows written to stored relations, added to `metrics` once the transaction commits
+    pub(crate) rows_written: u64,
+    /// rows removed from stored relations, added to `metrics` once the transaction commits
+    pub(crate) rows_removed: u64,
+    /// rows read from stored relations, added to `metrics` when the transaction is dropped
+    pub(crate) rows_read: AtomicU64,
 }
 
-pub const CURRENT_STORAGE_VERSION: [u8; 1] = [0x00];
+/// Version 1 stores names in the catalog in Unicode normalization form C.
+pub const CURRENT_STORAGE_VERSION: [u8; 1] = [0x01];
+/// Stores of this version are migrated to the current one when opened.
+const UNNORMALIZED_STORAGE_VERSION: [u8; 1] = [0x00];
 
-fn storage_version_key() -> Vec<u8> {
+pub(crate) fn storage_version_key() -> Vec<u8> {
     let storage_version_tuple = vec![DataValue::Null, DataValue::from("STORAGE_VERSION")];
     storage_version_tuple.encode_as_key(RelationId::SYSTEM)
 }
@@ -113,6 +130,13 @@ impl<'a> SessionTx<'a> {
                     None => {
                         bail!("Storage is used but un-versioned, probably created by an ancient version of Cozo.")
                     }
+                    Some(v) if v == UNNORMALIZED_STORAGE_VERSION => {
+                        for conflict in self.normalize_catalog_names()? {
+                            log::warn!("{conflict}: rename it to reach it");
+                        }
+                        self.store_tx
+                            .put(&storage_version_key, &CURRENT_STORAGE_VERSION)?;
+                    }
                     Some(v) => {
                         if v != CURRENT_STORAGE_VERSION {
                             bail!(
@@ -129,8 +153,27 @@ impl<'a> SessionTx<'a> {
         Ok(ret)
     }
 
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
     pub fn commit_tx(&mut self) -> Result<()> {
-        self.store_tx.commit()?;
+        if let Err(err) = self.store_tx.commit() {
+            self.metrics.failed_commits.fetch_add(1, Ordering::Relaxed);
+            return Err(err);
+        }
+        self.metrics
+            .rows_written
+            .fetch_add(mem::take(&mut self.rows_written), Ordering::Relaxed);
+        self.metrics
+            .rows_removed
+            .fetch_add(mem::take(&mut self.rows_removed), Ordering::Relaxed);
         Ok(())
     }
 }
+
+impl Drop for SessionTx<'_> {
+    fn drop(&mut self) {
+        // the rows were read whether the transaction commits or not
+        self.metrics
+            .rows_read
+            .fetch_add(*self.rows_read.get_mut(), Ordering::Relaxed);
+    }
+}
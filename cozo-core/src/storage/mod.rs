@@ -6,6 +6,8 @@
  * You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 
+use std::path::Path;
+
 use itertools::Itertools;
 use miette::Result;
 
@@ -23,6 +25,8 @@ pub(crate) mod sqlite;
 pub(crate) mod temp;
 #[cfg(feature = "storage-tikv")]
 pub(crate) mod tikv;
+#[cfg(feature = "tracing")]
+pub(crate) mod traced;
 // pub(crate) mod re;
 
 /// Swappable storage trait for Cozo's storage engine
@@ -33,6 +37,11 @@ pub trait Storage<'s>: Send + Sync + Clone {
     /// Returns a string that identifies the storage kind
     fn storage_kind(&self) -> &'static str;
 
+    /// Returns where the data is stored on disk, if it is stored on the local disk at all
+    fn path(&self) -> Option<&Path> {
+        None
+    }
+
     /// Create a transaction object. Write ops will only be called when `write == true`.
     fn transact(&'s self, write: bool) -> Result<Self::Tx>;
 
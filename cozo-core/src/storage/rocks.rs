@@ -105,7 +105,7 @@ pub fn new_cozo_rocksdb(path: impl AsRef<Path>) -> Result<Db<RocksDbStorage>> {
 
     let db = db_builder.build()?;
 
-    let ret = Db::new(RocksDbStorage::new(db))?;
+    let ret = Db::new(RocksDbStorage::new(db, path_buf))?;
     ret.initialize()?;
     Ok(ret)
 }
@@ -114,11 +114,12 @@ pub fn new_cozo_rocksdb(path: impl AsRef<Path>) -> Result<Db<RocksDbStorage>> {
 #[derive(Clone)]
 pub struct RocksDbStorage {
     db: RocksDb,
+    path: PathBuf,
 }
 
 impl RocksDbStorage {
-    pub(crate) fn new(db: RocksDb) -> Self {
-        Self { db }
+    pub(crate) fn new(db: RocksDb, path: PathBuf) -> Self {
+        Self { db, path }
     }
 }
 
@@ -129,6 +130,10 @@ impl Storage<'_> for RocksDbStorage {
         "rocksdb"
     }
 
+    fn path(&self) -> Option<&Path> {
+        Some(&self.path)
+    }
+
     fn transact(&self, _write: bool) -> Result<Self::Tx> {
         let db_tx = self.db.transact().set_snapshot(true).start();
         Ok(RocksDbTx { db_tx })
@@ -9,7 +9,7 @@
 use std::cmp::Ordering;
 use std::iter;
 use std::iter::Fuse;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use itertools::Itertools;
 use miette::{miette, IntoDiagnostic, Result};
@@ -25,8 +25,11 @@ use crate::utils::{swap_option_result, TempCollector};
 /// You should use [`new_cozo_rocksdb`](crate::new_cozo_rocksdb) or
 /// [`new_cozo_sqlite`](crate::new_cozo_sqlite) instead.
 pub fn new_cozo_sled(path: impl AsRef<Path>) -> Result<crate::Db<SledStorage>> {
-    let db = sled::open(path).into_diagnostic()?;
-    let ret = crate::Db::new(SledStorage { db })?;
+    let db = sled::open(&path).into_diagnostic()?;
+    let ret = crate::Db::new(SledStorage {
+        db,
+        path: path.as_ref().to_path_buf(),
+    })?;
 
     ret.initialize()?;
     Ok(ret)
@@ -36,6 +39,7 @@ pub fn new_cozo_sled(path: impl AsRef<Path>) -> Result<crate::Db<SledStorage>> {
 #[derive(Clone)]
 pub struct SledStorage {
     db: Db,
+    path: PathBuf,
 }
 
 const PUT_MARKER: u8 = 1;
@@ -48,6 +52,10 @@ impl Storage<'_> for SledStorage {
         "sled"
     }
 
+    fn path(&self) -> Option<&Path> {
+        Some(&self.path)
+    }
+
     fn transact(&self, _write: bool) -> Result<Self::Tx> {
         Ok(SledTx {
             db: self.db.clone(),
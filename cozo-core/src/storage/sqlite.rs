@@ -114,6 +114,10 @@ impl<'s> Storage<'s> for SqliteStorage {
     fn storage_kind(&self) -> &'static str {
         "sqlite"
     }
+
+    fn path(&self) -> Option<&Path> {
+        Some(&self.name)
+    }
 }
 
 pub struct SqliteTx<'a> {
@@ -45,6 +45,7 @@ impl<'s> Storage<'s> for TempStorage {
     }
 }
 
+#[derive(Default)]
 pub(crate) struct TempTx {
     store: BTreeMap<Vec<u8>, Vec<u8>>,
 }
@@ -0,0 +1,120 @@
+/*
+ * Copyright 2022, The Cozo Project Authors.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public License, v. 2.0.
+ * If a copy of the MPL was not distributed with this file,
+ * You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use miette::Result;
+use tracing::{trace_span, Span};
+
+use crate::data::tuple::Tuple;
+use crate::data::value::ValidityTs;
+use crate::storage::StoreTx;
+
+/// Passes everything through to the transaction it wraps, in `trace` level spans.
+/// Scans are in a span that is entered each time the scan is advanced.
+pub(crate) struct TracedStoreTx<T>(pub(crate) T);
+
+fn traced_iter<'a, I: Iterator + 'a>(
+    span: Span,
+    make_iter: impl FnOnce() -> I,
+) -> Box<dyn Iterator<Item = I::Item> + 'a> {
+    let mut inner = span.in_scope(make_iter);
+    Box::new(std::iter::from_fn(move || span.in_scope(|| inner.next())))
+}
+
+impl<'s, T: StoreTx<'s>> StoreTx<'s> for TracedStoreTx<T> {
+    fn get(&self, key: &[u8], for_update: bool) -> Result<Option<Vec<u8>>> {
+        trace_span!("storage_get").in_scope(|| self.0.get(key, for_update))
+    }
+
+    fn multi_get(&self, keys: &[Vec<u8>], for_update: bool) -> Result<Vec<Option<Vec<u8>>>> {
+        trace_span!("storage_multi_get", keys = keys.len())
+            .in_scope(|| self.0.multi_get(keys, for_update))
+    }
+
+    fn put(&mut self, key: &[u8], val: &[u8]) -> Result<()> {
+        trace_span!("storage_put").in_scope(|| self.0.put(key, val))
+    }
+
+    fn supports_par_put(&self) -> bool {
+        self.0.supports_par_put()
+    }
+
+    fn par_put(&self, key: &[u8], val: &[u8]) -> Result<()> {
+        trace_span!("storage_put").in_scope(|| self.0.par_put(key, val))
+    }
+
+    fn del(&mut self, key: &[u8]) -> Result<()> {
+        trace_span!("storage_del").in_scope(|| self.0.del(key))
+    }
+
+    fn par_del(&self, key: &[u8]) -> Result<()> {
+        trace_span!("storage_del").in_scope(|| self.0.par_del(key))
+    }
+
+    fn del_range_from_persisted(&mut self, lower: &[u8], upper: &[u8]) -> Result<()> {
+        trace_span!("storage_del_range").in_scope(|| self.0.del_range_from_persisted(lower, upper))
+    }
+
+    fn exists(&self, key: &[u8], for_update: bool) -> Result<bool> {
+        trace_span!("storage_exists").in_scope(|| self.0.exists(key, for_update))
+    }
+
+    fn commit(&mut self) -> Result<()> {
+        trace_span!("storage_commit").in_scope(|| self.0.commit())
+    }
+
+    fn range_scan_tuple<'a>(
+        &'a self,
+        lower: &[u8],
+        upper: &[u8],
+    ) -> Box<dyn Iterator<Item = Result<Tuple>> + 'a>
+    where
+        's: 'a,
+    {
+        traced_iter(trace_span!("storage_scan"), || {
+            self.0.range_scan_tuple(lower, upper)
+        })
+    }
+
+    fn range_skip_scan_tuple<'a>(
+        &'a self,
+        lower: &[u8],
+        upper: &[u8],
+        valid_at: ValidityTs,
+    ) -> Box<dyn Iterator<Item = Result<Tuple>> + 'a> {
+        traced_iter(trace_span!("storage_skip_scan"), || {
+            self.0.range_skip_scan_tuple(lower, upper, valid_at)
+        })
+    }
+
+    fn range_scan<'a>(
+        &'a self,
+        lower: &[u8],
+        upper: &[u8],
+    ) -> Box<dyn Iterator<Item = Result<(Vec<u8>, Vec<u8>)>> + 'a>
+    where
+        's: 'a,
+    {
+        traced_iter(trace_span!("storage_scan"), || {
+            self.0.range_scan(lower, upper)
+        })
+    }
+
+    fn range_count<'a>(&'a self, lower: &[u8], upper: &[u8]) -> Result<usize>
+    where
+        's: 'a,
+    {
+        trace_span!("storage_count").in_scope(|| self.0.range_count(lower, upper))
+    }
+
+    fn total_scan<'a>(&'a self) -> Box<dyn Iterator<Item = Result<(Vec<u8>, Vec<u8>)>> + 'a>
+    where
+        's: 'a,
+    {
+        traced_iter(trace_span!("storage_scan"), || self.0.total_scan())
+    }
+}